@@ -21,6 +21,10 @@ import {
   DialogTitle,
   DialogContent,
   DialogActions,
+  MenuItem,
+  Checkbox,
+  FormControlLabel,
+  Chip,
 } from "@mui/material";
 import MenuIcon from "@mui/icons-material/Menu";
 import CloseIcon from "@mui/icons-material/Close";
@@ -31,6 +35,35 @@ import ExitToAppIcon from "@mui/icons-material/ExitToApp";
 import useMediaQuery from "@mui/material/useMediaQuery";
 import { useTheme } from "@mui/material/styles";
 
+// 対応しているプロトコルバージョンと機能
+const PROTOCOL_VERSION = 2;
+const CAPABILITIES = ["history", "typing", "reactions", "read_receipts", "direct_messages"];
+// 切断してから再開を試みるまでの時間
+const RECONNECT_DELAY_MS = 2000;
+// 入力中に Typing を送り直す間隔
+const TYPING_INTERVAL_MS = 2000;
+
+// 招待・モデレーション用のコマンドと対応するメッセージ
+const MODERATION_COMMANDS: Record<string, string> = {
+  "/invite": "Invite",
+  "/mod": "GrantModerator",
+  "/kick": "Kick",
+  "/ban": "Ban",
+  "/unban": "Unban",
+  "/mute": "Mute",
+  "/unmute": "Unmute",
+};
+
+// ログイン・登録の失敗 (エラーコードごとの表示)
+const LOGIN_ERRORS: Record<string, string> = {
+  invalid_credentials: "ユーザー名またはパスワードが違います",
+  username_taken: "このユーザー名は使われているか、登録済みです",
+  weak_password: "パスワードは8文字以上にしてください",
+  account_in_use: "このアカウントは別の画面でログイン中です",
+  guests_disabled:
+    "ゲストではログインできません。アカウントを登録してください",
+};
+
 interface ClientMessage {
   type: string;
   [key: string]: any;
@@ -41,19 +74,47 @@ interface ServerMessage {
   [key: string]: any;
 }
 
+interface Reaction {
+  emoji: string;
+  count: number;
+  users: string[];
+}
+
 interface ChatMessage {
+  id: string;
+  seq?: number;
   sender: string;
   content: string;
   room_name: string;
   timestamp: string;
+  edited_at?: string | null;
+  deleted?: boolean;
+  reply_to?: string | null;
+  reactions?: Reaction[];
 }
 
+interface DirectChatMessage {
+  sender: string;
+  content: string;
+  timestamp: string;
+}
+
+// 表示中のメッセージ (DM にはルームのメッセージの項目がない)
+type VisibleMessage = DirectChatMessage & Partial<ChatMessage>;
+
+// GetThread の応答 (履歴と同じ形でルーム名を持たない)
+type ThreadMessage = Omit<ChatMessage, "room_name">;
+
 export default function page() {
   const [username, setUsername] = useState<string>("");
   const [userId, setUserId] = useState<string>("");
   const [socket, setSocket] = useState<WebSocket | null>(null);
   const [connected, setConnected] = useState<boolean>(false);
-  const [messages, setMessages] = useState<ChatMessage[]>([]);
+  // 参加中のルームごとのメッセージ (表示するルームを切り替えても残る)
+  const [roomMessages, setRoomMessages] = useState<
+    Record<string, ChatMessage[]>
+  >({ general: [] });
+  const [unreadRooms, setUnreadRooms] = useState<Record<string, number>>({});
   const [messageInput, setMessageInput] = useState<string>("");
   const [rooms, setRooms] = useState<string[]>(["general"]);
   const [currentRoom, setCurrentRoom] = useState<string>("general");
@@ -62,9 +123,49 @@ export default function page() {
   const [createRoomDialogOpen, setCreateRoomDialogOpen] =
     useState<boolean>(false);
   const [newRoomName, setNewRoomName] = useState<string>("");
+  const [newRoomVisibility, setNewRoomVisibility] = useState<string>("public");
+  const [newRoomPassword, setNewRoomPassword] = useState<string>("");
+  const [newRoomHidden, setNewRoomHidden] = useState<boolean>(false);
   const [usersDrawerOpen, setUsersDrawerOpen] = useState<boolean>(false);
+  // 相手ごとの DM 履歴と未読数。dmPeer が設定されている間は DM ペインを表示する
+  const [directMessages, setDirectMessages] = useState<
+    Record<string, DirectChatMessage[]>
+  >({});
+  const [unreadDirect, setUnreadDirect] = useState<Record<string, number>>({});
+  const [dmPeer, setDmPeer] = useState<string | null>(null);
+  // 返信しようとしているメッセージと、表示中のスレッド
+  const [replyTo, setReplyTo] = useState<ChatMessage | null>(null);
+  const [thread, setThread] = useState<{
+    root: ThreadMessage | null; // 履歴から消えていれば null
+    replies: ThreadMessage[];
+  } | null>(null);
+  // ルームごとの入力中のユーザー
+  const [typingUsers, setTypingUsers] = useState<Record<string, string[]>>(
+    {}
+  );
+  // ルームごとの他のメンバーの既読位置 (ReadBy)
+  const [readBy, setReadBy] = useState<
+    Record<string, Record<string, number>>
+  >({});
 
   const messagesEndRef = useRef<HTMLDivElement>(null);
+  // WebSocketのハンドラから最新の値を参照するためのref
+  const socketRef = useRef<WebSocket | null>(null);
+  const currentRoomRef = useRef<string>("general");
+  const joinedRoomsRef = useRef<string[]>(["general"]);
+  const dmPeerRef = useRef<string | null>(null);
+  const loggedInRef = useRef<boolean>(false);
+  // 参加を要求中のルーム (パスワードを求められたら入力して送り直す)
+  const pendingJoinRef = useRef<string | null>(null);
+  // セッションの再開用 (Welcome のトークンと、最後に受け取ったメッセージの通し番号)
+  const sessionTokenRef = useRef<string | null>(null);
+  const lastSeqRef = useRef<number>(0);
+  // ログアウトや画面を離れるときは再接続しない
+  const closingRef = useRef<boolean>(false);
+  // 最後に Typing を送った時刻
+  const lastTypingSentRef = useRef<number>(0);
+  // ルームごとにサーバーへ送った既読位置
+  const readSeqsRef = useRef<Record<string, number>>({});
   const router = useRouter();
   const theme = useTheme();
   const isMobile = useMediaQuery(theme.breakpoints.down("md"));
@@ -76,52 +177,110 @@ export default function page() {
       return;
     }
 
+    // ログイン画面で入力されたパスワードは一度だけ使う
+    const password = sessionStorage.getItem("chat_password");
+    const register = sessionStorage.getItem("chat_register") === "1";
+    sessionStorage.removeItem("chat_password");
+    sessionStorage.removeItem("chat_register");
+
     setUsername(storedUsername);
-    connectWebSocket(storedUsername);
+    connectWebSocket(storedUsername, password, register);
 
     return () => {
-      // コンポーネントの案マウント時にWebSocket接続を閉じる
-      if (socket) {
-        socket.close();
-      }
+      // コンポーネントのアンマウント時にWebSocket接続を閉じる
+      closingRef.current = true;
+      socketRef.current?.close();
     };
   }, []);
 
   // メッセージが追加されたときに自動スクロール
   useEffect(() => {
     scrollToBottom();
-  }, [messages]);
+  }, [roomMessages, currentRoom, directMessages, dmPeer]);
+
+  // 表示中のルームの最新のメッセージまでを既読にする (同じ位置は送り直さない)
+  useEffect(() => {
+    if (dmPeer || !currentRoom) return;
+    const last = latestRoomMessage(currentRoom);
+    if (!last || (readSeqsRef.current[currentRoom] ?? -1) >= last.seq!) return;
+    readSeqsRef.current[currentRoom] = last.seq!;
+    sendMessage({ type: "MarkRead", room_name: currentRoom, seq: last.seq });
+  }, [roomMessages, currentRoom, dmPeer]);
 
   const scrollToBottom = () => {
     messagesEndRef.current?.scrollIntoView({ behavior: "smooth" });
   };
 
-  const connectWebSocket = (username: string) => {
+  const connectWebSocket = (
+    username: string,
+    password: string | null,
+    register: boolean
+  ) => {
+    openSocket((ws) => {
+      // パスワードがなければゲストとしてログインする
+      const loginMessage: ClientMessage = register
+        ? { type: "Register", username: username, password: password }
+        : { type: "Login", username: username };
+      if (!register && password) {
+        loginMessage.password = password;
+      }
+      ws.send(JSON.stringify(loginMessage));
+    });
+  };
+
+  // 切断したセッションを再開する (取りこぼしたメッセージはサーバーが送り直す)
+  const resumeSession = () => {
+    openSocket((ws) => {
+      const resumeMessage: ClientMessage = {
+        type: "Resume",
+        token: sessionTokenRef.current,
+        last_seen_seq: lastSeqRef.current,
+      };
+      ws.send(JSON.stringify(resumeMessage));
+    });
+  };
+
+  const openSocket = (onOpen: (ws: WebSocket) => void) => {
     const ws = new WebSocket("ws://localhost:8080/ws");
 
     ws.onopen = () => {
       setConnected(true);
 
-      const loginMessage: ClientMessage = {
-        type: "Login",
-        username: username,
+      // ハンドシェイク (サーバーは受信順に処理する)
+      const helloMessage: ClientMessage = {
+        type: "Hello",
+        protocol_version: PROTOCOL_VERSION,
+        capabilities: CAPABILITIES,
       };
-      ws.send(JSON.stringify(loginMessage));
+      ws.send(JSON.stringify(helloMessage));
+      onOpen(ws);
     };
 
     ws.onmessage = (event) => {
       const message: ServerMessage = JSON.parse(event.data);
+      if (message.session_seq) {
+        lastSeqRef.current = message.session_seq;
+      }
       handleServerMessage(message);
     };
 
     ws.onclose = () => {
       setConnected(false);
+      // ログイン済みなら少し待って再開する (サーバーは猶予の間セッションを残している)
+      if (
+        loggedInRef.current &&
+        sessionTokenRef.current &&
+        !closingRef.current
+      ) {
+        setTimeout(resumeSession, RECONNECT_DELAY_MS);
+      }
     };
 
     ws.onerror = (error) => {
       console.error("WebSocket error: ", error);
     };
 
+    socketRef.current = ws;
     setSocket(ws);
   };
 
@@ -130,40 +289,155 @@ export default function page() {
     console.log("Received message:", message);
 
     switch (message.type) {
+      case "Hello":
+        console.log(
+          `Server protocol v${message.protocol_version}:`,
+          message.capabilities
+        );
+        break;
+
       case "Welcome":
+        loggedInRef.current = true;
+        sessionTokenRef.current = message.session_token ?? null;
         setUserId(message.user_id);
         // ルーム一覧を取得
         sendMessage({ type: "ListRooms" });
         // 現在のルームのユーザー一覧を取得
-        sendMessage({ type: "ListUsers" });
+        sendMessage({ type: "ListUsers", room_name: currentRoomRef.current });
+        break;
+
+      case "Resumed":
+        setUserId(message.user_id);
+        setUsername(message.username);
+        console.info("Session resumed");
+        sendMessage({ type: "ListRooms" });
+        sendMessage({ type: "ListUsers", room_name: currentRoomRef.current });
         break;
 
       case "NewMessage":
-        if (message.room_name === currentRoom) {
-          // 現在表示中のルームにメッセージが送信された場合、表示する
-          setMessages((prevMessages) => [
-            ...prevMessages,
-            {
-              sender: message.sender,
-              content: message.content,
-              room_name: message.room_name,
-              timestamp: message.timestamp,
-            },
-          ]);
+        setRoomMessages((prev) =>
+          prev[message.room_name]
+            ? {
+                ...prev,
+                [message.room_name]: [
+                  ...prev[message.room_name],
+                  {
+                    id: message.id,
+                    seq: message.seq,
+                    sender: message.sender,
+                    content: message.content,
+                    room_name: message.room_name,
+                    timestamp: message.timestamp,
+                    reply_to: message.reply_to,
+                  },
+                ],
+              }
+            : prev
+        );
+        // 表示していないルームのメッセージは未読に数える
+        if (
+          message.room_name !== currentRoomRef.current ||
+          dmPeerRef.current !== null
+        ) {
+          setUnreadRooms((prev) => ({
+            ...prev,
+            [message.room_name]: (prev[message.room_name] ?? 0) + 1,
+          }));
+        }
+        break;
+
+      case "DirectMessage":
+        appendDirectMessage(message.from, {
+          sender: message.from,
+          content: message.content,
+          timestamp: message.timestamp,
+        });
+        if (dmPeerRef.current !== message.from) {
+          setUnreadDirect((prev) => ({
+            ...prev,
+            [message.from]: (prev[message.from] ?? 0) + 1,
+          }));
         }
         break;
 
+      case "NickChanged":
+        renameUser(message.old_username, message.new_username);
+        sendMessage({ type: "ListUsers", room_name: currentRoomRef.current });
+        break;
+
+      case "Thread":
+        setThread({ root: message.root ?? null, replies: message.replies });
+        break;
+
+      case "MessageEdited":
+        updateRoomMessage(message.room_name, message.message_id, (m) => ({
+          ...m,
+          content: message.content,
+          edited_at: message.edited_at,
+        }));
+        break;
+
+      case "MessageDeleted":
+        // 削除されたメッセージは中身を消して残す
+        updateRoomMessage(message.room_name, message.message_id, (m) => ({
+          ...m,
+          content: "",
+          deleted: true,
+        }));
+        break;
+
+      case "UserTyping":
+        setTypingUsers((prev) => ({
+          ...prev,
+          [message.room_name]: [
+            ...(prev[message.room_name] ?? []).filter(
+              (u) => u !== message.username
+            ),
+            message.username,
+          ],
+        }));
+        break;
+
+      case "UserStoppedTyping":
+        setTypingUsers((prev) => ({
+          ...prev,
+          [message.room_name]: (prev[message.room_name] ?? []).filter(
+            (u) => u !== message.username
+          ),
+        }));
+        break;
+
+      case "ReactionsUpdated":
+        updateRoomMessage(message.room_name, message.message_id, (m) => ({
+          ...m,
+          reactions: message.reactions,
+        }));
+        break;
+
+      case "History":
+        // 参加直後に届く直近の履歴でそのルームの表示を置き換える
+        setRoomMessages((prev) => ({
+          ...prev,
+          [message.room_name]: message.messages.map(
+            (m: Omit<ChatMessage, "room_name">) => ({
+              ...m,
+              room_name: message.room_name,
+            })
+          ),
+        }));
+        break;
+
       case "UserJoined":
         // ユーザー一覧を更新するためにリクエスト
-        if (message.room_name === currentRoom) {
-          sendMessage({ type: "ListUsers" });
+        if (message.room_name === currentRoomRef.current) {
+          sendMessage({ type: "ListUsers", room_name: message.room_name });
         }
         break;
 
       case "UserLeft":
         // ユーザー一覧を更新するためにリクエスト
-        if (message.room_name === currentRoom) {
-          sendMessage({ type: "ListUsers" });
+        if (message.room_name === currentRoomRef.current) {
+          sendMessage({ type: "ListUsers", room_name: message.room_name });
         }
         break;
 
@@ -173,26 +447,136 @@ export default function page() {
         break;
 
       case "JoinedRoom":
-        // ルーム変更時の処理
-        setCurrentRoom(message.room_name);
-        setMessages([]); // メッセージをクリア
-        // ユーザー一覧を取得
-        sendMessage({ type: "ListUsers" });
-        setDrawerOpen(false); // モバイルの場合、ドロワーを閉じる
+        // 参加したルームを表示する (他のルームには参加したまま)
+        pendingJoinRef.current = null;
+        joinedRoomsRef.current = [
+          ...joinedRoomsRef.current,
+          message.room_name,
+        ];
+        setRoomMessages((prev) => ({ ...prev, [message.room_name]: [] }));
+        switchRoom(message.room_name);
+        break;
+
+      case "LeftRoom": {
+        const remaining = joinedRoomsRef.current.filter(
+          (room) => room !== message.room_name
+        );
+        joinedRoomsRef.current = remaining;
+        setRoomMessages((prev) => {
+          const { [message.room_name]: _, ...rest } = prev;
+          return rest;
+        });
+        setUnreadRooms((prev) => ({ ...prev, [message.room_name]: 0 }));
+        delete readSeqsRef.current[message.room_name];
+        setReadBy((prev) => {
+          const { [message.room_name]: _, ...rest } = prev;
+          return rest;
+        });
+        // 表示中のルームから退出した場合は残りのルームに切り替える
+        if (message.room_name === currentRoomRef.current) {
+          if (remaining.length > 0) {
+            switchRoom(remaining[0]);
+          } else {
+            currentRoomRef.current = "";
+            setCurrentRoom("");
+            setUsers([]);
+          }
+        }
         break;
+      }
 
-      case "RoomList":
+      case "ModeratorGranted":
+      case "UserKicked":
+      case "UserBanned":
+      case "UserUnbanned":
+      case "UserMuted":
+      case "UserUnmuted":
+        // モデレーション操作の通知 (キックや BAN でメンバーが変わる)
+        console.info(
+          `[${message.room_name}] ${message.type}: ${message.username} by ${message.by}`
+        );
+        if (message.room_name === currentRoomRef.current) {
+          sendMessage({ type: "ListUsers", room_name: message.room_name });
+        }
+        break;
+
+      case "UserInvited":
+        console.info(
+          `[${message.room_name}] ${message.by} invited ${message.username}`
+        );
+        // 招待されたのが自分なら非表示のルームでも選べるよう一覧に加える
+        if (!joinedRoomsRef.current.includes(message.room_name)) {
+          setRooms((prev) =>
+            prev.includes(message.room_name)
+              ? prev
+              : [...prev, message.room_name]
+          );
+        }
+        break;
+
+      case "RoomList": {
         setRooms(message.rooms);
+        // サーバーが数えた未読数で置き換える (表示中のルームは既読にする)
+        const unread: Record<string, number> = { ...(message.unread ?? {}) };
+        if (dmPeerRef.current === null) {
+          delete unread[currentRoomRef.current];
+        }
+        setUnreadRooms((prev) => ({ ...prev, ...unread }));
+        break;
+      }
+
+      case "ReadBy":
+        setReadBy((prev) => ({
+          ...prev,
+          [message.room_name]: {
+            ...prev[message.room_name],
+            [message.username]: message.seq,
+          },
+        }));
         break;
 
       case "UserList":
-        setUsers(message.users);
+        if (message.room_name === currentRoomRef.current) {
+          setUsers(message.users);
+        }
         break;
 
-      case "Error":
+      case "Error": {
         console.error("Server error:", message.message);
+        if (message.code === "invalid_session") {
+          // 猶予が過ぎてセッションが削除された
+          alert(
+            "接続が切れている間にセッションが終了しました。ログインし直してください"
+          );
+          closingRef.current = true;
+          localStorage.removeItem("chat_username");
+          router.push("/");
+          break;
+        }
+        if (!loggedInRef.current) {
+          // ユーザー名やパスワードが通らない場合などはログイン画面に戻す
+          alert(`エラー: ${LOGIN_ERRORS[message.code] || message.message}`);
+          localStorage.removeItem("chat_username");
+          socketRef.current?.close();
+          router.push("/");
+        }
+        const pendingJoin = pendingJoinRef.current;
+        pendingJoinRef.current = null;
+        if (
+          pendingJoin &&
+          (message.message === "Password required" ||
+            message.message === "Wrong password")
+        ) {
+          const password = prompt(
+            `「${pendingJoin}」のパスワードを入力してください`
+          );
+          if (password) {
+            joinRoom(pendingJoin, password);
+          }
+        }
         // エラーメッセージを表示する処理を追加できます
         break;
+      }
 
       default:
         break;
@@ -200,28 +584,185 @@ export default function page() {
   };
 
   const sendMessage = (message: ClientMessage) => {
-    if (socket && connected) {
-      socket.send(JSON.stringify(message));
+    const ws = socketRef.current;
+    if (ws && ws.readyState === WebSocket.OPEN) {
+      ws.send(JSON.stringify(message));
+    }
+  };
+
+  // 名前の変更を自分の表示と DM の相手に反映する
+  const renameUser = (oldUsername: string, newUsername: string) => {
+    setUsername((current) => {
+      if (current !== oldUsername) return current;
+      localStorage.setItem("chat_username", newUsername);
+      return newUsername;
+    });
+    setDirectMessages((prev) => {
+      if (!prev[oldUsername]) return prev;
+      const { [oldUsername]: history, ...rest } = prev;
+      return { ...rest, [newUsername]: history };
+    });
+    setUnreadDirect((prev) => {
+      const { [oldUsername]: unread, ...rest } = prev;
+      return unread ? { ...rest, [newUsername]: unread } : rest;
+    });
+    if (dmPeerRef.current === oldUsername) {
+      dmPeerRef.current = newUsername;
+      setDmPeer(newUsername);
+    }
+  };
+
+  const appendDirectMessage = (peer: string, message: DirectChatMessage) => {
+    setDirectMessages((prev) => ({
+      ...prev,
+      [peer]: [...(prev[peer] ?? []), message],
+    }));
+  };
+
+  // DM ペインを開く (null でルームの表示に戻る)
+  const openDirectMessage = (peer: string | null) => {
+    dmPeerRef.current = peer;
+    setDmPeer(peer);
+    setReplyTo(null);
+    if (peer) {
+      setDirectMessages((prev) => ({ ...prev, [peer]: prev[peer] ?? [] }));
+      setUnreadDirect((prev) => ({ ...prev, [peer]: 0 }));
+    } else if (currentRoomRef.current) {
+      // ルームの表示に戻ると、その間に届いたメッセージは既読になる
+      const room = currentRoomRef.current;
+      setUnreadRooms((prev) => ({ ...prev, [room]: 0 }));
     }
+    setDrawerOpen(false);
+    setUsersDrawerOpen(false);
   };
 
+  // 表示中のルーム宛てのモデレーション操作に変換する (該当しなければ null)
+  // /invite /mod /kick /ban <名前> [秒数] /unban /mute /unmute
+  const parseModerationCommand = (input: string): ClientMessage | null => {
+    const [command, target, seconds] = input.trim().split(/\s+/);
+    const type = MODERATION_COMMANDS[command];
+    if (!type || !target || !currentRoom) return null;
+
+    const message: ClientMessage = {
+      type,
+      room_name: currentRoom,
+      username: target,
+    };
+    if (type === "Ban") {
+      message.duration_secs = seconds ? Number(seconds) : null;
+    }
+    return message;
+  };
+
+  // /nick <名前> で名前を変更する
   const handleSendMessage = (e: React.FormEvent) => {
     e.preventDefault();
-    if (messageInput.trim() && connected) {
+    const moderation = dmPeer ? null : parseModerationCommand(messageInput);
+    if (moderation && connected) {
+      sendMessage(moderation);
+      setMessageInput("");
+    } else if (messageInput.startsWith("/nick ") && connected) {
+      sendMessage({
+        type: "ChangeNick",
+        username: messageInput.slice("/nick ".length).trim(),
+      });
+      setMessageInput("");
+    } else if (messageInput.trim() && connected && dmPeer) {
+      sendMessage({
+        type: "DirectMessage",
+        to: dmPeer,
+        content: messageInput,
+      });
+      // 自分の送信分はサーバーから返らないので手元で追加する
+      appendDirectMessage(dmPeer, {
+        sender: username,
+        content: messageInput,
+        timestamp: new Date().toISOString(),
+      });
+      setMessageInput("");
+    } else if (messageInput.trim() && connected && currentRoom) {
       const message: ClientMessage = {
         type: "SendMessage",
+        room_name: currentRoom,
         content: messageInput,
       };
+      if (replyTo) {
+        message.reply_to = replyTo.id;
+      }
       sendMessage(message);
+      // 送信するとサーバー側で入力中が解除されるので、次の入力ですぐに送り直す
+      lastTypingSentRef.current = 0;
+      setReplyTo(null);
       setMessageInput("");
     }
   };
 
+  // ルームで入力している間は Typing を送る (コマンドと DM では送らない)
+  const handleInputChange = (value: string) => {
+    setMessageInput(value);
+    const now = Date.now();
+    if (
+      !dmPeer &&
+      currentRoom &&
+      value.trim() &&
+      !value.startsWith("/") &&
+      now - lastTypingSentRef.current >= TYPING_INTERVAL_MS
+    ) {
+      lastTypingSentRef.current = now;
+      sendMessage({ type: "Typing", room_name: currentRoom });
+    }
+  };
+
+  // ルームの最新のメッセージ (通し番号があるもの)
+  const latestRoomMessage = (roomName: string) =>
+    (roomMessages[roomName] ?? []).filter((m) => m.seq !== undefined).at(-1);
+
+  // 表示中のルームの最新のメッセージが自分のものなら、読んだメンバーを返す
+  const readReceiptUsers = () => {
+    const last = dmPeer ? undefined : latestRoomMessage(currentRoom);
+    if (!last || last.sender !== username) return [];
+    return Object.entries(readBy[currentRoom] ?? {})
+      .filter(([name, seq]) => name !== username && seq >= last.seq!)
+      .map(([name]) => name);
+  };
+
+  // 表示するルームを切り替える (参加中のルームのみ)
+  const switchRoom = (roomName: string) => {
+    currentRoomRef.current = roomName;
+    setCurrentRoom(roomName);
+    setReplyTo(null);
+    setUnreadRooms((prev) => ({ ...prev, [roomName]: 0 }));
+    sendMessage({ type: "ListUsers", room_name: roomName });
+    setDrawerOpen(false); // モバイルの場合、ドロワーを閉じる
+  };
+
+  // 参加中のルームなら表示を切り替え、それ以外は参加する
   const handleRoomChange = (roomName: string) => {
-    if (roomName !== currentRoom) {
+    openDirectMessage(null);
+    if (joinedRoomsRef.current.includes(roomName)) {
+      switchRoom(roomName);
+    } else {
+      joinRoom(roomName);
+    }
+  };
+
+  const joinRoom = (roomName: string, password?: string) => {
+    pendingJoinRef.current = roomName;
+    const message: ClientMessage = {
+      type: "JoinRoom",
+      room_name: roomName,
+    };
+    if (password) {
+      message.password = password;
+    }
+    sendMessage(message);
+  };
+
+  const handleLeaveRoom = () => {
+    if (currentRoom && connected) {
       const message: ClientMessage = {
-        type: "JoinRoom",
-        room_name: roomName,
+        type: "LeaveRoom",
+        room_name: currentRoom,
       };
       sendMessage(message);
     }
@@ -232,14 +773,23 @@ export default function page() {
       const message: ClientMessage = {
         type: "CreateRoom",
         room_name: newRoomName,
+        visibility: newRoomVisibility,
+        hidden: newRoomHidden,
       };
+      if (newRoomVisibility === "password") {
+        message.password = newRoomPassword;
+      }
       sendMessage(message);
       setNewRoomName("");
+      setNewRoomVisibility("public");
+      setNewRoomPassword("");
+      setNewRoomHidden(false);
       setCreateRoomDialogOpen(false);
     }
   };
 
   const handleLogout = () => {
+    closingRef.current = true;
     localStorage.removeItem("chat_username");
     if (socket) {
       socket.close();
@@ -247,6 +797,87 @@ export default function page() {
     router.push("/");
   };
 
+  // 編集・削除・リアクションの通知をルームのメッセージに反映する
+  const updateRoomMessage = (
+    roomName: string,
+    messageId: string,
+    update: (message: ChatMessage) => ChatMessage
+  ) => {
+    setRoomMessages((prev) =>
+      prev[roomName]
+        ? {
+            ...prev,
+            [roomName]: prev[roomName].map((m) =>
+              m.id === messageId ? update(m) : m
+            ),
+          }
+        : prev
+    );
+  };
+
+  const handleEditMessage = (msg: ChatMessage) => {
+    const content = prompt("メッセージを編集", msg.content)?.trim();
+    if (content && content !== msg.content) {
+      sendMessage({
+        type: "EditMessage",
+        room_name: msg.room_name,
+        message_id: msg.id,
+        content: content,
+      });
+    }
+  };
+
+  const handleDeleteMessage = (msg: ChatMessage) => {
+    if (confirm("このメッセージを削除しますか？")) {
+      sendMessage({
+        type: "DeleteMessage",
+        room_name: msg.room_name,
+        message_id: msg.id,
+      });
+    }
+  };
+
+  // 自分が付けたリアクションなら外し、そうでなければ付ける
+  const toggleReaction = (msg: ChatMessage, emoji: string) => {
+    const reacted = (msg.reactions ?? []).some(
+      (r) => r.emoji === emoji && r.users.includes(username)
+    );
+    sendMessage({
+      type: reacted ? "Unreact" : "React",
+      room_name: msg.room_name,
+      message_id: msg.id,
+      emoji: emoji,
+    });
+  };
+
+  const handleReact = (msg: ChatMessage) => {
+    const emoji = prompt("リアクションする絵文字", "👍")?.trim();
+    if (emoji) {
+      toggleReaction(msg, emoji);
+    }
+  };
+
+  // 返信先のメッセージを探す (履歴にないものは undefined)
+  const findRoomMessage = (messageId: string) =>
+    (roomMessages[currentRoom] ?? []).find((m) => m.id === messageId);
+
+  const describeMessage = (msg: ThreadMessage) =>
+    msg.deleted
+      ? `${msg.sender}: (削除されました)`
+      : `${msg.sender}: ${msg.content}`;
+
+  const requestThread = (messageId: string) => {
+    sendMessage({
+      type: "GetThread",
+      room_name: currentRoom,
+      message_id: messageId,
+    });
+  };
+
+  const visibleMessages: VisibleMessage[] = dmPeer
+    ? directMessages[dmPeer] ?? []
+    : roomMessages[currentRoom] ?? [];
+
   const formatTimestamp = (timestamp: string) => {
     const date = new Date(timestamp);
     return date.toLocaleTimeString();
@@ -274,10 +905,18 @@ export default function page() {
         {rooms.map((room) => (
           <ListItemButton
             key={room}
-            selected={currentRoom === room}
+            selected={!dmPeer && currentRoom === room}
             onClick={() => handleRoomChange(room)}
           >
-            <ListItemText primary={room} />
+            <ListItemText
+              primary={room}
+              primaryTypographyProps={{
+                fontWeight: room in roomMessages ? "bold" : "normal",
+              }}
+              secondary={
+                unreadRooms[room] ? `未読 ${unreadRooms[room]}` : null
+              }
+            />
           </ListItemButton>
         ))}
       </List>
@@ -290,9 +929,46 @@ export default function page() {
       >
         新しいルーム
       </Button>
+      {Object.keys(directMessages).length > 0 && (
+        <>
+          <Typography variant="h6" sx={{ mt: 3, mb: 1 }}>
+            ダイレクトメッセージ
+          </Typography>
+          <Divider sx={{ mb: 1 }} />
+          <List>
+            {Object.keys(directMessages).map((peer) => (
+              <ListItemButton
+                key={peer}
+                selected={dmPeer === peer}
+                onClick={() => openDirectMessage(peer)}
+              >
+                <ListItemText
+                  primary={peer}
+                  secondary={
+                    unreadDirect[peer] ? `未読 ${unreadDirect[peer]}` : null
+                  }
+                />
+              </ListItemButton>
+            ))}
+          </List>
+        </>
+      )}
     </Box>
   );
 
+  // ユーザーをクリックすると DM ペインを開く
+  const userListItems = users.map((user) =>
+    user === username ? (
+      <ListItem key={user}>
+        <ListItemText primary={user} secondary="あなた" />
+      </ListItem>
+    ) : (
+      <ListItemButton key={user} onClick={() => openDirectMessage(user)}>
+        <ListItemText primary={user} />
+      </ListItemButton>
+    )
+  );
+
   const usersDrawerContent = (
     <Box sx={{ width: 250, p: 2 }}>
       <Box
@@ -309,13 +985,7 @@ export default function page() {
         </IconButton>
       </Box>
       <Divider sx={{ mb: 2 }} />
-      <List>
-        {users.map((user) => (
-          <ListItem key={user}>
-            <ListItemText primary={user} />
-          </ListItem>
-        ))}
-      </List>
+      <List>{userListItems}</List>
     </Box>
   );
 
@@ -383,9 +1053,20 @@ export default function page() {
                   <MenuIcon />
                 </IconButton>
               )}
-              <Typography variant="h6">{currentRoom}</Typography>
+              <Typography variant="h6">
+                {dmPeer ? `@${dmPeer}` : currentRoom || "ルーム未参加"}
+              </Typography>
             </Box>
             <Box sx={{ display: "flex", alignItems: "center" }}>
+              <Button
+                variant="text"
+                color="error"
+                onClick={handleLeaveRoom}
+                disabled={!!dmPeer || !currentRoom || !connected}
+                sx={{ mr: 1 }}
+              >
+                退出
+              </Button>
               <Button
                 variant="text"
                 startIcon={<PeopleIcon />}
@@ -417,7 +1098,7 @@ export default function page() {
             }}
           >
             <Box sx={{ flexGrow: 1, overflow: "auto" }}>
-              {messages.map((msg, index) => (
+              {visibleMessages.map((msg, index) => (
                 <Box
                   key={index}
                   sx={{
@@ -439,28 +1120,154 @@ export default function page() {
                       maxWidth: "70%",
                     }}
                   >
-                    <Typography variant="body1">{msg.content}</Typography>
+                    {/* 返信は返信先を引用し、クリックでスレッドを開く */}
+                    {msg.reply_to && (
+                      <Typography
+                        variant="caption"
+                        component="div"
+                        onClick={() => requestThread(msg.reply_to!)}
+                        sx={{
+                          borderLeft: 3,
+                          pl: 1,
+                          mb: 0.5,
+                          opacity: 0.8,
+                          cursor: "pointer",
+                        }}
+                      >
+                        ↪{" "}
+                        {findRoomMessage(msg.reply_to)
+                          ? describeMessage(findRoomMessage(msg.reply_to)!)
+                          : "以前のメッセージへの返信"}
+                      </Typography>
+                    )}
+                    {msg.deleted ? (
+                      <Typography variant="body1" sx={{ fontStyle: "italic" }}>
+                        このメッセージは削除されました
+                      </Typography>
+                    ) : (
+                      <Typography variant="body1">{msg.content}</Typography>
+                    )}
                   </Box>
                   <Typography variant="caption" sx={{ mt: 0.5 }}>
                     {msg.sender === username ? "あなた" : msg.sender} •{" "}
                     {formatTimestamp(msg.timestamp)}
+                    {msg.edited_at && !msg.deleted && " • 編集済み"}
                   </Typography>
+                  {/* 押すと自分のリアクションを付け外しする */}
+                  {!dmPeer && !msg.deleted && !!msg.reactions?.length && (
+                    <Box sx={{ display: "flex", gap: 0.5, mt: 0.5 }}>
+                      {msg.reactions!.map((reaction) => (
+                        <Chip
+                          key={reaction.emoji}
+                          size="small"
+                          label={`${reaction.emoji} ${reaction.count}`}
+                          title={reaction.users.join(", ")}
+                          color={
+                            reaction.users.includes(username)
+                              ? "primary"
+                              : "default"
+                          }
+                          variant="outlined"
+                          onClick={() =>
+                            toggleReaction(msg as ChatMessage, reaction.emoji)
+                          }
+                        />
+                      ))}
+                    </Box>
+                  )}
+                  {/* ルームのメッセージには返信でき、自分のものは編集・削除できる */}
+                  {!dmPeer && msg.id && !msg.deleted && (
+                    <Box>
+                      <Button
+                        size="small"
+                        onClick={() => setReplyTo(msg as ChatMessage)}
+                      >
+                        返信
+                      </Button>
+                      <Button
+                        size="small"
+                        onClick={() => handleReact(msg as ChatMessage)}
+                      >
+                        リアクション
+                      </Button>
+                      {msg.sender === username && (
+                        <>
+                          <Button
+                            size="small"
+                            onClick={() =>
+                              handleEditMessage(msg as ChatMessage)
+                            }
+                          >
+                            編集
+                          </Button>
+                          <Button
+                            size="small"
+                            color="error"
+                            onClick={() =>
+                              handleDeleteMessage(msg as ChatMessage)
+                            }
+                          >
+                            削除
+                          </Button>
+                        </>
+                      )}
+                    </Box>
+                  )}
                 </Box>
               ))}
+              {readReceiptUsers().length > 0 && (
+                <Typography
+                  variant="caption"
+                  color="text.secondary"
+                  component="div"
+                  sx={{ textAlign: "right" }}
+                >
+                  既読: {readReceiptUsers().join("、")}
+                </Typography>
+              )}
               <div ref={messagesEndRef} />
             </Box>
           </Paper>
 
           {/* メッセージ入力フォーム */}
           <Paper elevation={2} sx={{ p: 2 }}>
+            {!dmPeer && (typingUsers[currentRoom] ?? []).length > 0 && (
+              <Typography
+                variant="caption"
+                color="text.secondary"
+                component="div"
+                sx={{ fontStyle: "italic", mb: 1 }}
+              >
+                {typingUsers[currentRoom].join("、")} が入力中…
+              </Typography>
+            )}
+            {replyTo && !dmPeer && (
+              <Box
+                sx={{
+                  display: "flex",
+                  alignItems: "center",
+                  justifyContent: "space-between",
+                  mb: 1,
+                }}
+              >
+                <Typography variant="caption" color="text.secondary">
+                  ↪ {describeMessage(replyTo)} に返信
+                </Typography>
+                <IconButton size="small" onClick={() => setReplyTo(null)}>
+                  <CloseIcon fontSize="small" />
+                </IconButton>
+              </Box>
+            )}
             <form onSubmit={handleSendMessage}>
               <Box sx={{ display: "flex", gap: 2 }}>
                 <Box sx={{ flexGrow: 1 }}>
                   <TextField
                     fullWidth
-                    placeholder="メッセージを入力..."
+                    placeholder={
+                      dmPeer ? `${dmPeer} へのメッセージ...` : "メッセージを入力..."
+                    }
                     value={messageInput}
-                    onChange={(e) => setMessageInput(e.target.value)}
+                    onChange={(e) => handleInputChange(e.target.value)}
                     disabled={!connected}
                   />
                 </Box>
@@ -495,13 +1302,7 @@ export default function page() {
               ユーザー一覧
             </Typography>
             <Divider sx={{ mb: 2 }} />
-            <List>
-              {users.map((user) => (
-                <ListItem key={user}>
-                  <ListItemText primary={user} />
-                </ListItem>
-              ))}
-            </List>
+            <List>{userListItems}</List>
           </Box>
         )}
       </Box>
@@ -521,16 +1322,80 @@ export default function page() {
             value={newRoomName}
             onChange={(e) => setNewRoomName(e.target.value)}
           />
+          <TextField
+            select
+            margin="dense"
+            label="公開範囲"
+            fullWidth
+            value={newRoomVisibility}
+            onChange={(e) => setNewRoomVisibility(e.target.value)}
+          >
+            <MenuItem value="public">公開</MenuItem>
+            <MenuItem value="password">パスワード付き</MenuItem>
+            <MenuItem value="invite_only">招待制</MenuItem>
+          </TextField>
+          {newRoomVisibility === "password" && (
+            <TextField
+              margin="dense"
+              label="パスワード"
+              type="password"
+              fullWidth
+              value={newRoomPassword}
+              onChange={(e) => setNewRoomPassword(e.target.value)}
+            />
+          )}
+          <FormControlLabel
+            control={
+              <Checkbox
+                checked={newRoomHidden}
+                onChange={(e) => setNewRoomHidden(e.target.checked)}
+              />
+            }
+            label="参加者以外の一覧に表示しない"
+          />
         </DialogContent>
         <DialogActions>
           <Button onClick={() => setCreateRoomDialogOpen(false)}>
             キャンセル
           </Button>
-          <Button onClick={handleCreateRoom} disabled={!newRoomName.trim()}>
+          <Button
+            onClick={handleCreateRoom}
+            disabled={
+              !newRoomName.trim() ||
+              (newRoomVisibility === "password" && !newRoomPassword)
+            }
+          >
             作成
           </Button>
         </DialogActions>
       </Dialog>
+
+      {/* スレッドの表示 */}
+      <Dialog open={thread !== null} onClose={() => setThread(null)} fullWidth>
+        <DialogTitle>スレッド</DialogTitle>
+        <DialogContent>
+          {thread && (
+            <List dense>
+              {thread.root === null && (
+                <ListItem>
+                  <ListItemText secondary="元のメッセージは履歴に残っていません" />
+                </ListItem>
+              )}
+              {[...(thread.root ? [thread.root] : []), ...thread.replies].map((m) => (
+                <ListItem key={m.id} sx={{ pl: m.reply_to ? 4 : 0 }}>
+                  <ListItemText
+                    primary={describeMessage(m)}
+                    secondary={formatTimestamp(m.timestamp)}
+                  />
+                </ListItem>
+              ))}
+            </List>
+          )}
+        </DialogContent>
+        <DialogActions>
+          <Button onClick={() => setThread(null)}>閉じる</Button>
+        </DialogActions>
+      </Dialog>
     </Container>
   );
 }
@@ -6,13 +6,27 @@ import { Box, Button, Container, TextField, Typography } from "@mui/material";
 
 export default function Home() {
   const [username, setUsername] = useState("");
+  const [password, setPassword] = useState("");
   const router = useRouter();
 
+  // パスワードはチャット画面で送信したら消す (空ならゲストとしてログイン)
+  const enterChat = (register: boolean) => {
+    localStorage.setItem("chat_username", username.trim());
+    sessionStorage.removeItem("chat_password");
+    sessionStorage.removeItem("chat_register");
+    if (password) {
+      sessionStorage.setItem("chat_password", password);
+    }
+    if (register) {
+      sessionStorage.setItem("chat_register", "1");
+    }
+    router.push("/chat");
+  };
+
   const handleLogin = (e: React.FormEvent) => {
     e.preventDefault();
     if (username.trim()) {
-      localStorage.setItem("chat_username", username);
-      router.push("/chat");
+      enterChat(false);
     }
   };
 
@@ -44,15 +58,34 @@ export default function Home() {
             value={username}
             onChange={(e) => setUsername(e.target.value)}
           />
+          <TextField
+            margin="normal"
+            fullWidth
+            id="password"
+            label="パスワード"
+            type="password"
+            helperText="空欄ならゲストとしてログインします"
+            value={password}
+            onChange={(e) => setPassword(e.target.value)}
+          />
           <Button
             type="submit"
             fullWidth
             variant="contained"
-            sx={{ mt: 3, mb: 2 }}
+            sx={{ mt: 3, mb: 1 }}
             disabled={!username.trim()}
           >
             ログイン
           </Button>
+          <Button
+            fullWidth
+            variant="text"
+            sx={{ mb: 2 }}
+            disabled={!username.trim() || !password}
+            onClick={() => enterChat(true)}
+          >
+            このユーザー名とパスワードで登録
+          </Button>
         </Box>
       </Box>
     </Container>
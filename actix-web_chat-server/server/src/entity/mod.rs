@@ -1,2 +0,0 @@
-pub mod message;
-pub mod user;
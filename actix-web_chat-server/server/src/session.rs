@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use actix::{Actor, ActorContext, AsyncContext, Handler, Recipient, StreamHandler};
+use actix_web_actors::ws;
+use chat_core::connection::Connection;
+use chat_core::server::ChatServer;
+use chat_core::session::Flow;
+use chat_protocol::{SequencedMessage, ServerMessage};
+use tokio::sync::mpsc;
+
+// WebSocketセッションへ送信するためのメッセージ型
+pub struct WsMessage(pub String);
+
+impl actix::Message for WsMessage {
+    type Result = ();
+}
+
+// セッションアクターを chat-core の接続として扱う
+#[derive(Debug)]
+pub struct RecipientConnection(Recipient<WsMessage>);
+
+impl RecipientConnection {
+    pub fn new(recipient: Recipient<WsMessage>) -> Self {
+        Self(recipient)
+    }
+
+    fn deliver(&self, message: SequencedMessage) -> bool {
+        if !self.0.connected() {
+            return false;
+        }
+        let json = serde_json::to_string(&message).unwrap();
+        self.0.do_send(WsMessage(json));
+        true
+    }
+}
+
+impl Connection for RecipientConnection {
+    fn send(&self, message: ServerMessage) -> bool {
+        self.deliver(SequencedMessage {
+            session_seq: None,
+            message,
+        })
+    }
+
+    fn send_sequenced(&self, seq: u64, message: ServerMessage) -> bool {
+        self.deliver(SequencedMessage {
+            session_seq: Some(seq),
+            message,
+        })
+    }
+}
+
+pub struct WsSession {
+    server: ChatServer,
+    inbound: Option<mpsc::UnboundedSender<String>>, // 受信したテキストをセッションタスクへ渡す
+}
+
+impl WsSession {
+    pub fn new(server: ChatServer) -> Self {
+        Self {
+            server,
+            inbound: None,
+        }
+    }
+}
+
+impl Actor for WsSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        self.inbound = Some(tx);
+
+        let connection = RecipientConnection::new(ctx.address().recipient());
+        let mut session = self.server.connect(Arc::new(connection));
+        let addr = ctx.address();
+
+        // 受信した順に処理するため、1 本のタスクでセッションを回す
+        actix::spawn(async move {
+            while let Some(text) = rx.recv().await {
+                if session.handle_text(&text).await == Flow::Close {
+                    addr.do_send(CloseSession);
+                    break;
+                }
+            }
+            session.close().await;
+        });
+    }
+
+    fn stopped(&mut self, _: &mut Self::Context) {
+        // チャネルを閉じるとセッションタスクが切断処理を行う
+        self.inbound = None;
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Text(text)) => {
+                if let Some(inbound) = &self.inbound {
+                    let _ = inbound.send(text.to_string());
+                }
+            }
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Pong(_)) => {}
+            Ok(ws::Message::Binary(_)) => {}
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            _ => ctx.stop(),
+        }
+    }
+}
+
+impl Handler<WsMessage> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: WsMessage, ctx: &mut Self::Context) {
+        ctx.text(msg.0);
+    }
+}
+
+// セッションから切断を求められたときのメッセージ
+struct CloseSession;
+
+impl actix::Message for CloseSession {
+    type Result = ();
+}
+
+impl Handler<CloseSession> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, _: CloseSession, ctx: &mut Self::Context) {
+        ctx.close(Some(ws::CloseCode::Unsupported.into()));
+        ctx.stop();
+    }
+}
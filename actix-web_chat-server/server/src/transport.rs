@@ -0,0 +1,73 @@
+use std::net::SocketAddr;
+
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer, http, middleware, web};
+use actix_web_actors::ws;
+use chat_core::server::ChatServer;
+use chat_core::transport::Transport;
+
+use crate::session::WsSession;
+
+// actix-web の WebSocket トランスポート (Next.js のフロントエンドから接続される)
+#[derive(Debug)]
+pub struct ActixTransport {
+    addr: SocketAddr,
+    frontend_url: String,
+}
+
+impl ActixTransport {
+    pub fn new(addr: impl Into<SocketAddr>, frontend_url: &str) -> Self {
+        Self {
+            addr: addr.into(),
+            frontend_url: frontend_url.to_string(),
+        }
+    }
+}
+
+impl Transport for ActixTransport {
+    async fn serve(self, server: ChatServer) -> Result<(), Box<dyn std::error::Error>> {
+        let server_data = web::Data::new(server);
+        let frontend_url = self.frontend_url;
+
+        HttpServer::new(move || {
+            App::new()
+                .app_data(server_data.clone())
+                .wrap(
+                    middleware::DefaultHeaders::new()
+                        .add(("Access-Control-Allow-Origin", frontend_url.as_str())),
+                )
+                .wrap(
+                    actix_cors::Cors::default()
+                        .allowed_origin(&frontend_url)
+                        .allowed_methods(vec!["GET", "POST"])
+                        .allowed_headers(vec![http::header::AUTHORIZATION, http::header::ACCEPT])
+                        .allowed_header(http::header::CONTENT_TYPE)
+                        .max_age(3600),
+                )
+                .configure(routes)
+        })
+        .bind(self.addr)?
+        .run()
+        .await?;
+
+        Ok(())
+    }
+}
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/ws").to(ws_route))
+        .service(web::resource("/api/rooms").route(web::get().to(get_rooms)));
+}
+
+async fn ws_route(
+    req: HttpRequest,
+    stream: web::Payload,
+    server: web::Data<ChatServer>,
+) -> Result<HttpResponse, actix_web::Error> {
+    ws::start(WsSession::new(server.get_ref().clone()), &req, stream)
+}
+
+async fn get_rooms(server: web::Data<ChatServer>) -> HttpResponse {
+    let rooms = server.room_list().await;
+
+    HttpResponse::Ok().json(rooms)
+}
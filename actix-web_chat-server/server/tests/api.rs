@@ -0,0 +1,17 @@
+use actix_chat_server::transport::routes;
+use actix_web::{App, test, web};
+use chat_core::server::ChatServer;
+
+#[actix_web::test]
+async fn rooms_api_lists_engine_rooms() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(ChatServer::new()))
+            .configure(routes),
+    )
+    .await;
+
+    let req = test::TestRequest::get().uri("/api/rooms").to_request();
+    let rooms: Vec<String> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(rooms, vec!["general".to_string()]);
+}
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix::{Actor, ActorContext, Context, Handler};
+use actix_chat_server::session::{RecipientConnection, WsMessage};
+use chat_core::connection::Connection;
+use chat_core::server::ChatServer;
+use chat_protocol::{ClientMessage, ServerMessage};
+use tokio::sync::mpsc;
+
+// 受信した WsMessage をチャネルへ流すテスト用セッション
+struct Collector(mpsc::UnboundedSender<ServerMessage>);
+
+impl Actor for Collector {
+    type Context = Context<Self>;
+}
+
+impl Handler<WsMessage> for Collector {
+    type Result = ();
+
+    fn handle(&mut self, msg: WsMessage, _: &mut Self::Context) {
+        let _ = self.0.send(serde_json::from_str(&msg.0).unwrap());
+    }
+}
+
+struct Stop;
+
+impl actix::Message for Stop {
+    type Result = ();
+}
+
+impl Handler<Stop> for Collector {
+    type Result = ();
+
+    fn handle(&mut self, _: Stop, ctx: &mut Self::Context) {
+        ctx.stop();
+    }
+}
+
+async fn drain(rx: &mut mpsc::UnboundedReceiver<ServerMessage>) -> Vec<ServerMessage> {
+    // アクターのメールボックスが処理されるのを待つ
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let mut messages = Vec::new();
+    while let Ok(message) = rx.try_recv() {
+        messages.push(message);
+    }
+    messages
+}
+
+#[actix::test]
+async fn engine_messages_reach_the_session_actor() {
+    let server = ChatServer::new();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let connection = RecipientConnection::new(Collector(tx).start().recipient());
+    let mut session = server.connect(Arc::new(connection));
+
+    session
+        .handle(ClientMessage::Login {
+            username: "alice".to_string(),
+            password: None,
+        })
+        .await;
+    session
+        .handle(ClientMessage::SendMessage {
+            room_name: "general".to_string(),
+            content: "hello".to_string(),
+            reply_to: None,
+        })
+        .await;
+
+    let received = drain(&mut rx).await;
+    assert!(matches!(
+        received.first(),
+        Some(ServerMessage::Welcome { .. })
+    ));
+    assert!(received.iter().any(
+        |m| matches!(m, ServerMessage::NewMessage { sender, content, .. } if sender == "alice" && content == "hello")
+    ));
+}
+
+#[actix::test]
+async fn sending_to_a_stopped_actor_fails() {
+    let (tx, _rx) = mpsc::unbounded_channel();
+    let addr = Collector(tx).start();
+    let connection = RecipientConnection::new(addr.clone().recipient());
+    assert!(connection.send(ServerMessage::RoomCreated {
+        room_name: "rust".to_string()
+    }));
+
+    addr.do_send(Stop);
+    drop(addr);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert!(!connection.send(ServerMessage::RoomCreated {
+        room_name: "rust".to_string()
+    }));
+}
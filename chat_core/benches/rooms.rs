@@ -0,0 +1,82 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chat_core::server::ChatServer;
+use chat_core::session::Session;
+use chat_protocol::{ClientMessage, RoomVisibility, ServerMessage};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+
+const MEMBERS_PER_ROOM: usize = 4;
+const MESSAGES_PER_ROOM: usize = 100;
+
+// 受信側のチャネルを読み捨てる接続を作る
+fn connect(server: &ChatServer) -> Session {
+    let (tx, mut rx) = mpsc::unbounded_channel::<ServerMessage>();
+    tokio::spawn(async move { while rx.recv().await.is_some() {} });
+    server.connect(Arc::new(tx))
+}
+
+// ルームごとに送信者 1 人と受信者を用意し、ルーム名と送信者のセッションを返す
+async fn setup(server: &ChatServer, rooms: usize) -> Vec<(String, Session)> {
+    let mut senders = Vec::new();
+    for r in 0..rooms {
+        let room_name = format!("room-{}", r);
+        for m in 0..MEMBERS_PER_ROOM {
+            let mut session = connect(server);
+            session.handle(ClientMessage::Login { username: format!("user-{}-{}", r, m), password: None }).await;
+            if m == 0 {
+                session.handle(ClientMessage::CreateRoom { room_name: room_name.clone(), visibility: RoomVisibility::Public, password: None, hidden: false }).await;
+            }
+            session.handle(ClientMessage::JoinRoom { room_name: room_name.clone(), password: None }).await;
+            if m == 0 {
+                senders.push((room_name.clone(), session));
+            }
+        }
+    }
+    senders
+}
+
+// 各ルームで同時にメッセージを送り、全員分の処理が終わるまでの時間を測る
+fn room_throughput(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    let mut group = c.benchmark_group("room_throughput");
+
+    for rooms in [1, 2, 4, 8, 16] {
+        group.throughput(Throughput::Elements((rooms * MESSAGES_PER_ROOM) as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(rooms), &rooms, |b, &rooms| {
+            b.iter_custom(|iters| run(&runtime, rooms, iters));
+        });
+    }
+
+    group.finish();
+}
+
+fn run(runtime: &Runtime, rooms: usize, iters: u64) -> Duration {
+    runtime.block_on(async {
+        let server = ChatServer::new();
+        let mut senders = setup(&server, rooms).await;
+
+        let mut elapsed = Duration::ZERO;
+        for _ in 0..iters {
+            let start = Instant::now();
+            let handles: Vec<_> = senders.drain(..).map(|(room_name, mut session)| {
+                tokio::spawn(async move {
+                    for i in 0..MESSAGES_PER_ROOM {
+                        session.handle(ClientMessage::SendMessage { room_name: room_name.clone(), content: format!("message {}", i), reply_to: None }).await;
+                    }
+                    (room_name, session)
+                })
+            }).collect();
+            for handle in handles {
+                senders.push(handle.await.unwrap());
+            }
+            elapsed += start.elapsed();
+        }
+        elapsed
+    })
+}
+
+criterion_group!(benches, room_throughput);
+criterion_main!(benches);
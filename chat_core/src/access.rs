@@ -0,0 +1,67 @@
+use std::collections::HashSet;
+
+use chat_protocol::RoomVisibility;
+use crate::auth::hash_password;
+
+// ルームへの参加条件。パスワードはハッシュ (PHC 形式) だけを保持する
+#[derive(Debug, Default)]
+pub struct RoomAccess {
+    pub(crate) visibility: RoomVisibility,
+    pub(crate) password_hash: Option<String>,
+    pub(crate) invited: HashSet<String>, // 招待されたユーザーの identity_key
+    pub hidden: bool,                    // メンバー以外のルーム一覧に出さない
+}
+
+impl RoomAccess {
+    // password は visibility が Password のときだけ使う。ハッシュは重いので非同期のタスクからは spawn_blocking で呼ぶこと
+    pub fn new(visibility: RoomVisibility, password: Option<&str>, hidden: bool) -> Result<Self, String> {
+        let password_hash = match (visibility, password) {
+            (RoomVisibility::Password, Some(password)) if !password.is_empty() => Some(hash_password(password)?),
+            (RoomVisibility::Password, _) => return Err("Password required".to_string()),
+            _ => None,
+        };
+
+        Ok(Self {
+            visibility,
+            password_hash,
+            invited: HashSet::new(),
+            hidden,
+        })
+    }
+
+    pub fn visibility(&self) -> RoomVisibility {
+        self.visibility
+    }
+
+    pub fn password_hash(&self) -> Option<&str> {
+        self.password_hash.as_deref()
+    }
+
+    // ゲストの招待はそのセッションにだけ有効 (同じ名前でログインし直しても引き継がない)
+    pub fn invite(&mut self, identity: &str) {
+        self.invited.insert(identity.to_string());
+    }
+
+    pub fn is_invited(&self, identity: &str) -> bool {
+        self.invited.contains(identity)
+    }
+
+    // 招待されたユーザーはパスワードなしで参加できる
+    // password は照合済みの結果 (None はパスワードの入力なし)。照合はルームのタスクの外で済ませておく
+    pub fn check(&self, identity: &str, password: Option<bool>) -> Result<(), String> {
+        if self.is_invited(identity) {
+            return Ok(());
+        }
+
+        match self.visibility {
+            RoomVisibility::Public => Ok(()),
+            RoomVisibility::InviteOnly => Err("This room is invite-only".to_string()),
+            RoomVisibility::Password => match password {
+                Some(true) => Ok(()),
+                Some(false) => Err("Wrong password".to_string()),
+                None => Err("Password required".to_string()),
+            },
+        }
+    }
+
+}
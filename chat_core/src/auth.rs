@@ -0,0 +1,46 @@
+use argon2::Argon2;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng};
+
+use chat_protocol::{ErrorCode, ServerMessage};
+
+// アカウントのパスワードの最小文字数
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+// ログイン・登録の失敗。クライアントにはコード付きの Error として返す
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl AuthError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+impl From<AuthError> for ServerMessage {
+    fn from(error: AuthError) -> Self {
+        ServerMessage::Error { message: error.message, code: Some(error.code) }
+    }
+}
+
+pub fn validate_password(password: &str) -> Result<(), AuthError> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(AuthError::new(ErrorCode::WeakPassword, format!("Password must be at least {} characters", MIN_PASSWORD_LEN)));
+    }
+    Ok(())
+}
+
+// argon2 の PHC 形式の文字列を返す。どちらも重いので非同期のタスクからは spawn_blocking で呼ぶこと
+pub(crate) fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("Failed to hash password: {}", e))
+}
+
+pub(crate) fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+}
@@ -0,0 +1,82 @@
+use std::env;
+use std::path::PathBuf;
+use chrono::TimeDelta;
+use log::warn;
+
+use crate::store::RetentionPolicy;
+
+// 参加時に再送する履歴の既定件数
+pub const DEFAULT_HISTORY_LIMIT: usize = 50;
+
+// 切断したセッションを再開できる既定の時間
+pub const DEFAULT_RESUME_GRACE: TimeDelta = TimeDelta::seconds(60);
+
+// Typing が途絶えてから入力中の表示を消すまでの既定の時間
+pub const DEFAULT_TYPING_TIMEOUT: TimeDelta = TimeDelta::seconds(5);
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub history_limit: usize,
+    pub retention: RetentionPolicy, // 新しく作られるルームの保持ポリシー
+    pub database: Option<PathBuf>,  // 指定すると SQLite に保存し、再起動後も残す
+    pub allow_guests: bool,         // パスワードなしの Login (登録していない名前) を受け付ける
+    pub resume_grace: TimeDelta,    // 切断後にセッションを残しておく時間 (0 なら再開できない)
+    pub typing_timeout: TimeDelta,  // Typing が途絶えてから UserStoppedTyping を送るまでの時間
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            retention: RetentionPolicy::default(),
+            database: None,
+            allow_guests: true,
+            resume_grace: DEFAULT_RESUME_GRACE,
+            typing_timeout: DEFAULT_TYPING_TIMEOUT,
+        }
+    }
+}
+
+impl ServerConfig {
+    // CHAT_HISTORY_LIMIT / CHAT_ROOM_CAPACITY / CHAT_MESSAGE_TTL_SECS / CHAT_DATABASE / CHAT_ALLOW_GUESTS /
+    // CHAT_RESUME_GRACE_SECS / CHAT_TYPING_TIMEOUT_SECS で上書きする
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Some(history_limit) = parse_env("CHAT_HISTORY_LIMIT") {
+            config.history_limit = history_limit;
+        }
+        if let Some(capacity) = parse_env("CHAT_ROOM_CAPACITY") {
+            config.retention.capacity = capacity;
+        }
+        if let Some(ttl) = parse_env_secs("CHAT_MESSAGE_TTL_SECS") {
+            config.retention.ttl = Some(ttl);
+        }
+        if let Ok(path) = env::var("CHAT_DATABASE") {
+            config.database = Some(PathBuf::from(path));
+        }
+        if let Some(allow_guests) = parse_env("CHAT_ALLOW_GUESTS") {
+            config.allow_guests = allow_guests;
+        }
+        if let Some(grace) = parse_env_secs("CHAT_RESUME_GRACE_SECS") {
+            config.resume_grace = grace;
+        }
+        if let Some(timeout) = parse_env_secs("CHAT_TYPING_TIMEOUT_SECS") {
+            config.typing_timeout = timeout;
+        }
+        config
+    }
+}
+
+fn parse_env<T: std::str::FromStr>(key: &str) -> Option<T> {
+    env::var(key).ok().and_then(|value| value.parse().ok())
+}
+
+// 0 以上の秒数。負の値や TimeDelta で表せない値は使わない
+fn parse_env_secs(key: &str) -> Option<TimeDelta> {
+    let secs: u64 = parse_env(key)?;
+    let delta = i64::try_from(secs).ok().and_then(TimeDelta::try_seconds);
+    if delta.is_none() {
+        warn!("Ignoring {}: {} seconds is out of range", key, secs);
+    }
+    delta
+}
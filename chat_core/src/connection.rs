@@ -0,0 +1,38 @@
+use std::fmt::Debug;
+use tokio::sync::mpsc;
+
+use chat_protocol::{Capability, SequencedMessage, ServerMessage};
+
+// トランスポートごとの送信口。TCP の 1 行でも WebSocket の 1 フレームでもよい
+pub trait Connection: Debug + Send + Sync {
+    // 送信キューに積む。接続が閉じていれば false を返す
+    fn send(&self, message: ServerMessage) -> bool;
+
+    // セッションの通し番号を付けて送る。番号を運べない送信口は番号を落とす
+    fn send_sequenced(&self, _seq: u64, message: ServerMessage) -> bool {
+        self.send(message)
+    }
+
+    // ハンドシェイクで合意した機能か。合意の内容を持たない送信口はすべて受け取る
+    fn supports(&self, _capability: Capability) -> bool {
+        true
+    }
+}
+
+// 送信タスクへつながるチャネル
+impl Connection for mpsc::UnboundedSender<ServerMessage> {
+    fn send(&self, message: ServerMessage) -> bool {
+        mpsc::UnboundedSender::send(self, message).is_ok()
+    }
+}
+
+// 通し番号も運ぶチャネル (TCP / warp で使う)
+impl Connection for mpsc::UnboundedSender<SequencedMessage> {
+    fn send(&self, message: ServerMessage) -> bool {
+        mpsc::UnboundedSender::send(self, SequencedMessage { session_seq: None, message }).is_ok()
+    }
+
+    fn send_sequenced(&self, seq: u64, message: ServerMessage) -> bool {
+        mpsc::UnboundedSender::send(self, SequencedMessage { session_seq: Some(seq), message }).is_ok()
+    }
+}
@@ -0,0 +1,822 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+use uuid::Uuid;
+use chrono::{TimeDelta, Utc};
+use log::{info, error};
+
+use crate::access::RoomAccess;
+use crate::auth::AuthError;
+use crate::config::ServerConfig;
+use chat_protocol::{Capability, ClientMessage, ErrorCode, ServerMessage};
+use crate::connection::Connection;
+use crate::room::ChatRoom;
+use crate::moderation::Moderation;
+use crate::outbox::Outbox;
+use crate::room_actor::{ModAction, RoomCommand, RoomHandle};
+use crate::router::Router;
+use crate::storage_actor::{StorageCommand, StorageHandle};
+use crate::username::{identity_key, username_key, validate_username};
+
+// ディレクトリタスクへの要求。処理が終わると reply で応答する
+#[derive(Debug)]
+pub(crate) enum Command {
+    // registered はパスワードを確認済みのアカウント (username は登録時の表記)
+    // account_exists はゲストの名前がアカウントとして登録済みか (呼び出し側で照会する)
+    Login {
+        username: String,
+        registered: bool,
+        account_exists: bool,
+        capabilities: Vec<Capability>,
+        connection: Arc<dyn Connection>,
+        reply: oneshot::Sender<Result<String, AuthError>>,
+    },
+    // 登録中の名前をゲストや他の登録に使わせない (Register で解放する)
+    ReserveName {
+        username: String,
+        reply: oneshot::Sender<Result<(), AuthError>>,
+    },
+    // created はアカウントの作成結果 (None は保存先に届かなかった)
+    Register {
+        username: String,
+        created: Option<bool>,
+        capabilities: Vec<Capability>,
+        connection: Arc<dyn Connection>,
+        reply: oneshot::Sender<Result<String, AuthError>>,
+    },
+    Message {
+        user_id: String,
+        message: ClientMessage,
+        reply: oneshot::Sender<()>,
+    },
+    // account_exists は新しい名前がアカウントとして登録済みか (呼び出し側で照会する)
+    ChangeNick {
+        user_id: String,
+        username: String,
+        account_exists: bool,
+        reply: oneshot::Sender<()>,
+    },
+    // 切断したセッションを再開する
+    Resume {
+        token: String,
+        last_seen_seq: u64,
+        capabilities: Vec<Capability>,
+        connection: Arc<dyn Connection>,
+        reply: oneshot::Sender<Result<String, AuthError>>,
+    },
+    // connection はそのユーザーのどの接続が閉じたか (再開後の古い接続は無視する)
+    Disconnect {
+        user_id: String,
+        connection: Arc<dyn Connection>,
+        reply: oneshot::Sender<()>,
+    },
+    // 猶予が過ぎても再開されなければユーザーを削除する (ディレクトリ自身のタイマーが送る)
+    ExpireSession {
+        user_id: String,
+    },
+    // パスワードのハッシュは呼び出し側が spawn_blocking で済ませておく
+    CreateRoom {
+        user_id: String,
+        room_name: String,
+        access: Result<RoomAccess, String>,
+        reply: oneshot::Sender<()>,
+    },
+    // password は照合済みの結果 (None はパスワードの入力なし)
+    JoinRoom {
+        user_id: String,
+        room_name: String,
+        password: Option<bool>,
+        reply: oneshot::Sender<()>,
+    },
+    // パスワード付きのルームならそのハッシュ (照合は呼び出し側で行う)
+    RoomPasswordHash {
+        room_name: String,
+        reply: oneshot::Sender<Option<String>>,
+    },
+    RoomList {
+        reply: oneshot::Sender<Vec<String>>,
+    },
+    Flush {
+        reply: oneshot::Sender<()>,
+    },
+}
+
+#[derive(Debug)]
+struct User {
+    id: String,
+    username: String,
+    registered: bool, // false はゲスト
+    rooms: BTreeSet<String>, // 参加中のルーム
+    outbox: Arc<Outbox>, // ルーターと各ルームにはこれを渡す (ハンドシェイクで合意した機能も持つ)
+    session_token: Option<String>, // 再開できないサーバーでは None
+    detached_at: Option<Instant>, // 切断して再開を待っている間だけ Some
+}
+
+impl User {
+    fn identity(&self) -> String {
+        identity_key(&self.id, &self.username, self.registered)
+    }
+}
+
+// ユーザーとルームの一覧を所有するタスク。ルーム内の処理は各ルームのタスクが行う
+#[derive(Debug)]
+pub(crate) struct Directory {
+    rooms: HashMap<String, RoomHandle>,
+    users: HashMap<String, User>,
+    router: Router,
+    sessions: HashMap<String, String>, // session_token -> user_id
+    reserved: HashSet<String>, // 登録中の名前 (username_key)
+    config: ServerConfig,
+    storage: StorageHandle,
+    commands: mpsc::WeakUnboundedSender<Command>, // タイマーから自分宛てに送る
+}
+
+impl Directory {
+    pub(crate) fn new(config: ServerConfig, storage: StorageHandle, commands: mpsc::WeakUnboundedSender<Command>) -> Self {
+        Self {
+            rooms: HashMap::new(),
+            users: HashMap::new(),
+            router: Router::new(),
+            sessions: HashMap::new(),
+            reserved: HashSet::new(),
+            config,
+            storage,
+            commands,
+        }
+    }
+
+    // 参加・退出などメンバーシップの変更だけをここで順番に処理する
+    // (保存されていたルームを起動し終えるまでコマンドは待たせる)
+    pub(crate) async fn run(mut self, mut commands: mpsc::UnboundedReceiver<Command>) {
+        self.restore_rooms().await;
+        while let Some(command) = commands.recv().await {
+            self.handle_command(command).await;
+        }
+    }
+
+    async fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::Login { username, registered, account_exists, capabilities, connection, reply } => {
+                let result = self.handle_login(username, registered, account_exists, capabilities, connection).await;
+                let _ = reply.send(result);
+            }
+            Command::ReserveName { username, reply } => {
+                let _ = reply.send(self.reserve_name(&username));
+            }
+            Command::Register { username, created, capabilities, connection, reply } => {
+                let result = self.handle_register(username, created, capabilities, connection).await;
+                let _ = reply.send(result);
+            }
+            Command::Message { user_id, message, reply } => {
+                self.handle_message(user_id, message, reply).await;
+            }
+            Command::ChangeNick { user_id, username, account_exists, reply } => {
+                if self.users.contains_key(&user_id) {
+                    self.change_nick(&user_id, username, account_exists).await;
+                }
+                let _ = reply.send(());
+            }
+            Command::Resume { token, last_seen_seq, capabilities, connection, reply } => {
+                let _ = reply.send(self.handle_resume(&token, last_seen_seq, capabilities, connection));
+            }
+            Command::Disconnect { user_id, connection, reply } => {
+                self.handle_user_disconnect(&user_id, &connection).await;
+                let _ = reply.send(());
+            }
+            Command::ExpireSession { user_id } => {
+                let grace = self.config.resume_grace.to_std().unwrap_or_default();
+                let expired = self.users.get(&user_id).is_some_and(|u| u.detached_at.is_some_and(|at| at.elapsed() >= grace));
+                if expired {
+                    self.remove_user(&user_id).await;
+                }
+            }
+            Command::CreateRoom { user_id, room_name, access, reply } => {
+                self.create_room(&user_id, room_name, access);
+                let _ = reply.send(());
+            }
+            Command::JoinRoom { user_id, room_name, password, reply } => {
+                self.join_room(&user_id, room_name, password).await;
+                let _ = reply.send(());
+            }
+            Command::RoomPasswordHash { room_name, reply } => {
+                let _ = reply.send(self.rooms.get(&room_name).and_then(|room| room.password_hash.clone()));
+            }
+            Command::RoomList { reply } => {
+                let _ = reply.send(self.room_list(None));
+            }
+            Command::Flush { reply } => {
+                self.storage.request(|reply| StorageCommand::Flush { reply }).await;
+                let _ = reply.send(());
+            }
+        }
+    }
+
+    // 保存されていたルームを履歴ごと起動し、general がなければ作る
+    async fn restore_rooms(&mut self) {
+        let message_limit = self.config.retention.capacity;
+        let stored = self.storage.request(|reply| StorageCommand::LoadRooms { message_limit, reply }).await;
+        for room in stored.unwrap_or_default() {
+            info!("Restoring room {} ({} messages)", room.record.name, room.messages.len());
+            self.start_room(ChatRoom::restore(room, self.config.retention));
+        }
+
+        if !self.rooms.contains_key("general") {
+            self.spawn_room("general".to_string(), None, RoomAccess::default());
+        }
+    }
+
+    // 名前が使えない場合はエラーを返す (接続はログイン前のまま)
+    async fn handle_login(&mut self, username: String, registered: bool, account_exists: bool, capabilities: Vec<Capability>, connection: Arc<dyn Connection>) -> Result<String, AuthError> {
+        if registered {
+            // 同じアカウントで 2 本目の接続はできない。再開待ちのセッションは破棄してログインし直す
+            let key = username_key(&username);
+            let existing = self.users.values().find(|u| username_key(&u.username) == key).map(|u| (u.id.clone(), u.detached_at.is_some()));
+            match existing {
+                Some((_, false)) => return Err(AuthError::new(ErrorCode::AccountInUse, "Account is already logged in")),
+                Some((user_id, true)) => self.remove_user(&user_id).await,
+                None => {}
+            }
+        } else {
+            if !self.config.allow_guests {
+                return Err(AuthError::new(ErrorCode::GuestsDisabled, "Guest logins are disabled"));
+            }
+            self.check_username(&username, None, account_exists)?;
+        }
+
+        Ok(self.add_user(username, registered, capabilities, connection).await)
+    }
+
+    // 書式はセッション側で確認済み。ゲストが使っている名前は登録させない
+    fn reserve_name(&mut self, username: &str) -> Result<(), AuthError> {
+        if self.is_taken(username, None) {
+            return Err(AuthError::new(ErrorCode::UsernameTaken, "Username already taken"));
+        }
+        self.reserved.insert(username_key(username));
+        Ok(())
+    }
+
+    async fn handle_register(&mut self, username: String, created: Option<bool>, capabilities: Vec<Capability>, connection: Arc<dyn Connection>) -> Result<String, AuthError> {
+        self.reserved.remove(&username_key(&username));
+        match created {
+            Some(true) => info!("Registered account {}", username),
+            Some(false) => return Err(AuthError::new(ErrorCode::UsernameTaken, "Username is already registered")),
+            None => return Err(AuthError::new(ErrorCode::ServerUnavailable, "Server unavailable")),
+        }
+
+        Ok(self.add_user(username, true, capabilities, connection).await)
+    }
+
+    async fn add_user(&mut self, username: String, registered: bool, capabilities: Vec<Capability>, connection: Arc<dyn Connection>) -> String {
+        let uid = Uuid::new_v4().to_string();
+        let outbox = Arc::new(Outbox::new(connection, capabilities));
+        let session_token = (self.config.resume_grace > TimeDelta::zero()).then(|| Uuid::new_v4().to_string());
+
+        let user = User {
+            id: uid.clone(),
+            username: username.clone(),
+            registered,
+            rooms: BTreeSet::new(),
+            outbox: Arc::clone(&outbox),
+            session_token: session_token.clone(),
+            detached_at: None,
+        };
+
+        // ユーザーを追加
+        self.users.insert(uid.clone(), user);
+        if let Some(token) = &session_token {
+            self.sessions.insert(token.clone(), uid.clone());
+        }
+        self.storage.send(StorageCommand::TouchUser { username: username.clone(), at: Utc::now() });
+        self.router.register(uid.clone(), outbox);
+
+        // ウェルカムメッセージ
+        let welcome_msg = ServerMessage::Welcome { user_id: uid.clone(), session_token };
+        self.send_to_user(&uid, welcome_msg);
+
+        // 一般ルームに追加 (ログイン時は JoinedRoom を送らない)
+        self.enter_room(&uid, "general", false, None).await;
+
+        info!("User {} logged in{}", username, if registered { "" } else { " as a guest" });
+        uid
+    }
+
+    // ゲストの名前の書式と重複 (接続中のユーザー・登録中の名前・登録済みのアカウント) を確認する
+    // except には自分自身 (名前変更時) を渡す
+    fn check_username(&self, username: &str, except: Option<&str>, account_exists: bool) -> Result<(), AuthError> {
+        validate_username(username).map_err(|message| AuthError::new(ErrorCode::InvalidUsername, message))?;
+
+        if self.is_taken(username, except) {
+            return Err(AuthError::new(ErrorCode::UsernameTaken, "Username already taken"));
+        }
+        if account_exists {
+            return Err(AuthError::new(ErrorCode::UsernameTaken, "Username is already registered"));
+        }
+        Ok(())
+    }
+
+    fn is_taken(&self, username: &str, except: Option<&str>) -> bool {
+        let key = username_key(username);
+        self.reserved.contains(&key) || self.users.values().any(|u| Some(u.id.as_str()) != except && username_key(&u.username) == key)
+    }
+
+    async fn handle_message(&mut self, user_id: String, message: ClientMessage, reply: oneshot::Sender<()>) {
+        let Some(user) = self.users.get(&user_id) else {
+            let _ = reply.send(());
+            return;
+        };
+        let joined = user.rooms.clone();
+
+        match message {
+            // ルーム内の処理はルームのタスクへ渡し、応答もそこから返す
+            ClientMessage::SendMessage { room_name, content, reply_to } => {
+                if let Some(room) = self.rooms.get(&room_name).filter(|_| joined.contains(&room_name)) {
+                    room.send(RoomCommand::Post { user_id, content, reply_to, reply });
+                    return;
+                }
+                self.send_not_in_room(&user_id);
+            }
+
+            ClientMessage::EditMessage { room_name, message_id, content } => {
+                if let Some(room) = self.rooms.get(&room_name).filter(|_| joined.contains(&room_name)) {
+                    room.send(RoomCommand::EditMessage { user_id, message_id, content: Some(content), reply });
+                    return;
+                }
+                self.send_not_in_room(&user_id);
+            }
+
+            ClientMessage::DeleteMessage { room_name, message_id } => {
+                if let Some(room) = self.rooms.get(&room_name).filter(|_| joined.contains(&room_name)) {
+                    room.send(RoomCommand::EditMessage { user_id, message_id, content: None, reply });
+                    return;
+                }
+                self.send_not_in_room(&user_id);
+            }
+
+            ClientMessage::GetThread { room_name, message_id } => {
+                if let Some(room) = self.rooms.get(&room_name).filter(|_| joined.contains(&room_name)) {
+                    room.send(RoomCommand::GetThread { user_id, message_id, reply });
+                    return;
+                }
+                self.send_not_in_room(&user_id);
+            }
+
+            ClientMessage::React { room_name, message_id, emoji } => {
+                if let Some(room) = self.rooms.get(&room_name).filter(|_| joined.contains(&room_name)) {
+                    room.send(RoomCommand::React { user_id, message_id, emoji, add: true, reply });
+                    return;
+                }
+                self.send_not_in_room(&user_id);
+            }
+
+            ClientMessage::Unreact { room_name, message_id, emoji } => {
+                if let Some(room) = self.rooms.get(&room_name).filter(|_| joined.contains(&room_name)) {
+                    room.send(RoomCommand::React { user_id, message_id, emoji, add: false, reply });
+                    return;
+                }
+                self.send_not_in_room(&user_id);
+            }
+
+            ClientMessage::MarkRead { room_name, seq } => {
+                if let Some(room) = self.rooms.get(&room_name).filter(|_| joined.contains(&room_name)) {
+                    room.send(RoomCommand::MarkRead { user_id, seq, reply });
+                    return;
+                }
+                self.send_not_in_room(&user_id);
+            }
+
+            ClientMessage::Typing { room_name } => {
+                if let Some(room) = self.rooms.get(&room_name).filter(|_| joined.contains(&room_name)) {
+                    room.send(RoomCommand::Typing { user_id, reply });
+                    return;
+                }
+                self.send_not_in_room(&user_id);
+            }
+
+            ClientMessage::ListUsers { room_name } => {
+                if let Some(room) = self.rooms.get(&room_name).filter(|_| joined.contains(&room_name)) {
+                    room.send(RoomCommand::ListUsers { user_id, reply });
+                    return;
+                }
+                self.send_not_in_room(&user_id);
+            }
+
+            ClientMessage::LeaveRoom { room_name } => {
+                if !joined.contains(&room_name) {
+                    self.send_not_in_room(&user_id);
+                } else {
+                    self.leave_room(&user_id, &room_name).await;
+
+                    // 退出確認をユーザーに送信
+                    let left_msg = ServerMessage::LeftRoom { room_name };
+                    self.send_to_user(&user_id, left_msg);
+                }
+            }
+
+            ClientMessage::ListRooms => {
+                // 未読数はルームごとに問い合わせるので、ディレクトリのタスクを止めないよう別のタスクで集めて送る
+                let room_names = self.room_list(Some(&user_id));
+                let rooms: Vec<(String, RoomHandle)> = joined.iter()
+                    .filter_map(|name| Some((name.clone(), self.rooms.get(name)?.clone())))
+                    .collect();
+                let Some(outbox) = self.users.get(&user_id).map(|u| Arc::clone(&u.outbox)) else {
+                    let _ = reply.send(());
+                    return;
+                };
+                tokio::spawn(async move {
+                    let unread = unread_counts(&user_id, rooms).await;
+                    outbox.send(ServerMessage::RoomList { rooms: room_names, unread });
+                    let _ = reply.send(());
+                });
+                return;
+            }
+
+            ClientMessage::DirectMessage { to, content } => {
+                self.send_direct_message(&user_id, &to, content);
+            }
+
+            ClientMessage::GrantModerator { room_name, username } => {
+                self.moderate(&user_id, &joined, &room_name, ModAction::GrantModerator { username }).await;
+            }
+
+            ClientMessage::Kick { room_name, username } => {
+                self.moderate(&user_id, &joined, &room_name, ModAction::Kick { username }).await;
+            }
+
+            ClientMessage::Ban { room_name, username, duration_secs } => {
+                self.moderate(&user_id, &joined, &room_name, ModAction::Ban { username, duration_secs }).await;
+            }
+
+            ClientMessage::Unban { room_name, username } => {
+                self.moderate(&user_id, &joined, &room_name, ModAction::Unban { username }).await;
+            }
+
+            ClientMessage::Mute { room_name, username } => {
+                self.moderate(&user_id, &joined, &room_name, ModAction::Mute { username }).await;
+            }
+
+            ClientMessage::Unmute { room_name, username } => {
+                self.moderate(&user_id, &joined, &room_name, ModAction::Unmute { username }).await;
+            }
+
+            ClientMessage::Invite { room_name, username } => {
+                self.invite(&user_id, &joined, &room_name, &username).await;
+            }
+
+            _ => {}
+        }
+
+        let _ = reply.send(());
+    }
+
+    // CreateRoom と JoinRoom はパスワードを扱うので、ChatServer が Command::CreateRoom / JoinRoom にして送ってくる
+    fn create_room(&mut self, user_id: &str, room_name: String, access: Result<RoomAccess, String>) {
+        let Some(user) = self.users.get(user_id) else {
+            return;
+        };
+        if self.rooms.contains_key(&room_name) {
+            let error_msg = ServerMessage::Error {
+                message: "Room already exists".to_string(),
+                code: None,
+            };
+            self.send_to_user(user_id, error_msg);
+            return;
+        }
+
+        match access {
+            Ok(access) => {
+                // 作成者がオーナーになる
+                let owner = user.identity();
+                self.spawn_room(room_name.clone(), Some(&owner), access);
+
+                let response = ServerMessage::RoomCreated { room_name };
+                self.send_to_user(user_id, response);
+            }
+            Err(message) => self.send_to_user(user_id, ServerMessage::Error { message, code: None }),
+        }
+    }
+
+    async fn join_room(&mut self, user_id: &str, room_name: String, password: Option<bool>) {
+        let Some(user) = self.users.get(user_id) else {
+            return;
+        };
+        if user.rooms.contains(&room_name) {
+            let error_msg = ServerMessage::Error {
+                message: "Already in room".to_string(),
+                code: None,
+            };
+            self.send_to_user(user_id, error_msg);
+        } else if self.rooms.contains_key(&room_name) {
+            // 参加中の他のルームはそのまま
+            self.enter_room(user_id, &room_name, true, password).await;
+        } else {
+            let error_msg = ServerMessage::Error {
+                message: "Room not found".to_string(),
+                code: None,
+            };
+            self.send_to_user(user_id, error_msg);
+        }
+    }
+
+    fn spawn_room(&mut self, room_name: String, owner: Option<&str>, access: RoomAccess) {
+        let mut room = ChatRoom::with_retention(room_name.clone(), self.config.retention);
+        room.access = access;
+        if let Some(owner) = owner {
+            room.moderation = Moderation::with_owner(owner);
+        }
+        self.storage.send(StorageCommand::SaveRoom { record: room.record() });
+        self.start_room(room);
+    }
+
+    fn start_room(&mut self, room: ChatRoom) {
+        let room_name = room.name.clone();
+        let handle = RoomHandle::spawn(room, self.config.history_limit, self.config.typing_timeout, self.storage.clone());
+        self.rooms.insert(room_name, handle);
+    }
+
+    // ルームのタスクが参加を処理し終えるまで待つ (BAN やパスワード違いなら参加できない)
+    async fn enter_room(&mut self, user_id: &str, room_name: &str, confirm: bool, password: Option<bool>) {
+        let (Some(user), Some(room)) = (self.users.get(user_id), self.rooms.get(room_name)) else {
+            return;
+        };
+
+        let send_history = user.outbox.supports(Capability::History);
+        let username = user.username.clone();
+        let identity = user.identity();
+        let connection: Arc<dyn Connection> = user.outbox.clone();
+        let result = room.request(|reply| RoomCommand::Join {
+            user_id: user_id.to_string(),
+            username,
+            identity,
+            connection,
+            send_history,
+            confirm,
+            password,
+            reply,
+        })
+        .await;
+
+        match result {
+            Some(Ok(())) => {
+                if let Some(user) = self.users.get_mut(user_id) {
+                    user.rooms.insert(room_name.to_string());
+                }
+            }
+            Some(Err(message)) => self.send_to_user(user_id, ServerMessage::Error { message, code: None }),
+            None => {}
+        }
+    }
+
+    // 判定はルームのタスクが行う。外されたメンバーには LeftRoom を送る
+    async fn moderate(&mut self, user_id: &str, joined: &BTreeSet<String>, room_name: &str, action: ModAction) {
+        let Some(room) = self.rooms.get(room_name).filter(|_| joined.contains(room_name)) else {
+            self.send_not_in_room(user_id);
+            return;
+        };
+
+        let target = self.find_identity(action.username());
+        let user_id = user_id.to_string();
+        let result = room.request(|reply| RoomCommand::Moderate { user_id: user_id.clone(), action, target, reply }).await;
+        match result {
+            Some(Ok(Some(removed_id))) => {
+                if let Some(user) = self.users.get_mut(&removed_id) {
+                    user.rooms.remove(room_name);
+                }
+                let left_msg = ServerMessage::LeftRoom { room_name: room_name.to_string() };
+                self.send_to_user(&removed_id, left_msg);
+            }
+            Some(Err(message)) => self.send_to_user(&user_id, ServerMessage::Error { message, code: None }),
+            _ => {}
+        }
+    }
+
+    // ユーザー名で指定された相手の identity_key。接続中のユーザーでなければアカウントの名前とみなす
+    fn find_identity(&self, username: &str) -> String {
+        let key = username_key(username);
+        match self.users.values().find(|u| username_key(&u.username) == key) {
+            Some(user) => user.identity(),
+            None => key,
+        }
+    }
+
+    // ルームのタスクが招待を記録したら、招待された本人にも同じ通知を送る
+    async fn invite(&mut self, user_id: &str, joined: &BTreeSet<String>, room_name: &str, username: &str) {
+        let Some(room) = self.rooms.get(room_name).filter(|_| joined.contains(room_name)) else {
+            self.send_not_in_room(user_id);
+            return;
+        };
+
+        let key = username_key(username);
+        let Some(target) = self.users.values().find(|u| username_key(&u.username) == key) else {
+            let error_msg = ServerMessage::Error {
+                message: "User not found".to_string(),
+                code: None,
+            };
+            self.send_to_user(user_id, error_msg);
+            return;
+        };
+
+        let (target_id, username, identity) = (target.id.clone(), target.username.clone(), target.identity());
+        let user_id = user_id.to_string();
+        match room.request(|reply| RoomCommand::Invite { user_id: user_id.clone(), username, identity, reply }).await {
+            Some(Ok(event)) => self.send_to_user(&target_id, event),
+            Some(Err(message)) => self.send_to_user(&user_id, ServerMessage::Error { message, code: None }),
+            None => {}
+        }
+    }
+
+    async fn leave_room(&mut self, user_id: &str, room_name: &str) {
+        if let Some(u) = self.users.get_mut(user_id) {
+            u.rooms.remove(room_name);
+        }
+
+        // 残りのメンバーへの退出通知はルームのタスクが行う
+        if let Some(room) = self.rooms.get(room_name) {
+            let user_id = user_id.to_string();
+            room.request(|reply| RoomCommand::Leave { user_id, reply }).await;
+        }
+    }
+
+    // ユーザー名 (大文字小文字を区別しない) で宛先を探し、そのユーザーの接続にだけ送る
+    fn send_direct_message(&self, user_id: &str, to: &str, content: String) {
+        let Some(sender) = self.users.get(user_id) else {
+            return;
+        };
+
+        // ユーザー名は大文字小文字を区別せず一意
+        let key = username_key(to);
+        let Some(recipient) = self.users.values().find(|u| username_key(&u.username) == key) else {
+            let error_msg = ServerMessage::Error {
+                message: "User not found".to_string(),
+                code: None,
+            };
+            self.send_to_user(user_id, error_msg);
+            return;
+        };
+        // DirectMessages を合意していないクライアントには送らない
+        if !recipient.outbox.supports(Capability::DirectMessages) {
+            let error_msg = ServerMessage::Error {
+                message: "User does not accept direct messages".to_string(),
+                code: None,
+            };
+            self.send_to_user(user_id, error_msg);
+            return;
+        }
+
+        // 再開待ちの相手にもエラーにせず送信口に溜め、再開したときに届ける
+        // (猶予が切れて再開されなければ捨てられる。そのあとは "User not found" になる)
+        let dm = ServerMessage::DirectMessage {
+            from: sender.username.clone(),
+            content,
+            timestamp: Utc::now().to_rfc3339(),
+        };
+        self.send_to_user(&recipient.id, dm);
+    }
+
+    // 参加中のルームの表示名を更新し、同じルームのユーザーに一度ずつ通知する
+    async fn change_nick(&mut self, user_id: &str, username: String, account_exists: bool) {
+        // アカウントの名前は変えられない
+        if self.users.get(user_id).is_some_and(|u| u.registered) {
+            let error_msg = ServerMessage::Error {
+                message: "Registered users cannot change their username".to_string(),
+                code: None,
+            };
+            self.send_to_user(user_id, error_msg);
+            return;
+        }
+        if let Err(error) = self.check_username(&username, Some(user_id), account_exists) {
+            self.send_to_user(user_id, error.into());
+            return;
+        }
+
+        let Some(user) = self.users.get_mut(user_id) else {
+            return;
+        };
+        let old_username = std::mem::replace(&mut user.username, username.clone());
+        let joined = user.rooms.clone();
+
+        for room in joined.iter().filter_map(|name| self.rooms.get(name)) {
+            let user_id = user_id.to_string();
+            let username = username.clone();
+            room.request(|reply| RoomCommand::Rename { user_id, username, reply }).await;
+        }
+
+        info!("User {} is now known as {}", old_username, username);
+        self.storage.send(StorageCommand::TouchUser { username: username.clone(), at: Utc::now() });
+
+        let nick_msg = ServerMessage::NickChanged { old_username, new_username: username };
+        let recipients: Vec<String> = self.users.values()
+            .filter(|u| u.id == user_id || !u.rooms.is_disjoint(&joined))
+            .map(|u| u.id.clone())
+            .collect();
+        for recipient in recipients {
+            self.send_to_user(&recipient, nick_msg.clone());
+        }
+    }
+
+    // 非表示のルームはそのメンバーにだけ見せる
+    fn room_list(&self, user_id: Option<&str>) -> Vec<String> {
+        let joined = user_id.and_then(|id| self.users.get(id)).map(|u| &u.rooms);
+        self.rooms.iter()
+            .filter(|(name, room)| !room.hidden || joined.is_some_and(|rooms| rooms.contains(*name)))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    // 参加中の各ルームのタスクに未読数を問い合わせる
+    fn send_not_in_room(&self, user_id: &str) {
+        let error_msg = ServerMessage::Error {
+            message: "Not in room".to_string(),
+            code: None,
+        };
+        self.send_to_user(user_id, error_msg);
+    }
+
+    fn send_to_user(&self, user_id: &str, message: ServerMessage) {
+        if !self.router.send_to_user(user_id, message) {
+            error!("Failed to deliver message to user {}", user_id);
+        }
+    }
+
+    // 古い接続がまだ切断を検知していなくても、新しい接続に切り替える
+    fn handle_resume(&mut self, token: &str, last_seen_seq: u64, capabilities: Vec<Capability>, connection: Arc<dyn Connection>) -> Result<String, AuthError> {
+        let Some(user) = self.sessions.get(token).and_then(|user_id| self.users.get_mut(user_id)) else {
+            return Err(AuthError::new(ErrorCode::InvalidSession, "Session expired or unknown"));
+        };
+        // まだ送っていない番号まで受け取ったというクライアントは、別のセッションと取り違えている
+        if last_seen_seq > user.outbox.last_seq() {
+            return Err(AuthError::new(ErrorCode::InvalidSession, "Last seen sequence number is ahead of the session"));
+        }
+        user.detached_at = None;
+
+        // Resumed は番号を付けずに先に送り、続けて取りこぼした分を送り直す
+        connection.send(ServerMessage::Resumed {
+            user_id: user.id.clone(),
+            username: user.username.clone(),
+            rooms: user.rooms.iter().cloned().collect(),
+        });
+        user.outbox.attach(connection, last_seen_seq, capabilities);
+
+        info!("User {} resumed their session", user.username);
+        Ok(user.id.clone())
+    }
+
+    // 猶予の間はルームに残したまま送信口だけ切り離し、メッセージは溜めておく
+    async fn handle_user_disconnect(&mut self, user_id: &str, connection: &Arc<dyn Connection>) {
+        let Some(user) = self.users.get_mut(user_id) else {
+            return;
+        };
+        if !user.outbox.detach(connection) {
+            // 別の接続ですでに再開している
+            return;
+        }
+        if user.session_token.is_none() {
+            self.remove_user(user_id).await;
+            return;
+        }
+
+        info!("User {} ({}) disconnected, holding the session for {}s", user.username, user.id, self.config.resume_grace.num_seconds());
+        user.detached_at = Some(Instant::now());
+        self.schedule_expiry(user_id.to_string());
+    }
+
+    fn schedule_expiry(&self, user_id: String) {
+        let commands = self.commands.clone();
+        let grace = self.config.resume_grace.to_std().unwrap_or_default();
+        tokio::spawn(async move {
+            tokio::time::sleep(grace).await;
+            if let Some(commands) = commands.upgrade() {
+                let _ = commands.send(Command::ExpireSession { user_id });
+            }
+        });
+    }
+
+    async fn remove_user(&mut self, user_id: &str) {
+        let user = self.users.remove(user_id);
+        self.router.unregister(user_id);
+
+        if let Some(user) = user {
+            info!("User {} ({}) left", user.username, user.id);
+            if let Some(token) = &user.session_token {
+                self.sessions.remove(token);
+            }
+
+            // 参加中のすべてのルームから離脱
+            for room_name in &user.rooms {
+                self.leave_room(user_id, room_name).await;
+            }
+        }
+    }
+}
+
+// 参加中のルームの未読数 (応答のなかったルームは含めない)
+async fn unread_counts(user_id: &str, rooms: Vec<(String, RoomHandle)>) -> BTreeMap<String, u64> {
+    let mut unread = BTreeMap::new();
+    for (room_name, room) in rooms {
+        let user_id = user_id.to_string();
+        if let Some(Some(count)) = room.request(|reply| RoomCommand::UnreadCount { user_id, reply }).await {
+            unread.insert(room_name, count);
+        }
+    }
+    unread
+}
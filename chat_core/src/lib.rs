@@ -0,0 +1,17 @@
+pub mod access;
+pub mod auth;
+pub mod config;
+pub mod connection;
+mod directory;
+pub mod moderation;
+mod outbox;
+pub mod room;
+mod room_actor;
+pub mod router;
+pub mod server;
+pub mod session;
+pub mod storage;
+mod storage_actor;
+pub mod store;
+pub mod transport;
+pub mod username;
@@ -0,0 +1,66 @@
+use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, Utc};
+
+// ルームのオーナー・モデレーターと制裁の状態。
+// どれも identity_key で管理する (ゲストが名前を変えたり、別のゲストが同じ名前を使ったりしても引き継がれない)
+#[derive(Debug, Default)]
+pub struct Moderation {
+    pub(crate) owner: Option<String>,
+    pub(crate) moderators: HashSet<String>,
+    pub(crate) bans: HashMap<String, Option<DateTime<Utc>>>, // 期限 (None は無期限)
+    pub(crate) muted: HashSet<String>,
+}
+
+impl Moderation {
+    pub fn with_owner(identity: &str) -> Self {
+        Self {
+            owner: Some(identity.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_owner(&self, identity: &str) -> bool {
+        self.owner.as_deref() == Some(identity)
+    }
+
+    // オーナーはモデレーターを兼ねる
+    pub fn is_moderator(&self, identity: &str) -> bool {
+        self.is_owner(identity) || self.moderators.contains(identity)
+    }
+
+    pub fn grant_moderator(&mut self, identity: &str) {
+        self.moderators.insert(identity.to_string());
+    }
+
+    pub fn ban(&mut self, identity: &str, expires_at: Option<DateTime<Utc>>) {
+        self.bans.insert(identity.to_string(), expires_at);
+    }
+
+    pub fn unban(&mut self, identity: &str) -> bool {
+        self.bans.remove(identity).is_some()
+    }
+
+    // 期限切れの BAN はここで取り除く
+    pub fn is_banned(&mut self, identity: &str, now: DateTime<Utc>) -> bool {
+        match self.bans.get(identity) {
+            Some(Some(expires_at)) if *expires_at <= now => {
+                self.bans.remove(identity);
+                false
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    pub fn mute(&mut self, identity: &str) {
+        self.muted.insert(identity.to_string());
+    }
+
+    pub fn unmute(&mut self, identity: &str) -> bool {
+        self.muted.remove(identity)
+    }
+
+    pub fn is_muted(&self, identity: &str) -> bool {
+        self.muted.contains(identity)
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use log::warn;
+
+use chat_protocol::{Capability, ServerMessage};
+use crate::connection::Connection;
+
+// 再開時に再送できるよう残しておくメッセージ数
+pub const RESUME_BUFFER_LEN: usize = 1000;
+
+#[derive(Debug, Default)]
+struct OutboxState {
+    connection: Option<Arc<dyn Connection>>, // None は切断中
+    capabilities: Vec<Capability>, // 今の接続がハンドシェイクで合意した機能
+    last_seq: u64,
+    sent: VecDeque<(u64, ServerMessage)>, // 直近に送ったメッセージ (切断中は送れなかったものも含む)
+}
+
+// ログイン中のユーザーの送信口。ルーターとルームにはこれを配る
+// 送るたびに通し番号を振って直近の分を残し、切断中は溜めておいて再開時に新しい接続へ送り直す
+#[derive(Debug, Default)]
+pub(crate) struct Outbox {
+    state: Mutex<OutboxState>,
+}
+
+impl Outbox {
+    pub(crate) fn new(connection: Arc<dyn Connection>, capabilities: Vec<Capability>) -> Self {
+        Self { state: Mutex::new(OutboxState { connection: Some(connection), capabilities, ..Default::default() }) }
+    }
+
+    // 今の接続が connection のときだけ切り離す (新しい接続で再開済みなら何もしない)
+    pub(crate) fn detach(&self, connection: &Arc<dyn Connection>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.connection.as_ref().is_some_and(|current| Arc::ptr_eq(current, connection)) {
+            state.connection = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    // 最後に振った通し番号 (まだ何も送っていなければ 0)
+    pub(crate) fn last_seq(&self) -> u64 {
+        self.state.lock().unwrap().last_seq
+    }
+
+    // last_seen_seq より後のメッセージを新しい接続へ送り直し、以後はそちらへ送る
+    // (last_seen_seq が last_seq を越えていないことは呼び出し側で確認する)
+    // 溜めてあるメッセージは送ったときの機能で選んだものなので、新しい接続の機能では選び直さない
+    pub(crate) fn attach(&self, connection: Arc<dyn Connection>, last_seen_seq: u64, capabilities: Vec<Capability>) {
+        let mut state = self.state.lock().unwrap();
+        state.capabilities = capabilities;
+        let first_missed = last_seen_seq.saturating_add(1);
+        if let Some((oldest, _)) = state.sent.front()
+            && *oldest > first_missed
+        {
+            warn!("Messages {}..{} can no longer be resent", first_missed, oldest);
+        }
+        for (seq, message) in state.sent.iter().filter(|(seq, _)| *seq > last_seen_seq) {
+            connection.send_sequenced(*seq, message.clone());
+        }
+        state.connection = Some(connection);
+    }
+}
+
+impl Connection for Outbox {
+    // 切断中でも溜めておけるので、送れなかったことにはしない
+    fn send(&self, message: ServerMessage) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.last_seq += 1;
+        let seq = state.last_seq;
+        if let Some(connection) = &state.connection {
+            connection.send_sequenced(seq, message.clone());
+        }
+        state.sent.push_back((seq, message));
+        if state.sent.len() > RESUME_BUFFER_LEN {
+            state.sent.pop_front();
+        }
+        true
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        self.state.lock().unwrap().capabilities.contains(&capability)
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use chat_protocol::{HistoryMessage, Reaction};
+use crate::access::RoomAccess;
+use crate::moderation::Moderation;
+use crate::storage::{RoomRecord, StoredRoom};
+use crate::store::{MessageStore, RetentionPolicy};
+use crate::username::{is_guest_identity, username_key};
+
+// リアクションの絵文字の最大文字数 (ZWJ で繋いだ絵文字は複数文字になる)
+pub const MAX_EMOJI_LEN: usize = 16;
+
+// 1 つのメッセージに付けられる絵文字の種類の上限
+pub const MAX_REACTIONS_PER_MESSAGE: usize = 20;
+
+// メンバーがこの人数以下のルームでだけ ReadBy を送る
+pub const READ_RECEIPT_MAX_MEMBERS: usize = 20;
+
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+    pub id: String,
+    pub seq: u64, // ルーム内の通し番号 (ルームに追加したときに振られる)
+    pub sender: String,
+    pub author: String, // 投稿したユーザーの identity_key (編集・削除の権限に使う。表示名は変えられるので sender は使わない)
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub deleted: bool, // 削除したメッセージは中身を消して残す
+    pub reply_to: Option<String>, // スレッドの元のメッセージの ID
+    pub reactions: Vec<MessageReaction>, // 最初にリアクションされた順
+}
+
+// 1 つの絵文字にリアクションしたユーザー (リアクションした順)
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageReaction {
+    pub emoji: String,
+    pub users: Vec<Reactor>,
+}
+
+// 同じユーザーかは identity_key で判断する (username はリアクションしたときの表示名)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reactor {
+    pub identity: String,
+    pub username: String,
+}
+
+impl ChatMessage {
+    // ID と時刻は作成時に一度だけ決める
+    pub fn new(sender: String, author: String, content: String) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            seq: 0,
+            sender,
+            author,
+            content,
+            timestamp: Utc::now(),
+            edited_at: None,
+            deleted: false,
+            reply_to: None,
+            reactions: Vec::new(),
+        }
+    }
+
+    pub fn edit(&mut self, content: String, at: DateTime<Utc>) {
+        self.content = content;
+        self.edited_at = Some(at);
+    }
+
+    pub fn delete(&mut self) {
+        self.content.clear();
+        self.deleted = true;
+    }
+
+    // 追加したら true。同じ絵文字ですでにリアクションしていれば何もしない
+    pub fn react(&mut self, emoji: &str, identity: &str, username: &str) -> bool {
+        let reactor = Reactor { identity: identity.to_string(), username: username.to_string() };
+        match self.reactions.iter_mut().find(|r| r.emoji == emoji) {
+            Some(reaction) if reaction.users.iter().any(|u| u.identity == identity) => false,
+            Some(reaction) => {
+                reaction.users.push(reactor);
+                true
+            }
+            None => {
+                self.reactions.push(MessageReaction { emoji: emoji.to_string(), users: vec![reactor] });
+                true
+            }
+        }
+    }
+
+    // 外したら true。誰もいなくなった絵文字は消す
+    pub fn unreact(&mut self, emoji: &str, identity: &str) -> bool {
+        let Some(index) = self.reactions.iter().position(|r| r.emoji == emoji) else {
+            return false;
+        };
+        let users = &mut self.reactions[index].users;
+        let before = users.len();
+        users.retain(|u| u.identity != identity);
+        let removed = users.len() != before;
+        if users.is_empty() {
+            self.reactions.remove(index);
+        }
+        removed
+    }
+
+    // 配信用に絵文字ごとの件数とユーザーをまとめる。
+    // names (identity_key -> 今の表示名) にいないユーザーはリアクションしたときの名前で表す
+    pub fn reaction_summary(&self, names: &HashMap<String, String>) -> Vec<Reaction> {
+        self.reactions.iter().map(|r| Reaction {
+            emoji: r.emoji.clone(),
+            count: r.users.len() as u32,
+            users: r.users.iter().map(|u| names.get(&u.identity).unwrap_or(&u.username).clone()).collect(),
+        }).collect()
+    }
+
+    // 配信用の形 (names は reaction_summary と同じ)
+    pub fn to_history(&self, names: &HashMap<String, String>) -> HistoryMessage {
+        HistoryMessage {
+            id: self.id.clone(),
+            seq: self.seq,
+            sender: self.sender.clone(),
+            content: self.content.clone(),
+            timestamp: self.timestamp.to_rfc3339(),
+            edited_at: self.edited_at.map(|t| t.to_rfc3339()),
+            deleted: self.deleted,
+            reply_to: self.reply_to.clone(),
+            reactions: self.reaction_summary(names),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ChatRoom {
+    pub name: String,
+    pub users: HashMap<String, String>, // user_id -> username
+    pub identities: HashMap<String, String>, // user_id -> identity_key
+    pub messages: MessageStore,
+    pub moderation: Moderation,
+    pub access: RoomAccess,
+    pub read_markers: HashMap<String, u64>, // identity_key -> 既読にした最後の通し番号
+}
+
+impl ChatRoom {
+    pub fn new(name: String) -> Self {
+        Self::with_retention(name, RetentionPolicy::default())
+    }
+
+    pub fn with_retention(name: String, retention: RetentionPolicy) -> Self {
+        Self {
+            name,
+            users: HashMap::new(),
+            identities: HashMap::new(),
+            messages: MessageStore::new(retention),
+            moderation: Moderation::default(),
+            access: RoomAccess::default(),
+            read_markers: HashMap::new(),
+        }
+    }
+
+    // 保存されていたルームを作り直す (メンバーはいない状態で始まる)
+    pub fn restore(stored: StoredRoom, retention: RetentionPolicy) -> Self {
+        let record = stored.record;
+        let moderation = Moderation {
+            owner: record.owner,
+            moderators: record.moderators.into_iter().collect(),
+            bans: record.bans.into_iter().collect(),
+            muted: record.muted.into_iter().collect(),
+        };
+        let access = RoomAccess {
+            visibility: record.visibility,
+            password_hash: record.password_hash,
+            invited: record.invited.into_iter().collect(),
+            hidden: record.hidden,
+        };
+
+        Self {
+            name: record.name,
+            users: HashMap::new(),
+            identities: HashMap::new(),
+            messages: MessageStore::restore(retention, stored.messages),
+            moderation,
+            access,
+            read_markers: stored.read_markers.into_iter().collect(),
+        }
+    }
+
+    // 保存する設定 (メンバーとメッセージは含まない)
+    pub fn record(&self) -> RoomRecord {
+        let moderation = &self.moderation;
+        let mut bans: Vec<_> = moderation.bans.iter().map(|(name, expires_at)| (name.clone(), *expires_at)).collect();
+        bans.sort();
+
+        RoomRecord {
+            name: self.name.clone(),
+            owner: moderation.owner.clone(),
+            moderators: sorted(&moderation.moderators),
+            bans,
+            muted: sorted(&moderation.muted),
+            visibility: self.access.visibility,
+            password_hash: self.access.password_hash.clone(),
+            hidden: self.access.hidden,
+            invited: sorted(&self.access.invited),
+        }
+    }
+
+    pub fn add_user(&mut self, user_id: String, username: String, identity: String) -> bool {
+        self.identities.insert(user_id.clone(), identity);
+        self.users.insert(user_id, username).is_none()
+    }
+
+    // ゲストの既読位置は退出したら捨てる (同じゲストが戻ってくることはないので)
+    pub fn remove_user(&mut self, user_id: &str) -> Option<String> {
+        if let Some(identity) = self.identities.remove(user_id).filter(|identity| is_guest_identity(identity)) {
+            self.read_markers.remove(&identity);
+        }
+        self.users.remove(user_id)
+    }
+
+    pub fn identity(&self, user_id: &str) -> Option<&str> {
+        self.identities.get(user_id).map(String::as_str)
+    }
+
+    // 通し番号を振って追加し、番号の付いたメッセージを返す。保持ポリシーを越えた古いメッセージはストア側で捨てられる
+    pub fn add_message(&mut self, message: ChatMessage) -> ChatMessage {
+        let seq = self.messages.push(message.clone());
+        ChatMessage { seq, ..message }
+    }
+
+    // メンバーの identity_key -> 今の表示名
+    pub fn member_names(&self) -> HashMap<String, String> {
+        self.identities.iter()
+            .filter_map(|(user_id, identity)| Some((identity.clone(), self.users.get(user_id)?.clone())))
+            .collect()
+    }
+
+    // ユーザー名からメンバーの user_id を探す
+    pub fn find_user(&self, username: &str) -> Option<String> {
+        let key = username_key(username);
+        self.users.iter().find(|(_, name)| username_key(name) == key).map(|(id, _)| id.clone())
+    }
+
+    pub fn get_user_list(&self) -> Vec<String> {
+        self.users.values().cloned().collect()
+    }
+
+    pub fn get_message_history(&self, last_n: usize) -> Vec<ChatMessage> {
+        self.messages.last_n(last_n)
+    }
+
+    pub fn get_messages_since(&self, timestamp: DateTime<Utc>) -> Vec<ChatMessage> {
+        self.messages.since_timestamp(timestamp)
+    }
+
+    pub fn get_messages_after(&self, seq: u64) -> Vec<ChatMessage> {
+        self.messages.since_seq(seq)
+    }
+
+    // 既読位置を進め、進んだら新しい位置を返す (まだないメッセージの番号は最新に丸める)
+    pub fn mark_read(&mut self, identity: &str, seq: u64) -> Option<u64> {
+        let seq = seq.min(self.messages.last_seq()?);
+        if self.read_markers.get(identity).is_some_and(|&read| read >= seq) {
+            return None;
+        }
+        self.read_markers.insert(identity.to_string(), seq);
+        Some(seq)
+    }
+
+    // 初めて参加したときはそれまでのメッセージを既読とする。既読位置を作ったらその位置を返す
+    pub fn start_reading(&mut self, identity: &str) -> Option<u64> {
+        if self.read_markers.contains_key(identity) {
+            return None;
+        }
+        let seq = self.messages.last_seq()?;
+        self.read_markers.insert(identity.to_string(), seq);
+        Some(seq)
+    }
+
+    // 既読位置より後の、他のメンバーの削除されていないメッセージの数
+    pub fn unread_count(&self, identity: &str) -> u64 {
+        let read = self.read_markers.get(identity).copied();
+        self.messages.count(|m| !m.deleted && m.author != identity && read.is_none_or(|read| m.seq > read)) as u64
+    }
+
+    // 返信先のスレッドの元のメッセージの ID。返信への返信は同じスレッドにまとめる
+    pub fn thread_root(&self, message_id: &str) -> Option<String> {
+        let message = self.messages.find(message_id)?;
+        Some(message.reply_to.clone().unwrap_or_else(|| message.id.clone()))
+    }
+
+    // スレッドの元のメッセージと返信 (古い順)。元のメッセージが保持期間を過ぎて消えていれば None で、残っている返信だけを返す
+    pub fn get_thread(&self, message_id: &str) -> Option<(Option<ChatMessage>, Vec<ChatMessage>)> {
+        let root_id = self.thread_root(message_id).unwrap_or_else(|| message_id.to_string());
+        let root = self.messages.find(&root_id).cloned();
+        let replies = self.messages.filter(|m| m.reply_to.as_deref() == Some(root_id.as_str()));
+        if root.is_none() && replies.is_empty() {
+            return None;
+        }
+        Some((root, replies))
+    }
+}
+
+// 空白や制御文字を含まない短い文字列だけをリアクションとして受け付ける
+pub fn is_valid_emoji(emoji: &str) -> bool {
+    !emoji.is_empty() && emoji.chars().count() <= MAX_EMOJI_LEN && !emoji.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+fn sorted(names: &HashSet<String>) -> Vec<String> {
+    let mut names: Vec<String> = names.iter().cloned().collect();
+    names.sort();
+    names
+}
@@ -0,0 +1,658 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+use chrono::{DateTime, TimeDelta, Utc};
+
+use chat_protocol::{Capability, HistoryMessage, ServerMessage};
+use crate::connection::Connection;
+use crate::room::{is_valid_emoji, ChatMessage, ChatRoom, MAX_REACTIONS_PER_MESSAGE, READ_RECEIPT_MAX_MEMBERS};
+use crate::router::Router;
+use crate::storage_actor::{StorageCommand, StorageHandle};
+use crate::username::is_guest_identity;
+
+// ルームタスクへの要求。処理が終わると reply で応答する
+#[derive(Debug)]
+pub(crate) enum RoomCommand {
+    Join {
+        user_id: String,
+        username: String,
+        identity: String,
+        connection: Arc<dyn Connection>,
+        send_history: bool,
+        confirm: bool, // JoinedRoom を本人に送るか (ログイン時の general では送らない)
+        password: Option<bool>, // 照合済みのパスワード (None は入力なし)
+        reply: oneshot::Sender<Result<(), String>>,
+    },
+    Leave {
+        user_id: String,
+        reply: oneshot::Sender<Option<String>>,
+    },
+    Post {
+        user_id: String,
+        content: String,
+        reply_to: Option<String>,
+        reply: oneshot::Sender<()>,
+    },
+    // content が None なら削除する
+    EditMessage {
+        user_id: String,
+        message_id: String,
+        content: Option<String>,
+        reply: oneshot::Sender<()>,
+    },
+    GetThread {
+        user_id: String,
+        message_id: String,
+        reply: oneshot::Sender<()>,
+    },
+    // add が false ならリアクションを外す
+    React {
+        user_id: String,
+        message_id: String,
+        emoji: String,
+        add: bool,
+        reply: oneshot::Sender<()>,
+    },
+    Typing {
+        user_id: String,
+        reply: oneshot::Sender<()>,
+    },
+    MarkRead {
+        user_id: String,
+        seq: u64,
+        reply: oneshot::Sender<()>,
+    },
+    // メンバーでなければ None
+    UnreadCount {
+        user_id: String,
+        reply: oneshot::Sender<Option<u64>>,
+    },
+    // Typing が途絶えていれば入力中を解除する (ルーム自身のタイマーが送る)
+    TypingExpired {
+        user_id: String,
+    },
+    ListUsers {
+        user_id: String,
+        reply: oneshot::Sender<()>,
+    },
+    Rename {
+        user_id: String,
+        username: String,
+        reply: oneshot::Sender<()>,
+    },
+    // 成功するとルームから外したメンバーの user_id を返す (キック・BAN)
+    // target は対象の identity_key (ルームにいない相手もいるので、接続中のユーザーからディレクトリが引く)
+    Moderate {
+        user_id: String,
+        action: ModAction,
+        target: String,
+        reply: oneshot::Sender<Result<Option<String>, String>>,
+    },
+    // 招待を記録し、メンバーに通知する (招待された本人への通知はディレクトリが行う)
+    Invite {
+        user_id: String,
+        username: String,
+        identity: String,
+        reply: oneshot::Sender<Result<ServerMessage, String>>,
+    },
+}
+
+// モデレーション操作。対象はユーザー名で指定する (BAN の期限は権限を確認してから計算する)
+#[derive(Debug)]
+pub(crate) enum ModAction {
+    GrantModerator { username: String },
+    Kick { username: String },
+    Ban { username: String, duration_secs: Option<u64> },
+    Unban { username: String },
+    Mute { username: String },
+    Unmute { username: String },
+}
+
+impl ModAction {
+    pub(crate) fn username(&self) -> &str {
+        match self {
+            ModAction::GrantModerator { username }
+            | ModAction::Kick { username }
+            | ModAction::Ban { username, .. }
+            | ModAction::Unban { username }
+            | ModAction::Mute { username }
+            | ModAction::Unmute { username } => username,
+        }
+    }
+}
+
+// ルームタスクへのハンドル
+#[derive(Debug, Clone)]
+pub(crate) struct RoomHandle {
+    commands: mpsc::UnboundedSender<RoomCommand>,
+    pub(crate) hidden: bool, // メンバー以外のルーム一覧に出さない
+    pub(crate) password_hash: Option<String>, // 作成後は変わらないので、照合はルームのタスクに頼まずに行える
+}
+
+impl RoomHandle {
+    pub(crate) fn spawn(room: ChatRoom, history_limit: usize, typing_timeout: TimeDelta, storage: StorageHandle) -> Self {
+        let (commands, rx) = mpsc::unbounded_channel();
+        let hidden = room.access.hidden;
+        let password_hash = room.access.password_hash().map(str::to_string);
+        let actor = RoomActor {
+            room,
+            members: Router::new(),
+            history_readers: HashSet::new(),
+            history_limit,
+            typing: HashMap::new(),
+            typing_timeout: typing_timeout.to_std().unwrap_or_default(),
+            storage,
+            commands: commands.downgrade(),
+        };
+        tokio::spawn(actor.run(rx));
+
+        Self { commands, hidden, password_hash }
+    }
+
+    // 応答を待たずに送る (reply はコマンドに含めて呼び出し元へ返る)
+    pub(crate) fn send(&self, command: RoomCommand) -> bool {
+        self.commands.send(command).is_ok()
+    }
+
+    // コマンドを送り、処理が終わるまで待つ
+    pub(crate) async fn request<T>(&self, command: impl FnOnce(oneshot::Sender<T>) -> RoomCommand) -> Option<T> {
+        let (reply, rx) = oneshot::channel();
+        if !self.send(command(reply)) {
+            return None;
+        }
+        rx.await.ok()
+    }
+}
+
+// 1 つのルームの状態を所有するタスク。ルーム同士は互いに待たない
+#[derive(Debug)]
+struct RoomActor {
+    room: ChatRoom,
+    members: Router,                  // メンバーの接続
+    history_readers: HashSet<String>, // 履歴を受け取るメンバー
+    history_limit: usize,
+    typing: HashMap<String, TypingState>, // user_id -> 最後に受け付けた Typing
+    typing_timeout: Duration,
+    storage: StorageHandle,
+    commands: mpsc::WeakUnboundedSender<RoomCommand>, // タイマーから自分宛てに送る
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TypingState {
+    refreshed_at: Instant,
+    active: bool, // UserTyping を送ってから UserStoppedTyping を送るまで true
+}
+
+impl RoomActor {
+    // コマンドを届いた順に 1 つずつ処理するので、ルーム内の配送順は常に一定になる
+    async fn run(mut self, mut commands: mpsc::UnboundedReceiver<RoomCommand>) {
+        while let Some(command) = commands.recv().await {
+            self.handle_command(command);
+        }
+    }
+
+    fn handle_command(&mut self, command: RoomCommand) {
+        match command {
+            RoomCommand::Join { user_id, username, identity, connection, send_history, confirm, password, reply } => {
+                let result = self.admit(&identity, password);
+                if result.is_ok() {
+                    self.join(user_id, username, identity, connection, send_history, confirm);
+                }
+                let _ = reply.send(result);
+            }
+            RoomCommand::Leave { user_id, reply } => {
+                let _ = reply.send(self.leave(&user_id));
+            }
+            RoomCommand::Post { user_id, content, reply_to, reply } => {
+                self.post(&user_id, content, reply_to);
+                let _ = reply.send(());
+            }
+            RoomCommand::EditMessage { user_id, message_id, content, reply } => {
+                if let Err(message) = self.edit_message(&user_id, &message_id, content) {
+                    self.members.send_to_user(&user_id, ServerMessage::Error { message, code: None });
+                }
+                let _ = reply.send(());
+            }
+            RoomCommand::GetThread { user_id, message_id, reply } => {
+                let names = self.room.member_names();
+                let response = match self.room.get_thread(&message_id) {
+                    Some((root, replies)) => ServerMessage::Thread {
+                        room_name: self.room.name.clone(),
+                        root: root.map(|m| m.to_history(&names)),
+                        replies: replies.iter().map(|m| m.to_history(&names)).collect(),
+                    },
+                    None => ServerMessage::Error { message: "Message not found".to_string(), code: None },
+                };
+                self.members.send_to_user(&user_id, response);
+                let _ = reply.send(());
+            }
+            RoomCommand::React { user_id, message_id, emoji, add, reply } => {
+                if let Err(message) = self.react(&user_id, &message_id, &emoji, add) {
+                    self.members.send_to_user(&user_id, ServerMessage::Error { message, code: None });
+                }
+                let _ = reply.send(());
+            }
+            RoomCommand::Typing { user_id, reply } => {
+                self.typing(&user_id);
+                let _ = reply.send(());
+            }
+            RoomCommand::MarkRead { user_id, seq, reply } => {
+                self.mark_read(&user_id, seq);
+                let _ = reply.send(());
+            }
+            RoomCommand::UnreadCount { user_id, reply } => {
+                let count = self.room.identity(&user_id).map(|identity| self.room.unread_count(identity));
+                let _ = reply.send(count);
+            }
+            RoomCommand::TypingExpired { user_id } => {
+                let expired = self.typing.get(&user_id).is_some_and(|t| t.active && t.refreshed_at.elapsed() >= self.typing_timeout);
+                if expired {
+                    self.stop_typing(&user_id);
+                }
+            }
+            RoomCommand::ListUsers { user_id, reply } => {
+                let response = ServerMessage::UserList {
+                    room_name: self.room.name.clone(),
+                    users: self.room.get_user_list(),
+                };
+                self.members.send_to_user(&user_id, response);
+                let _ = reply.send(());
+            }
+            RoomCommand::Rename { user_id, username, reply } => {
+                // 通知はディレクトリがまとめて送る (複数ルームで重複させない)
+                self.stop_typing(&user_id);
+                if let Some(name) = self.room.users.get_mut(&user_id) {
+                    *name = username;
+                }
+                let _ = reply.send(());
+            }
+            RoomCommand::Moderate { user_id, action, target, reply } => {
+                let result = self.moderate(&user_id, action, &target);
+                if result.is_ok() {
+                    self.save();
+                }
+                let _ = reply.send(result);
+            }
+            RoomCommand::Invite { user_id, username, identity, reply } => {
+                let result = self.invite(&user_id, username, &identity);
+                if result.is_ok() {
+                    self.save();
+                }
+                let _ = reply.send(result);
+            }
+        }
+    }
+
+    // BAN されておらず、参加条件を満たしているか
+    fn admit(&mut self, identity: &str, password: Option<bool>) -> Result<(), String> {
+        if self.room.moderation.is_banned(identity, Utc::now()) {
+            return Err("You are banned from this room".to_string());
+        }
+        // オーナーとモデレーターはパスワードや招待なしで参加できる
+        if !self.room.moderation.is_moderator(identity) {
+            self.room.access.check(identity, password)?;
+        }
+        Ok(())
+    }
+
+    fn join(&mut self, user_id: String, username: String, identity: String, connection: Arc<dyn Connection>, send_history: bool, confirm: bool) {
+        if let Some(seq) = self.room.start_reading(&identity) {
+            self.save_read_marker(&identity, seq);
+        }
+        self.room.add_user(user_id.clone(), username.clone(), identity);
+        self.members.register(user_id.clone(), connection);
+        if send_history {
+            self.history_readers.insert(user_id.clone());
+        }
+
+        // 参加通知
+        let join_msg = ServerMessage::UserJoined {
+            username,
+            room_name: self.room.name.clone(),
+        };
+        self.members.send_to_room(&self.room, join_msg);
+
+        // 参加確認をユーザーに送信
+        if confirm {
+            let joined_msg = ServerMessage::JoinedRoom { room_name: self.room.name.clone() };
+            self.members.send_to_user(&user_id, joined_msg);
+        }
+
+        self.send_history(&user_id);
+    }
+
+    // 直近の履歴をまとめて送信 (ライブメッセージより先に届く)
+    fn send_history(&self, user_id: &str) {
+        if !self.history_readers.contains(user_id) {
+            return;
+        }
+
+        let names = self.room.member_names();
+        let messages: Vec<HistoryMessage> = self
+            .room
+            .get_message_history(self.history_limit)
+            .iter()
+            .map(|m| m.to_history(&names))
+            .collect();
+
+        let history_msg = ServerMessage::History {
+            room_name: self.room.name.clone(),
+            messages,
+        };
+        self.members.send_to_user(user_id, history_msg);
+    }
+
+    // 残りのメンバーに退出を通知し、退出したユーザー名を返す
+    fn leave(&mut self, user_id: &str) -> Option<String> {
+        self.stop_typing(user_id);
+        self.typing.remove(user_id);
+        self.members.unregister(user_id);
+        self.history_readers.remove(user_id);
+
+        let username = self.room.remove_user(user_id)?;
+        let leave_msg = ServerMessage::UserLeft {
+            username: username.clone(),
+            room_name: self.room.name.clone(),
+        };
+        self.members.send_to_room(&self.room, leave_msg);
+        Some(username)
+    }
+
+    fn post(&mut self, user_id: &str, content: String, reply_to: Option<String>) {
+        let Ok((username, identity)) = self.member(user_id) else {
+            return;
+        };
+        if self.room.moderation.is_muted(&identity) {
+            let error_msg = ServerMessage::Error { message: "You are muted in this room".to_string(), code: None };
+            self.members.send_to_user(user_id, error_msg);
+            return;
+        }
+
+        // 削除されたメッセージには返信できない
+        let reply_to = match reply_to {
+            Some(message_id) => match self.room.messages.find(&message_id).filter(|m| !m.deleted) {
+                Some(_) => self.room.thread_root(&message_id),
+                None => {
+                    let error_msg = ServerMessage::Error { message: "Message not found".to_string(), code: None };
+                    self.members.send_to_user(user_id, error_msg);
+                    return;
+                }
+            },
+            None => None,
+        };
+
+        self.stop_typing(user_id);
+
+        // 保存するメッセージと配信するメッセージは同じ ID・番号・時刻を持つ
+        let chat_message = self.room.add_message(ChatMessage { reply_to, ..ChatMessage::new(username, identity, content) });
+        let server_message = ServerMessage::NewMessage {
+            id: chat_message.id.clone(),
+            seq: chat_message.seq,
+            sender: chat_message.sender.clone(),
+            content: chat_message.content.clone(),
+            room_name: self.room.name.clone(),
+            timestamp: chat_message.timestamp.to_rfc3339(),
+            reply_to: chat_message.reply_to.clone(),
+        };
+        self.storage.send(StorageCommand::AppendMessage {
+            room_name: self.room.name.clone(),
+            message: chat_message,
+        });
+
+        self.members.send_to_room(&self.room, server_message);
+    }
+
+    // 投稿者本人かモデレーターだけが編集・削除できる。削除済みのメッセージは変更できない
+    fn edit_message(&mut self, user_id: &str, message_id: &str, content: Option<String>) -> Result<(), String> {
+        let (by, identity) = self.member(user_id)?;
+        let moderation = &self.room.moderation;
+        if content.is_some() && moderation.is_muted(&identity) {
+            return Err("You are muted in this room".to_string());
+        }
+        let is_moderator = moderation.is_moderator(&identity);
+
+        let message = self.room.messages.find_mut(message_id).filter(|m| !m.deleted).ok_or("Message not found")?;
+        if message.author != identity && !is_moderator {
+            return Err("Permission denied".to_string());
+        }
+
+        let room_name = self.room.name.clone();
+        let event = match content {
+            Some(content) => {
+                let edited_at = Utc::now();
+                message.edit(content, edited_at);
+                ServerMessage::MessageEdited {
+                    room_name: room_name.clone(),
+                    message_id: message.id.clone(),
+                    content: message.content.clone(),
+                    edited_at: edited_at.to_rfc3339(),
+                    by,
+                }
+            }
+            None => {
+                message.delete();
+                ServerMessage::MessageDeleted { room_name: room_name.clone(), message_id: message.id.clone(), by }
+            }
+        };
+        self.storage.send(StorageCommand::UpdateMessage { room_name, message: message.clone() });
+
+        self.members.send_to_room(&self.room, event);
+        Ok(())
+    }
+
+    // 既読位置が進んだら保存し、メンバーの少ないルームでは他のメンバーに知らせる
+    fn mark_read(&mut self, user_id: &str, seq: u64) {
+        let Ok((username, identity)) = self.member(user_id) else {
+            return;
+        };
+        let Some(seq) = self.room.mark_read(&identity, seq) else {
+            return;
+        };
+        self.save_read_marker(&identity, seq);
+
+        if self.room.users.len() <= READ_RECEIPT_MAX_MEMBERS {
+            let event = ServerMessage::ReadBy { room_name: self.room.name.clone(), username, seq };
+            self.members.send_to_room_supporting(&self.room, Capability::ReadReceipts, Some(user_id), event);
+        }
+    }
+
+    // ゲストの既読位置はセッションが終われば使われないので保存しない
+    fn save_read_marker(&self, identity: &str, seq: u64) {
+        if is_guest_identity(identity) {
+            return;
+        }
+        self.storage.send(StorageCommand::SaveReadMarker { room_name: self.room.name.clone(), identity: identity.to_string(), seq });
+    }
+
+    // 入力中になったときだけ他のメンバーに知らせ、以降の Typing は期限を延ばすだけにする。
+    // タイムアウトの 1/5 より短い間隔で届いた Typing は無視する (キー入力ごとに送られても溢れない)
+    fn typing(&mut self, user_id: &str) {
+        let Ok((username, identity)) = self.member(user_id) else {
+            return;
+        };
+        if self.room.moderation.is_muted(&identity) {
+            return;
+        }
+        let now = Instant::now();
+        let was_active = match self.typing.get(user_id) {
+            Some(state) if now.duration_since(state.refreshed_at) < self.typing_timeout / 5 => return,
+            Some(state) => state.active,
+            None => false,
+        };
+
+        self.typing.insert(user_id.to_string(), TypingState { refreshed_at: now, active: true });
+        self.schedule_typing_expiry(user_id.to_string());
+        if !was_active {
+            let event = ServerMessage::UserTyping { room_name: self.room.name.clone(), username };
+            self.members.send_to_room_supporting(&self.room, Capability::Typing, Some(user_id), event);
+        }
+    }
+
+    // 入力中だったときだけ他のメンバーに知らせる (受け付けた時刻は間隔の制限のために残す)
+    fn stop_typing(&mut self, user_id: &str) {
+        let Some(state) = self.typing.get_mut(user_id).filter(|t| t.active) else {
+            return;
+        };
+        state.active = false;
+        if let Some(username) = self.room.users.get(user_id).cloned() {
+            let event = ServerMessage::UserStoppedTyping { room_name: self.room.name.clone(), username };
+            self.members.send_to_room_supporting(&self.room, Capability::Typing, Some(user_id), event);
+        }
+    }
+
+    fn schedule_typing_expiry(&self, user_id: String) {
+        let commands = self.commands.clone();
+        let timeout = self.typing_timeout;
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            if let Some(commands) = commands.upgrade() {
+                let _ = commands.send(RoomCommand::TypingExpired { user_id });
+            }
+        });
+    }
+
+    // 変化があったときだけ集計をルームに配信する (二重のリアクションや、していないリアクションの取り消しは無視)
+    fn react(&mut self, user_id: &str, message_id: &str, emoji: &str, add: bool) -> Result<(), String> {
+        let (username, identity) = self.member(user_id)?;
+        if add && !is_valid_emoji(emoji) {
+            return Err("Invalid emoji".to_string());
+        }
+        if add && self.room.moderation.is_muted(&identity) {
+            return Err("You are muted in this room".to_string());
+        }
+
+        let names = self.room.member_names();
+        let message = self.room.messages.find_mut(message_id).filter(|m| !m.deleted).ok_or("Message not found")?;
+        if add && message.reactions.len() >= MAX_REACTIONS_PER_MESSAGE && !message.reactions.iter().any(|r| r.emoji == emoji) {
+            return Err("Too many different reactions on this message".to_string());
+        }
+        let changed = if add { message.react(emoji, &identity, &username) } else { message.unreact(emoji, &identity) };
+        if !changed {
+            return Ok(());
+        }
+
+        let room_name = self.room.name.clone();
+        let event = ServerMessage::ReactionsUpdated {
+            room_name: room_name.clone(),
+            message_id: message.id.clone(),
+            reactions: message.reaction_summary(&names),
+        };
+        self.storage.send(StorageCommand::UpdateMessage { room_name, message: message.clone() });
+
+        self.members.send_to_room_supporting(&self.room, Capability::Reactions, None, event);
+        Ok(())
+    }
+
+    // メンバーのユーザー名と identity_key
+    fn member(&self, user_id: &str) -> Result<(String, String), String> {
+        match (self.room.users.get(user_id), self.room.identity(user_id)) {
+            (Some(username), Some(identity)) => Ok((username.clone(), identity.to_string())),
+            _ => Err("Not in room".to_string()),
+        }
+    }
+
+    // 設定やモデレーションの状態が変わったら保存する
+    fn save(&self) {
+        self.storage.send(StorageCommand::SaveRoom { record: self.room.record() });
+    }
+
+    // 公開ルーム以外ではモデレーターだけが招待できる
+    // target は招待された相手の identity_key
+    fn invite(&mut self, user_id: &str, username: String, target: &str) -> Result<ServerMessage, String> {
+        let (by, identity) = self.member(user_id)?;
+        if !self.room.access.visibility().is_public() && !self.room.moderation.is_moderator(&identity) {
+            return Err("Permission denied".to_string());
+        }
+        if self.room.find_user(&username).is_some() {
+            return Err("User already in room".to_string());
+        }
+
+        self.room.access.invite(target);
+        let event = ServerMessage::UserInvited {
+            room_name: self.room.name.clone(),
+            username,
+            by,
+        };
+        self.members.send_to_room(&self.room, event.clone());
+        Ok(event)
+    }
+
+    // 権限を確認して操作を適用し、結果をルーム全体に通知する
+    fn moderate(&mut self, user_id: &str, action: ModAction, target: &str) -> Result<Option<String>, String> {
+        let (by, identity) = self.member(user_id)?;
+        let target_id = self.room.find_user(action.username());
+        let moderation = &mut self.room.moderation;
+
+        let granting = matches!(action, ModAction::GrantModerator { .. });
+        if !granting && moderation.is_owner(target) {
+            return Err("Cannot moderate the room owner".to_string());
+        }
+
+        // モデレーターの任命と、モデレーターへの操作はオーナーだけができる
+        let owner_only = granting || moderation.is_moderator(target);
+        let allowed = if owner_only { moderation.is_owner(&identity) } else { moderation.is_moderator(&identity) };
+        if !allowed {
+            return Err("Permission denied".to_string());
+        }
+
+        let room_name = self.room.name.clone();
+        let mut removed = None;
+        let event = match action {
+            ModAction::GrantModerator { username } => {
+                // 権限はメンバーの identity_key に与える (いないユーザーの名前には与えない)
+                target_id.ok_or("User not in room")?;
+                moderation.grant_moderator(target);
+                ServerMessage::ModeratorGranted { room_name, username, by }
+            }
+            ModAction::Kick { username } => {
+                removed = Some(target_id.ok_or("User not in room")?);
+                ServerMessage::UserKicked { room_name, username, by }
+            }
+            ModAction::Ban { username, duration_secs } => {
+                let expires_at = match duration_secs {
+                    Some(secs) => Some(ban_expiry(Utc::now(), secs).ok_or("Ban duration is out of range")?),
+                    None => None,
+                };
+                moderation.ban(target, expires_at);
+                removed = target_id;
+                let expires_at = expires_at.map(|t| t.to_rfc3339());
+                ServerMessage::UserBanned { room_name, username, by, expires_at }
+            }
+            ModAction::Unban { username } => {
+                if !moderation.unban(target) {
+                    return Err("User is not banned".to_string());
+                }
+                ServerMessage::UserUnbanned { room_name, username, by }
+            }
+            ModAction::Mute { username } => {
+                moderation.mute(target);
+                ServerMessage::UserMuted { room_name, username, by }
+            }
+            ModAction::Unmute { username } => {
+                if !moderation.unmute(target) {
+                    return Err("User is not muted".to_string());
+                }
+                ServerMessage::UserUnmuted { room_name, username, by }
+            }
+        };
+
+        // 対象者にも届くよう、外す前に通知する
+        self.members.send_to_room(&self.room, event);
+        if let Some(target_id) = &removed {
+            self.stop_typing(target_id);
+            self.typing.remove(target_id);
+            self.members.unregister(target_id);
+            self.history_readers.remove(target_id);
+            self.room.remove_user(target_id);
+        }
+        Ok(removed)
+    }
+}
+
+// 期限が表せないほど長い BAN は None (受け付けない)
+fn ban_expiry(now: DateTime<Utc>, duration_secs: u64) -> Option<DateTime<Utc>> {
+    let secs = i64::try_from(duration_secs).ok()?;
+    now.checked_add_signed(TimeDelta::try_seconds(secs)?)
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use log::warn;
+
+use chat_protocol::{Capability, ServerMessage};
+use crate::connection::Connection;
+use crate::room::ChatRoom;
+
+#[derive(Debug, Default)]
+pub struct Router {
+    connections: HashMap<String, Arc<dyn Connection>>, // user_id -> 接続ごとの送信口
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, user_id: String, connection: Arc<dyn Connection>) {
+        self.connections.insert(user_id, connection);
+    }
+
+    pub fn unregister(&mut self, user_id: &str) -> Option<Arc<dyn Connection>> {
+        self.connections.remove(user_id)
+    }
+
+    // 指定ユーザーの接続へ配送し、配送できたかを返す
+    pub fn send_to_user(&self, user_id: &str, message: ServerMessage) -> bool {
+        match self.connections.get(user_id) {
+            Some(connection) => Self::deliver(user_id, connection.as_ref(), message),
+            None => false,
+        }
+    }
+
+    // ルームの全メンバーへ配送し、配送できた接続数を返す
+    pub fn send_to_room(&self, room: &ChatRoom, message: ServerMessage) -> usize {
+        room.users
+            .keys()
+            .filter_map(|user_id| self.connections.get(user_id).map(|connection| (user_id, connection)))
+            .filter(|(user_id, connection)| Self::deliver(user_id, connection.as_ref(), message.clone()))
+            .count()
+    }
+
+    // 指定したユーザー以外のメンバーへ配送する
+    pub fn send_to_room_except(&self, room: &ChatRoom, except_user_id: &str, message: ServerMessage) -> usize {
+        room.users
+            .keys()
+            .filter(|user_id| user_id.as_str() != except_user_id)
+            .filter_map(|user_id| self.connections.get(user_id).map(|connection| (user_id, connection)))
+            .filter(|(user_id, connection)| Self::deliver(user_id, connection.as_ref(), message.clone()))
+            .count()
+    }
+
+    // capability を合意したメンバーだけへ配送する (except_user_id を指定すればそのユーザーを除く)
+    pub fn send_to_room_supporting(&self, room: &ChatRoom, capability: Capability, except_user_id: Option<&str>, message: ServerMessage) -> usize {
+        room.users
+            .keys()
+            .filter(|user_id| Some(user_id.as_str()) != except_user_id)
+            .filter_map(|user_id| self.connections.get(user_id).map(|connection| (user_id, connection)))
+            .filter(|(_, connection)| connection.supports(capability))
+            .filter(|(user_id, connection)| Self::deliver(user_id, connection.as_ref(), message.clone()))
+            .count()
+    }
+
+    fn deliver(user_id: &str, connection: &dyn Connection, message: ServerMessage) -> bool {
+        if connection.send(message) {
+            true
+        } else {
+            // 受信側が閉じている(切断処理中)
+            warn!("Connection for user {} is closed", user_id);
+            false
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+use chrono::Utc;
+use log::error;
+
+use crate::access::RoomAccess;
+use crate::config::ServerConfig;
+use chat_protocol::{Capability, ClientMessage, ErrorCode};
+use crate::auth::{hash_password, validate_password, verify_password, AuthError};
+use crate::connection::Connection;
+use crate::session::Session;
+use crate::directory::{Command, Directory};
+use crate::storage::{Account, MemoryStorage, Storage};
+use crate::storage_actor::{StorageCommand, StorageHandle};
+use crate::username::validate_username;
+
+// このサーバーが提供する機能
+pub const CAPABILITIES: &[Capability] = &[Capability::History, Capability::Typing, Capability::Reactions, Capability::ReadReceipts, Capability::DirectMessages];
+
+// ディレクトリタスクへのハンドル。clone してトランスポートやセッションに配る
+#[derive(Debug, Clone)]
+pub struct ChatServer {
+    commands: mpsc::UnboundedSender<Command>,
+    storage: StorageHandle, // アカウントの照会と作成用 (ディレクトリのタスクを止めないようここで行う)
+}
+
+impl ChatServer {
+    pub fn new() -> Self {
+        Self::with_config(ServerConfig::default())
+    }
+
+    // 保存先はメモリ (再起動すると消える)
+    pub fn with_config(config: ServerConfig) -> Self {
+        let storage = MemoryStorage::new(config.retention.capacity);
+        Self::with_storage(config, Box::new(storage))
+    }
+
+    // tokio ランタイム上で呼ぶこと (ディレクトリとルーム、ストレージのタスクを起動する)
+    // 設定に合わせた保存先は storage::open で作れる
+    pub fn with_storage(config: ServerConfig, storage: Box<dyn Storage>) -> Self {
+        let (commands, rx) = mpsc::unbounded_channel();
+        let storage = StorageHandle::spawn(storage);
+        tokio::spawn(Directory::new(config, storage.clone(), commands.downgrade()).run(rx));
+
+        Self { commands, storage }
+    }
+
+    // 新しい接続のセッションを作る (トランスポートから呼ばれる)
+    pub fn connect(&self, connection: Arc<dyn Connection>) -> Session {
+        Session::new(self.clone(), connection)
+    }
+
+    pub async fn room_list(&self) -> Vec<String> {
+        self.request(|reply| Command::RoomList { reply }).await.unwrap_or_default()
+    }
+
+    // それまでの変更が保存先に書き込まれるまで待つ
+    pub async fn flush(&self) {
+        self.request(|reply| Command::Flush { reply }).await;
+    }
+
+    // password がなければゲストとしてログインする
+    pub(crate) async fn handle_login(&self, username: String, password: Option<String>, capabilities: Vec<Capability>, connection: Arc<dyn Connection>) -> Result<String, AuthError> {
+        let (username, registered, account_exists) = match password {
+            Some(password) => (self.authenticate(&username, password).await?, true, true),
+            None => {
+                let account_exists = self.find_account(&username).await.is_some();
+                (username, false, account_exists)
+            }
+        };
+        self.request(|reply| Command::Login { username, registered, account_exists, capabilities, connection, reply }).await
+            .unwrap_or_else(|| Err(server_unavailable()))
+    }
+
+    // アカウントを作ってそのままログインする
+    pub(crate) async fn handle_register(&self, username: String, password: String, capabilities: Vec<Capability>, connection: Arc<dyn Connection>) -> Result<String, AuthError> {
+        validate_username(&username).map_err(|message| AuthError::new(ErrorCode::InvalidUsername, message))?;
+        validate_password(&password)?;
+
+        let password_hash = tokio::task::spawn_blocking(move || hash_password(&password)).await
+            .map_err(|e| e.to_string())
+            .and_then(|hash| hash)
+            .map_err(|e| {
+                error!("{}", e);
+                server_unavailable()
+            })?;
+
+        // 名前を予約したら必ず Register で解放する (接続が閉じて呼び出し側が待つのをやめても続ける)
+        let server = self.clone();
+        tokio::spawn(async move {
+            let name = username.clone();
+            server.request(|reply| Command::ReserveName { username: name, reply }).await
+                .unwrap_or_else(|| Err(server_unavailable()))?;
+
+            let account = Account { username: username.clone(), password_hash };
+            let created = server.storage.request(|reply| StorageCommand::CreateAccount { account, at: Utc::now(), reply }).await;
+            server.request(|reply| Command::Register { username, created, capabilities, connection, reply }).await
+                .unwrap_or_else(|| Err(server_unavailable()))
+        }).await.unwrap_or_else(|e| {
+            error!("{}", e);
+            Err(server_unavailable())
+        })
+    }
+
+    // パスワードを確かめ、登録時の表記のユーザー名を返す
+    // (ハッシュの照合は重いので、ディレクトリのタスクを止めないようここで行う)
+    async fn authenticate(&self, username: &str, password: String) -> Result<String, AuthError> {
+        if let Some(account) = self.find_account(username).await {
+            let Account { username, password_hash } = account;
+            let verified = tokio::task::spawn_blocking(move || verify_password(&password, &password_hash)).await.unwrap_or(false);
+            if verified {
+                return Ok(username);
+            }
+        }
+        Err(AuthError::new(ErrorCode::InvalidCredentials, "Invalid username or password"))
+    }
+
+    async fn find_account(&self, username: &str) -> Option<Account> {
+        let username = username.to_string();
+        self.storage.request(|reply| StorageCommand::FindAccount { username, reply }).await.flatten()
+    }
+
+    pub(crate) async fn handle_resume(&self, token: String, last_seen_seq: u64, capabilities: Vec<Capability>, connection: Arc<dyn Connection>) -> Result<String, AuthError> {
+        self.request(|reply| Command::Resume { token, last_seen_seq, capabilities, connection, reply }).await
+            .unwrap_or_else(|| Err(server_unavailable()))
+    }
+
+    // ルームのパスワードのハッシュと照合、アカウントの照会も、ディレクトリやルームのタスクを止めないようここで行う
+    pub(crate) async fn handle_message(&self, user_id: String, message: ClientMessage) {
+        match message {
+            ClientMessage::CreateRoom { room_name, visibility, password, hidden } => {
+                let access = tokio::task::spawn_blocking(move || RoomAccess::new(visibility, password.as_deref(), hidden)).await
+                    .unwrap_or_else(|e| {
+                        error!("{}", e);
+                        Err("Failed to create room".to_string())
+                    });
+                self.request(|reply| Command::CreateRoom { user_id, room_name, access, reply }).await;
+            }
+            ClientMessage::JoinRoom { room_name, password } => {
+                let password = match password {
+                    Some(password) => Some(self.verify_room_password(&room_name, password).await),
+                    None => None,
+                };
+                self.request(|reply| Command::JoinRoom { user_id, room_name, password, reply }).await;
+            }
+            ClientMessage::ChangeNick { username } => {
+                let account_exists = self.find_account(&username).await.is_some();
+                self.request(|reply| Command::ChangeNick { user_id, username, account_exists, reply }).await;
+            }
+            message => {
+                self.request(|reply| Command::Message { user_id, message, reply }).await;
+            }
+        }
+    }
+
+    // パスワード付きのルームでなければ false
+    async fn verify_room_password(&self, room_name: &str, password: String) -> bool {
+        let room_name = room_name.to_string();
+        let Some(hash) = self.request(|reply| Command::RoomPasswordHash { room_name, reply }).await.flatten() else {
+            return false;
+        };
+        tokio::task::spawn_blocking(move || verify_password(&password, &hash)).await.unwrap_or(false)
+    }
+
+    pub(crate) async fn handle_user_disconnect(&self, user_id: &str, connection: Arc<dyn Connection>) {
+        let user_id = user_id.to_string();
+        self.request(|reply| Command::Disconnect { user_id, connection, reply }).await;
+    }
+
+    // コマンドを送り、処理が終わるまで待つ
+    async fn request<T>(&self, command: impl FnOnce(oneshot::Sender<T>) -> Command) -> Option<T> {
+        let (reply, rx) = oneshot::channel();
+        if self.commands.send(command(reply)).is_err() {
+            error!("Chat server task has stopped");
+            return None;
+        }
+        rx.await.ok()
+    }
+}
+
+fn server_unavailable() -> AuthError {
+    AuthError::new(ErrorCode::ServerUnavailable, "Server unavailable")
+}
+
+impl Default for ChatServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use chat_protocol::handshake::negotiate;
+use chat_protocol::{Capability, ClientMessage, ErrorCode, ServerMessage, PROTOCOL_VERSION};
+use crate::auth::AuthError;
+use crate::connection::Connection;
+use crate::server::{ChatServer, CAPABILITIES};
+
+// 受信処理のあとに接続を続けるかどうか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Close,
+}
+
+// 1 本の接続の状態 (ハンドシェイク / ログイン)。トランスポートは受信した順に渡す
+#[derive(Debug)]
+pub struct Session {
+    server: ChatServer,
+    connection: Arc<dyn Connection>,
+    user_id: Option<String>,
+    capabilities: Option<Vec<Capability>>, // Hello を送ってこない旧クライアントは None
+}
+
+impl Session {
+    pub(crate) fn new(server: ChatServer, connection: Arc<dyn Connection>) -> Self {
+        Self {
+            server,
+            connection,
+            user_id: None,
+            capabilities: None,
+        }
+    }
+
+    pub fn user_id(&self) -> Option<&str> {
+        self.user_id.as_deref()
+    }
+
+    // 1 行 / 1 フレーム分の JSON を処理する。解釈できない入力は無視する
+    pub async fn handle_text(&mut self, text: &str) -> Flow {
+        match serde_json::from_str::<ClientMessage>(text.trim()) {
+            Ok(message) => self.handle(message).await,
+            Err(_) => Flow::Continue,
+        }
+    }
+
+    pub async fn handle(&mut self, message: ClientMessage) -> Flow {
+        match message {
+            ClientMessage::Hello { protocol_version, capabilities } => {
+                return self.handle_hello(protocol_version, &capabilities);
+            }
+            ClientMessage::Login { .. } | ClientMessage::Register { .. } | ClientMessage::Resume { .. } => {
+                self.handle_login(message).await;
+            }
+            _ => {
+                if let Some(uid) = &self.user_id {
+                    self.server.handle_message(uid.clone(), message).await;
+                }
+            }
+        }
+        Flow::Continue
+    }
+
+    // 接続終了時の後始末
+    pub async fn close(self) {
+        if let Some(uid) = &self.user_id {
+            self.server.handle_user_disconnect(uid, Arc::clone(&self.connection)).await;
+        }
+    }
+
+    // ログイン・登録・再開。名前やパスワード、トークンが通らなければコード付きのエラーを返し、ログイン前のまま次の要求を待つ
+    async fn handle_login(&mut self, message: ClientMessage) {
+        if self.user_id.is_some() {
+            self.connection.send(AuthError::new(ErrorCode::AlreadyLoggedIn, "Already logged in").into());
+            return;
+        }
+        // Hello を送ってこない旧クライアントには全機能を有効にする
+        let negotiated = self.capabilities.clone().unwrap_or_else(|| CAPABILITIES.to_vec());
+        let connection = Arc::clone(&self.connection);
+
+        let result = match message {
+            ClientMessage::Login { username, password } => self.server.handle_login(username, password, negotiated, connection).await,
+            ClientMessage::Register { username, password } => self.server.handle_register(username, password, negotiated, connection).await,
+            ClientMessage::Resume { token, last_seen_seq } => self.server.handle_resume(token, last_seen_seq, negotiated, connection).await,
+            _ => return,
+        };
+        match result {
+            Ok(uid) => self.user_id = Some(uid),
+            Err(error) => {
+                self.connection.send(error.into());
+            }
+        }
+    }
+
+    fn handle_hello(&mut self, protocol_version: u32, requested: &[Capability]) -> Flow {
+        if self.capabilities.is_some() || self.user_id.is_some() {
+            self.send_error("Handshake already completed");
+            return Flow::Continue;
+        }
+
+        match negotiate(protocol_version, requested, CAPABILITIES) {
+            Ok(negotiated) => {
+                self.capabilities = Some(negotiated);
+                self.connection.send(ServerMessage::Hello {
+                    protocol_version: PROTOCOL_VERSION,
+                    capabilities: CAPABILITIES.to_vec(),
+                });
+                Flow::Continue
+            }
+            Err(message) => {
+                // 非対応のクライアントはエラーを返して切断する
+                self.connection.send(ServerMessage::Error { message, code: None });
+                Flow::Close
+            }
+        }
+    }
+
+    fn send_error(&self, message: &str) {
+        self.connection.send(ServerMessage::Error { message: message.to_string(), code: None });
+    }
+}
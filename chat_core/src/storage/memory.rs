@@ -0,0 +1,104 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Utc};
+
+use crate::room::ChatMessage;
+use crate::username::username_key;
+use super::{Account, RoomRecord, Storage, StorageResult, StoredRoom};
+
+#[derive(Debug, Default)]
+struct MemoryData {
+    rooms: Vec<RoomRecord>, // 作成順
+    messages: HashMap<String, VecDeque<ChatMessage>>,
+    read_markers: HashMap<String, HashMap<String, u64>>, // ルーム名 -> identity_key -> 既読位置
+    users: HashMap<String, DateTime<Utc>>, // 最後に使われた時刻
+    accounts: HashMap<String, Account>,
+}
+
+// プロセス内だけの保存先 (既定)。clone したものは同じ内容を共有するので、テストで再起動を再現できる
+#[derive(Debug, Clone)]
+pub struct MemoryStorage {
+    data: Arc<Mutex<MemoryData>>,
+    capacity: usize, // ルームごとに残すメッセージ数
+}
+
+impl MemoryStorage {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            data: Arc::new(Mutex::new(MemoryData::default())),
+            capacity,
+        }
+    }
+
+    pub fn last_seen(&self, username: &str) -> Option<DateTime<Utc>> {
+        self.data.lock().unwrap().users.get(&username_key(username)).copied()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load_rooms(&mut self, message_limit: usize) -> StorageResult<Vec<StoredRoom>> {
+        let data = self.data.lock().unwrap();
+        let rooms = data.rooms.iter().map(|record| {
+            let messages = data.messages.get(&record.name).map(|m| {
+                let start = m.len().saturating_sub(message_limit);
+                m.range(start..).cloned().collect()
+            });
+            let mut read_markers: Vec<(String, u64)> = data.read_markers.get(&record.name).map(|m| m.iter().map(|(name, seq)| (name.clone(), *seq)).collect()).unwrap_or_default();
+            read_markers.sort();
+            StoredRoom { record: record.clone(), messages: messages.unwrap_or_default(), read_markers }
+        });
+        Ok(rooms.collect())
+    }
+
+    fn save_room(&mut self, record: &RoomRecord) -> StorageResult<()> {
+        let mut data = self.data.lock().unwrap();
+        match data.rooms.iter_mut().find(|r| r.name == record.name) {
+            Some(existing) => *existing = record.clone(),
+            None => data.rooms.push(record.clone()),
+        }
+        Ok(())
+    }
+
+    fn append_message(&mut self, room_name: &str, message: &ChatMessage) -> StorageResult<()> {
+        let mut data = self.data.lock().unwrap();
+        let messages = data.messages.entry(room_name.to_string()).or_default();
+        messages.push_back(message.clone());
+        while messages.len() > self.capacity {
+            messages.pop_front();
+        }
+        Ok(())
+    }
+
+    fn update_message(&mut self, room_name: &str, message: &ChatMessage) -> StorageResult<()> {
+        let mut data = self.data.lock().unwrap();
+        if let Some(stored) = data.messages.get_mut(room_name).and_then(|messages| messages.iter_mut().find(|m| m.seq == message.seq)) {
+            *stored = message.clone();
+        }
+        Ok(())
+    }
+
+    fn save_read_marker(&mut self, room_name: &str, identity: &str, seq: u64) -> StorageResult<()> {
+        let mut data = self.data.lock().unwrap();
+        data.read_markers.entry(room_name.to_string()).or_default().insert(identity.to_string(), seq);
+        Ok(())
+    }
+
+    fn touch_user(&mut self, username: &str, at: DateTime<Utc>) -> StorageResult<()> {
+        self.data.lock().unwrap().users.insert(username_key(username), at);
+        Ok(())
+    }
+
+    fn find_account(&mut self, username: &str) -> StorageResult<Option<Account>> {
+        Ok(self.data.lock().unwrap().accounts.get(&username_key(username)).cloned())
+    }
+
+    fn create_account(&mut self, account: &Account, _at: DateTime<Utc>) -> StorageResult<bool> {
+        let mut data = self.data.lock().unwrap();
+        let key = username_key(&account.username);
+        if data.accounts.contains_key(&key) {
+            return Ok(false);
+        }
+        data.accounts.insert(key, account.clone());
+        Ok(true)
+    }
+}
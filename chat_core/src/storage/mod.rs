@@ -0,0 +1,76 @@
+pub mod memory;
+pub mod sqlite;
+
+use chrono::{DateTime, Utc};
+
+use chat_protocol::RoomVisibility;
+use crate::config::ServerConfig;
+use crate::room::ChatMessage;
+
+pub use memory::MemoryStorage;
+pub use sqlite::SqliteStorage;
+
+pub type StorageResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+// ルームの設定とモデレーションの状態 (ユーザーはすべて identity_key で表す)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RoomRecord {
+    pub name: String,
+    pub owner: Option<String>,
+    pub moderators: Vec<String>,
+    pub bans: Vec<(String, Option<DateTime<Utc>>)>, // 期限 (None は無期限)
+    pub muted: Vec<String>,
+    pub visibility: RoomVisibility,
+    pub password_hash: Option<String>,
+    pub hidden: bool,
+    pub invited: Vec<String>,
+}
+
+// 起動時に読み込むルーム。messages は通し番号の古い順
+#[derive(Debug, Clone)]
+pub struct StoredRoom {
+    pub record: RoomRecord,
+    pub messages: Vec<ChatMessage>,
+    pub read_markers: Vec<(String, u64)>, // アカウントの identity_key と既読にした最後の通し番号
+}
+
+// 登録済みのアカウント (パスワードはハッシュだけを保存する)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account {
+    pub username: String, // 登録時の表記
+    pub password_hash: String,
+}
+
+// ルーム・メッセージ・ユーザーの保存先。呼び出しはストレージ用のスレッドから行われる
+pub trait Storage: Send + std::fmt::Debug {
+    // すべてのルームを、それぞれ直近 message_limit 件のメッセージ付きで返す
+    fn load_rooms(&mut self, message_limit: usize) -> StorageResult<Vec<StoredRoom>>;
+
+    // 同じ名前のルームがあれば置き換える
+    fn save_room(&mut self, record: &RoomRecord) -> StorageResult<()>;
+
+    fn append_message(&mut self, room_name: &str, message: &ChatMessage) -> StorageResult<()>;
+
+    // 編集・削除されたメッセージを通し番号で探して置き換える
+    fn update_message(&mut self, room_name: &str, message: &ChatMessage) -> StorageResult<()>;
+
+    // ユーザーの既読位置を置き換える (identity はアカウントの identity_key)
+    fn save_read_marker(&mut self, room_name: &str, identity: &str, seq: u64) -> StorageResult<()>;
+
+    // ログインや名前の変更で使われたユーザー名を記録する
+    fn touch_user(&mut self, username: &str, at: DateTime<Utc>) -> StorageResult<()>;
+
+    // 大文字小文字を区別せずに探す
+    fn find_account(&mut self, username: &str) -> StorageResult<Option<Account>>;
+
+    // 同じ名前のアカウントがすでにあれば作らずに false を返す
+    fn create_account(&mut self, account: &Account, at: DateTime<Utc>) -> StorageResult<bool>;
+}
+
+// データベースのパスが設定されていれば SQLite、なければメモリに保存する
+pub fn open(config: &ServerConfig) -> StorageResult<Box<dyn Storage>> {
+    match &config.database {
+        Some(path) => Ok(Box::new(SqliteStorage::open(path)?)),
+        None => Ok(Box::new(MemoryStorage::new(config.retention.capacity))),
+    }
+}
@@ -0,0 +1,366 @@
+use std::path::Path;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+
+use chat_protocol::RoomVisibility;
+use crate::room::ChatMessage;
+use crate::username::username_key;
+use super::{Account, RoomRecord, Storage, StorageResult, StoredRoom};
+
+// スキーマの変更を適用する順に並べる。適用済みの数は PRAGMA user_version に記録する
+// (一度リリースしたものは書き換えず、末尾に追加すること)
+const MIGRATIONS: &[&str] = &[
+    // 1: ルームとその設定、メッセージ、ユーザー
+    "CREATE TABLE rooms (
+        name TEXT PRIMARY KEY,
+        owner TEXT,
+        visibility TEXT NOT NULL,
+        password_hash TEXT,
+        hidden INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE room_roles (
+        room_name TEXT NOT NULL REFERENCES rooms (name),
+        username TEXT NOT NULL,
+        role TEXT NOT NULL, -- moderator / muted / invited
+        PRIMARY KEY (room_name, username, role)
+    );
+    CREATE TABLE room_bans (
+        room_name TEXT NOT NULL REFERENCES rooms (name),
+        username TEXT NOT NULL,
+        expires_at TEXT,
+        PRIMARY KEY (room_name, username)
+    );
+    CREATE TABLE messages (
+        room_name TEXT NOT NULL REFERENCES rooms (name),
+        seq INTEGER NOT NULL,
+        sender TEXT NOT NULL,
+        author TEXT NOT NULL, -- 投稿者の identity_key (表示名は変えられるので編集・削除の権限はこちらで確かめる)
+        content TEXT NOT NULL,
+        timestamp TEXT NOT NULL,
+        PRIMARY KEY (room_name, seq)
+    );
+    -- 名前は username_key で引く (COLLATE NOCASE は ASCII しか畳まない)
+    CREATE TABLE users (
+        username_key TEXT PRIMARY KEY,
+        username TEXT NOT NULL,
+        first_seen_at TEXT NOT NULL,
+        last_seen_at TEXT NOT NULL
+    );",
+    // 2: パスワードでログインするアカウント
+    "CREATE TABLE accounts (
+        username_key TEXT PRIMARY KEY,
+        username TEXT NOT NULL,
+        password_hash TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );",
+    // 3: メッセージ ID (既存のメッセージにはランダムな ID を振る)
+    "ALTER TABLE messages ADD COLUMN id TEXT NOT NULL DEFAULT '';
+    UPDATE messages SET id = lower(hex(randomblob(16))) WHERE id = '';",
+    // 4: メッセージの編集と削除
+    "ALTER TABLE messages ADD COLUMN edited_at TEXT;
+    ALTER TABLE messages ADD COLUMN deleted INTEGER NOT NULL DEFAULT 0;",
+    // 5: スレッドへの返信
+    "ALTER TABLE messages ADD COLUMN reply_to TEXT;",
+    // 6: メッセージへのリアクション (rowid の順がリアクションした順。username はリアクションしたときの表示名)
+    "CREATE TABLE message_reactions (
+        room_name TEXT NOT NULL,
+        seq INTEGER NOT NULL,
+        emoji TEXT NOT NULL,
+        identity TEXT NOT NULL,
+        username TEXT NOT NULL,
+        PRIMARY KEY (room_name, seq, emoji, identity),
+        FOREIGN KEY (room_name, seq) REFERENCES messages (room_name, seq)
+    );",
+    // 7: アカウントごとの既読位置
+    "CREATE TABLE read_markers (
+        room_name TEXT NOT NULL REFERENCES rooms (name),
+        identity TEXT NOT NULL,
+        seq INTEGER NOT NULL,
+        PRIMARY KEY (room_name, identity)
+    );",
+];
+
+// SQLite ファイルへの保存先。開いたときに未適用のマイグレーションを流す
+#[derive(Debug)]
+pub struct SqliteStorage {
+    conn: Connection,
+}
+
+impl SqliteStorage {
+    pub fn open(path: impl AsRef<Path>) -> StorageResult<Self> {
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::with_connection(conn)
+    }
+
+    pub fn open_in_memory() -> StorageResult<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut conn: Connection) -> StorageResult<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut conn)?;
+        Ok(Self { conn })
+    }
+
+    // 適用済みのマイグレーションの数
+    pub fn schema_version(&self) -> StorageResult<usize> {
+        Ok(schema_version(&self.conn)?)
+    }
+
+    pub fn latest_schema_version() -> usize {
+        MIGRATIONS.len()
+    }
+}
+
+fn schema_version(conn: &Connection) -> rusqlite::Result<usize> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+fn migrate(conn: &mut Connection) -> StorageResult<()> {
+    let version = schema_version(conn)?;
+    if version > MIGRATIONS.len() {
+        return Err(format!("Database schema version {} is newer than this server supports ({})", version, MIGRATIONS.len()).into());
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+impl Storage for SqliteStorage {
+    fn load_rooms(&mut self, message_limit: usize) -> StorageResult<Vec<StoredRoom>> {
+        let mut records = Vec::new();
+        let mut stmt = self.conn.prepare("SELECT name, owner, visibility, password_hash, hidden FROM rooms ORDER BY rowid")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let visibility: String = row.get(2)?;
+            records.push(RoomRecord {
+                name: row.get(0)?,
+                owner: row.get(1)?,
+                visibility: parse_visibility(&visibility)?,
+                password_hash: row.get(3)?,
+                hidden: row.get(4)?,
+                ..Default::default()
+            });
+        }
+
+        let mut rooms = Vec::new();
+        for mut record in records {
+            self.load_roles(&mut record)?;
+            let messages = self.load_messages(&record.name, message_limit)?;
+            let read_markers = self.load_read_markers(&record.name)?;
+            rooms.push(StoredRoom { record, messages, read_markers });
+        }
+        Ok(rooms)
+    }
+
+    fn save_room(&mut self, record: &RoomRecord) -> StorageResult<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO rooms (name, owner, visibility, password_hash, hidden) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (name) DO UPDATE SET owner = ?2, visibility = ?3, password_hash = ?4, hidden = ?5",
+            params![record.name, record.owner, visibility_name(record.visibility), record.password_hash, record.hidden],
+        )?;
+
+        // 役割と BAN は丸ごと書き直す
+        tx.execute("DELETE FROM room_roles WHERE room_name = ?1", [&record.name])?;
+        tx.execute("DELETE FROM room_bans WHERE room_name = ?1", [&record.name])?;
+        insert_roles(&tx, &record.name, "moderator", &record.moderators)?;
+        insert_roles(&tx, &record.name, "muted", &record.muted)?;
+        insert_roles(&tx, &record.name, "invited", &record.invited)?;
+        for (username, expires_at) in &record.bans {
+            tx.execute(
+                "INSERT INTO room_bans (room_name, username, expires_at) VALUES (?1, ?2, ?3)",
+                params![record.name, username, expires_at.map(|t| t.to_rfc3339())],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn append_message(&mut self, room_name: &str, message: &ChatMessage) -> StorageResult<()> {
+        self.conn.execute(
+            "INSERT INTO messages (room_name, seq, id, sender, author, content, timestamp, reply_to) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![room_name, message.seq as i64, message.id, message.sender, message.author, message.content, message.timestamp.to_rfc3339(), message.reply_to],
+        )?;
+        Ok(())
+    }
+
+    fn update_message(&mut self, room_name: &str, message: &ChatMessage) -> StorageResult<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "UPDATE messages SET content = ?3, edited_at = ?4, deleted = ?5 WHERE room_name = ?1 AND seq = ?2",
+            params![room_name, message.seq as i64, message.content, message.edited_at.map(|t| t.to_rfc3339()), message.deleted],
+        )?;
+
+        // リアクションは丸ごと書き直す
+        tx.execute("DELETE FROM message_reactions WHERE room_name = ?1 AND seq = ?2", params![room_name, message.seq as i64])?;
+        for reaction in &message.reactions {
+            for reactor in &reaction.users {
+                tx.execute(
+                    "INSERT INTO message_reactions (room_name, seq, emoji, identity, username) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![room_name, message.seq as i64, reaction.emoji, reactor.identity, reactor.username],
+                )?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn save_read_marker(&mut self, room_name: &str, identity: &str, seq: u64) -> StorageResult<()> {
+        self.conn.execute(
+            "INSERT INTO read_markers (room_name, identity, seq) VALUES (?1, ?2, ?3)
+             ON CONFLICT (room_name, identity) DO UPDATE SET seq = ?3",
+            params![room_name, identity, seq as i64],
+        )?;
+        Ok(())
+    }
+
+    fn touch_user(&mut self, username: &str, at: DateTime<Utc>) -> StorageResult<()> {
+        self.conn.execute(
+            "INSERT INTO users (username_key, username, first_seen_at, last_seen_at) VALUES (?1, ?2, ?3, ?3)
+             ON CONFLICT (username_key) DO UPDATE SET username = ?2, last_seen_at = ?3",
+            params![username_key(username), username, at.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    fn find_account(&mut self, username: &str) -> StorageResult<Option<Account>> {
+        let account = self.conn.query_row(
+            "SELECT username, password_hash FROM accounts WHERE username_key = ?1",
+            [username_key(username)],
+            |row| Ok(Account { username: row.get(0)?, password_hash: row.get(1)? }),
+        ).optional()?;
+        Ok(account)
+    }
+
+    fn create_account(&mut self, account: &Account, at: DateTime<Utc>) -> StorageResult<bool> {
+        let inserted = self.conn.execute(
+            "INSERT INTO accounts (username_key, username, password_hash, created_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (username_key) DO NOTHING",
+            params![username_key(&account.username), account.username, account.password_hash, at.to_rfc3339()],
+        )?;
+        Ok(inserted == 1)
+    }
+}
+
+impl SqliteStorage {
+    fn load_roles(&self, record: &mut RoomRecord) -> StorageResult<()> {
+        let mut stmt = self.conn.prepare("SELECT username, role FROM room_roles WHERE room_name = ?1 ORDER BY username")?;
+        let mut rows = stmt.query([&record.name])?;
+        while let Some(row) = rows.next()? {
+            let username: String = row.get(0)?;
+            let role: String = row.get(1)?;
+            match role.as_str() {
+                "moderator" => record.moderators.push(username),
+                "muted" => record.muted.push(username),
+                "invited" => record.invited.push(username),
+                other => return Err(format!("Unknown room role: {}", other).into()),
+            }
+        }
+
+        let mut stmt = self.conn.prepare("SELECT username, expires_at FROM room_bans WHERE room_name = ?1 ORDER BY username")?;
+        let mut rows = stmt.query([&record.name])?;
+        while let Some(row) = rows.next()? {
+            let expires_at: Option<String> = row.get(1)?;
+            record.bans.push((row.get(0)?, expires_at.as_deref().map(parse_timestamp).transpose()?));
+        }
+        Ok(())
+    }
+
+    // 直近 limit 件を古い順に返す
+    fn load_messages(&self, room_name: &str, limit: usize) -> StorageResult<Vec<ChatMessage>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT seq, id, sender, content, timestamp, edited_at, deleted, reply_to, author FROM messages WHERE room_name = ?1 ORDER BY seq DESC LIMIT ?2",
+        )?;
+        let mut rows = stmt.query(params![room_name, limit as i64])?;
+        let mut messages = Vec::new();
+        while let Some(row) = rows.next()? {
+            let seq: i64 = row.get(0)?;
+            let timestamp: String = row.get(4)?;
+            let edited_at: Option<String> = row.get(5)?;
+            let message = ChatMessage {
+                id: row.get(1)?,
+                seq: seq as u64,
+                sender: row.get(2)?,
+                author: row.get(8)?,
+                content: row.get(3)?,
+                timestamp: parse_timestamp(&timestamp)?,
+                edited_at: edited_at.as_deref().map(parse_timestamp).transpose()?,
+                deleted: row.get(6)?,
+                reply_to: row.get(7)?,
+                reactions: Vec::new(),
+            };
+            messages.push(message);
+        }
+        messages.reverse();
+        self.load_reactions(room_name, &mut messages)?;
+        Ok(messages)
+    }
+
+    fn load_read_markers(&self, room_name: &str) -> StorageResult<Vec<(String, u64)>> {
+        let mut stmt = self.conn.prepare("SELECT identity, seq FROM read_markers WHERE room_name = ?1 ORDER BY identity")?;
+        let mut rows = stmt.query([room_name])?;
+        let mut markers = Vec::new();
+        while let Some(row) = rows.next()? {
+            let seq: i64 = row.get(1)?;
+            markers.push((row.get(0)?, seq as u64));
+        }
+        Ok(markers)
+    }
+
+    fn load_reactions(&self, room_name: &str, messages: &mut [ChatMessage]) -> StorageResult<()> {
+        let Some(first_seq) = messages.first().map(|m| m.seq) else {
+            return Ok(());
+        };
+        let mut stmt = self.conn.prepare(
+            "SELECT seq, emoji, identity, username FROM message_reactions WHERE room_name = ?1 AND seq >= ?2 ORDER BY rowid",
+        )?;
+        let mut rows = stmt.query(params![room_name, first_seq as i64])?;
+        while let Some(row) = rows.next()? {
+            let seq: i64 = row.get(0)?;
+            let emoji: String = row.get(1)?;
+            let identity: String = row.get(2)?;
+            let username: String = row.get(3)?;
+            if let Ok(index) = messages.binary_search_by_key(&(seq as u64), |m| m.seq) {
+                messages[index].react(&emoji, &identity, &username);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn insert_roles(tx: &Transaction, room_name: &str, role: &str, usernames: &[String]) -> rusqlite::Result<()> {
+    for username in usernames {
+        tx.execute("INSERT INTO room_roles (room_name, username, role) VALUES (?1, ?2, ?3)", params![room_name, username, role])?;
+    }
+    Ok(())
+}
+
+fn visibility_name(visibility: RoomVisibility) -> &'static str {
+    match visibility {
+        RoomVisibility::Public => "public",
+        RoomVisibility::Password => "password",
+        RoomVisibility::InviteOnly => "invite_only",
+    }
+}
+
+fn parse_visibility(name: &str) -> StorageResult<RoomVisibility> {
+    match name {
+        "public" => Ok(RoomVisibility::Public),
+        "password" => Ok(RoomVisibility::Password),
+        "invite_only" => Ok(RoomVisibility::InviteOnly),
+        other => Err(format!("Unknown room visibility: {}", other).into()),
+    }
+}
+
+fn parse_timestamp(text: &str) -> StorageResult<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(text)?.with_timezone(&Utc))
+}
@@ -0,0 +1,107 @@
+use tokio::sync::{mpsc, oneshot};
+use chrono::{DateTime, Utc};
+use log::error;
+
+use crate::room::ChatMessage;
+use crate::storage::{Account, RoomRecord, Storage, StoredRoom};
+
+// ストレージタスクへの要求。書き込みは応答を待たない
+#[derive(Debug)]
+pub(crate) enum StorageCommand {
+    LoadRooms {
+        message_limit: usize,
+        reply: oneshot::Sender<Vec<StoredRoom>>,
+    },
+    SaveRoom {
+        record: RoomRecord,
+    },
+    AppendMessage {
+        room_name: String,
+        message: ChatMessage,
+    },
+    UpdateMessage {
+        room_name: String,
+        message: ChatMessage,
+    },
+    SaveReadMarker {
+        room_name: String,
+        identity: String,
+        seq: u64,
+    },
+    TouchUser {
+        username: String,
+        at: DateTime<Utc>,
+    },
+    FindAccount {
+        username: String,
+        reply: oneshot::Sender<Option<Account>>,
+    },
+    // 作れたら true (同じ名前が登録済みなら false)
+    CreateAccount {
+        account: Account,
+        at: DateTime<Utc>,
+        reply: oneshot::Sender<bool>,
+    },
+    // それまでに送られた書き込みがすべて終わったら応答する
+    Flush {
+        reply: oneshot::Sender<()>,
+    },
+}
+
+// ストレージタスクへのハンドル。ディレクトリと各ルームが clone して持つ
+#[derive(Debug, Clone)]
+pub(crate) struct StorageHandle {
+    commands: mpsc::UnboundedSender<StorageCommand>,
+}
+
+impl StorageHandle {
+    // 保存先の呼び出しはブロックするので、非同期のタスクとは別のスレッドで処理する
+    pub(crate) fn spawn(storage: Box<dyn Storage>) -> Self {
+        let (commands, rx) = mpsc::unbounded_channel();
+        tokio::task::spawn_blocking(move || run(storage, rx));
+
+        Self { commands }
+    }
+
+    pub(crate) fn send(&self, command: StorageCommand) {
+        if self.commands.send(command).is_err() {
+            error!("Storage task has stopped");
+        }
+    }
+
+    // コマンドを送り、処理が終わるまで待つ
+    pub(crate) async fn request<T>(&self, command: impl FnOnce(oneshot::Sender<T>) -> StorageCommand) -> Option<T> {
+        let (reply, rx) = oneshot::channel();
+        self.send(command(reply));
+        rx.await.ok()
+    }
+}
+
+// 失敗はログに残して続ける (チャット自体は止めない)
+fn run(mut storage: Box<dyn Storage>, mut commands: mpsc::UnboundedReceiver<StorageCommand>) {
+    while let Some(command) = commands.blocking_recv() {
+        let result = match command {
+            StorageCommand::LoadRooms { message_limit, reply } => storage.load_rooms(message_limit).map(|rooms| {
+                let _ = reply.send(rooms);
+            }),
+            StorageCommand::SaveRoom { record } => storage.save_room(&record),
+            StorageCommand::AppendMessage { room_name, message } => storage.append_message(&room_name, &message),
+            StorageCommand::UpdateMessage { room_name, message } => storage.update_message(&room_name, &message),
+            StorageCommand::SaveReadMarker { room_name, identity, seq } => storage.save_read_marker(&room_name, &identity, seq),
+            StorageCommand::TouchUser { username, at } => storage.touch_user(&username, at),
+            StorageCommand::FindAccount { username, reply } => storage.find_account(&username).map(|account| {
+                let _ = reply.send(account);
+            }),
+            StorageCommand::CreateAccount { account, at, reply } => storage.create_account(&account, at).map(|created| {
+                let _ = reply.send(created);
+            }),
+            StorageCommand::Flush { reply } => {
+                let _ = reply.send(());
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            error!("Storage error: {}", e);
+        }
+    }
+}
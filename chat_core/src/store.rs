@@ -0,0 +1,147 @@
+use std::collections::VecDeque;
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::room::ChatMessage;
+
+// ルームごとのメッセージ保持ポリシー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub capacity: usize,
+    pub ttl: Option<TimeDelta>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            capacity: 100, // メッセージ履歴の最大数
+            ttl: None,
+        }
+    }
+}
+
+// 容量固定のリングバッファ。古いメッセージから O(1) で捨てる
+#[derive(Debug)]
+pub struct MessageStore {
+    buffer: VecDeque<ChatMessage>,
+    policy: RetentionPolicy,
+    next_seq: u64, // これまでに追加した件数 (= 次のメッセージの通し番号)
+}
+
+impl MessageStore {
+    pub fn new(policy: RetentionPolicy) -> Self {
+        Self {
+            buffer: VecDeque::with_capacity(policy.capacity),
+            policy,
+            next_seq: 0,
+        }
+    }
+
+    // 保存されていたメッセージ (通し番号の古い順) から作り直す。番号は続きから振る
+    pub fn restore(policy: RetentionPolicy, messages: Vec<ChatMessage>) -> Self {
+        let mut store = Self::new(policy);
+        for message in messages {
+            store.next_seq = message.seq;
+            store.push(message);
+        }
+        store.expire(Utc::now());
+        store
+    }
+
+    pub fn policy(&self) -> RetentionPolicy {
+        self.policy
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    // メッセージに通し番号を振って追加し、その番号を返す
+    pub fn push(&mut self, mut message: ChatMessage) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        message.seq = seq;
+
+        let now = message.timestamp;
+        if self.policy.capacity > 0 {
+            if self.buffer.len() == self.policy.capacity {
+                self.buffer.pop_front();
+            }
+            self.buffer.push_back(message);
+        }
+        self.expire(now);
+
+        seq
+    }
+
+    // TTL を過ぎたメッセージを先頭から捨てる
+    pub fn expire(&mut self, now: DateTime<Utc>) {
+        let live_start = self.live_start(now);
+        self.buffer.drain(..live_start);
+    }
+
+    // 直近 n 件 (古い順)
+    pub fn last_n(&self, n: usize) -> Vec<ChatMessage> {
+        let live_start = self.live_start(Utc::now());
+        let start = self.buffer.len().saturating_sub(n).max(live_start);
+        self.buffer.range(start..).cloned().collect()
+    }
+
+    // 指定時刻より後のメッセージ (古い順)
+    pub fn since_timestamp(&self, timestamp: DateTime<Utc>) -> Vec<ChatMessage> {
+        let live_start = self.live_start(Utc::now());
+        let start = self.buffer.partition_point(|m| m.timestamp <= timestamp).max(live_start);
+        self.buffer.range(start..).cloned().collect()
+    }
+
+    // 条件に合うメッセージ (古い順)
+    pub fn filter(&self, predicate: impl Fn(&ChatMessage) -> bool) -> Vec<ChatMessage> {
+        let live_start = self.live_start(Utc::now());
+        self.buffer.range(live_start..).filter(|m| predicate(m)).cloned().collect()
+    }
+
+    pub fn count(&self, predicate: impl Fn(&ChatMessage) -> bool) -> usize {
+        let live_start = self.live_start(Utc::now());
+        self.buffer.range(live_start..).filter(|m| predicate(m)).count()
+    }
+
+    // 指定した通し番号より後のメッセージ (古い順)
+    pub fn since_seq(&self, seq: u64) -> Vec<ChatMessage> {
+        let live_start = self.live_start(Utc::now());
+        let start = (seq + 1).saturating_sub(self.first_seq()).min(self.buffer.len() as u64) as usize;
+        self.buffer.range(start.max(live_start)..).cloned().collect()
+    }
+
+    // ID でメッセージを探す (捨てられたものや TTL を過ぎたものは見つからない)
+    pub fn find(&self, id: &str) -> Option<&ChatMessage> {
+        let live_start = self.live_start(Utc::now());
+        self.buffer.range(live_start..).find(|m| m.id == id)
+    }
+
+    pub fn find_mut(&mut self, id: &str) -> Option<&mut ChatMessage> {
+        let live_start = self.live_start(Utc::now());
+        self.buffer.range_mut(live_start..).find(|m| m.id == id)
+    }
+
+    // バッファ先頭のメッセージの通し番号
+    pub fn first_seq(&self) -> u64 {
+        self.next_seq - self.buffer.len() as u64
+    }
+
+    // これまでに追加した最後のメッセージの通し番号 (まだ 1 件もなければ None)
+    pub fn last_seq(&self) -> Option<u64> {
+        self.next_seq.checked_sub(1)
+    }
+
+    // TTL 内に残っている最初の位置。タイムスタンプは追加順に並んでいる前提
+    // (表せる時刻より前までさかのぼる長い TTL は、期限がないのと同じに扱う)
+    fn live_start(&self, now: DateTime<Utc>) -> usize {
+        match self.policy.ttl.and_then(|ttl| now.checked_sub_signed(ttl)) {
+            Some(cutoff) => self.buffer.partition_point(|m| m.timestamp < cutoff),
+            None => 0,
+        }
+    }
+}
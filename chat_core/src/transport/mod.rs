@@ -0,0 +1,11 @@
+pub mod tcp;
+
+use std::future::Future;
+
+use crate::server::ChatServer;
+
+// 接続を受け付け、それぞれをサーバーのセッションにつなぐ
+// 同じ ChatServer を複数のトランスポートに渡せば、全員が同じルームを共有する
+pub trait Transport {
+    fn serve(self, server: ChatServer) -> impl Future<Output = Result<(), Box<dyn std::error::Error>>>;
+}
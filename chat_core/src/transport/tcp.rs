@@ -0,0 +1,101 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use log::{info, error};
+
+use chat_protocol::SequencedMessage;
+use crate::server::ChatServer;
+use crate::session::Flow;
+use crate::transport::Transport;
+
+// 改行区切り JSON の TCP トランスポート
+#[derive(Debug)]
+pub struct TcpTransport {
+    listener: TcpListener,
+}
+
+impl TcpTransport {
+    pub async fn bind(addr: &str) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        Ok(Self { listener })
+    }
+
+    pub fn from_listener(listener: TcpListener) -> Self {
+        Self { listener }
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+impl Transport for TcpTransport {
+    async fn serve(self, server: ChatServer) -> Result<(), Box<dyn std::error::Error>> {
+        info!("Chat server listening on {} (tcp)", self.listener.local_addr()?);
+
+        loop {
+            let (socket, addr) = self.listener.accept().await?;
+            info!("New connection from: {}", addr);
+
+            let server = server.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_client(server, socket).await {
+                    error!("Error handling client {}: {}", addr, e);
+                }
+            });
+        }
+    }
+}
+
+async fn handle_client(server: ChatServer, stream: TcpStream) -> Result<(), Box<dyn std::error::Error>> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    // 接続ごとの送信チャネル (ルーターからの配送先)
+    let (tx, mut rx) = mpsc::unbounded_channel::<SequencedMessage>();
+
+    // 送信タスク
+    let writer_task = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            let json = match serde_json::to_string(&message) {
+                Ok(json) => json,
+                Err(e) => {
+                    error!("Failed to serialize message: {}", e);
+                    continue;
+                }
+            };
+            if writer.write_all(json.as_bytes()).await.is_err() || writer.write_all(b"\n").await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut session = server.connect(Arc::new(tx));
+
+    // 受信ループ
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => {
+                if session.handle_text(&line).await == Flow::Close {
+                    break;
+                }
+            }
+            Ok(None) => {
+                // 接続が閉じられた
+                break;
+            }
+            Err(e) => {
+                error!("Error reading line: {}", e);
+                break;
+            }
+        }
+    }
+
+    // セッションを閉じると送信チャネルも閉じ、送信タスクが終了する
+    session.close().await;
+    writer_task.await?;
+
+    Ok(())
+}
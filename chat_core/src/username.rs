@@ -0,0 +1,42 @@
+// ユーザー名の最大文字数
+pub const MAX_USERNAME_LEN: usize = 32;
+
+// システムの表示と紛らわしい名前は使わせない (大文字小文字は区別しない)
+pub const RESERVED_USERNAMES: &[&str] = &["admin", "root", "server", "system", "moderator"];
+
+// 文字・数字・'_'・'-' だけを許す。問題があればエラーメッセージを返す
+pub fn validate_username(username: &str) -> Result<(), String> {
+    if username.is_empty() {
+        return Err("Username must not be empty".to_string());
+    }
+    if username.chars().count() > MAX_USERNAME_LEN {
+        return Err(format!("Username must be at most {} characters", MAX_USERNAME_LEN));
+    }
+    if !username.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+        return Err("Username may only contain letters, digits, '_' and '-'".to_string());
+    }
+    if RESERVED_USERNAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(username)) {
+        return Err("Username is reserved".to_string());
+    }
+    Ok(())
+}
+
+// 一意性の判定に使うキー ("Alice" と "alice" は同じ名前とみなす)
+pub fn username_key(username: &str) -> String {
+    username.to_lowercase()
+}
+
+// ルームの権限の判定に使うキー。アカウントは名前 (変えられない)、ゲストは再利用されない user_id にひもづける
+// (ゲストの名前は切断すれば誰でも使えるので、同じ名前でログインしても権限は引き継がない)
+pub fn identity_key(user_id: &str, username: &str, registered: bool) -> String {
+    if registered {
+        username_key(username)
+    } else {
+        format!("guest:{}", user_id)
+    }
+}
+
+// ゲストの identity_key はそのセッションが終われば二度と現れない
+pub fn is_guest_identity(identity: &str) -> bool {
+    identity.starts_with("guest:")
+}
@@ -0,0 +1,206 @@
+mod common;
+
+use std::time::Instant;
+
+use common::{create_room, error_message, join_room, send_message, server_without_resume, TestClient};
+use chat_protocol::{ClientMessage, RoomVisibility, ServerMessage};
+use chat_core::server::ChatServer;
+
+fn create_private_room(room_name: &str, visibility: RoomVisibility, password: Option<&str>, hidden: bool) -> ClientMessage {
+    ClientMessage::CreateRoom { room_name: room_name.to_string(), visibility, password: password.map(|p| p.to_string()), hidden }
+}
+
+fn join_with_password(room_name: &str, password: &str) -> ClientMessage {
+    ClientMessage::JoinRoom { room_name: room_name.to_string(), password: Some(password.to_string()) }
+}
+
+fn invite(room_name: &str, username: &str) -> ClientMessage {
+    ClientMessage::Invite { room_name: room_name.to_string(), username: username.to_string() }
+}
+
+fn room_list(messages: &[ServerMessage]) -> Vec<String> {
+    match messages {
+        [ServerMessage::RoomList { rooms, .. }] => {
+            let mut rooms = rooms.clone();
+            rooms.sort();
+            rooms
+        }
+        other => panic!("expected RoomList, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn password_rooms_need_the_right_password() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    alice.send(create_private_room("secret", RoomVisibility::Password, Some("hunter2"), false)).await;
+    alice.drain();
+    bob.drain();
+
+    bob.send(join_room("secret")).await;
+    assert_eq!(error_message(&bob.drain()), Some("Password required"));
+    bob.send(join_with_password("secret", "hunter3")).await;
+    assert_eq!(error_message(&bob.drain()), Some("Wrong password"));
+
+    bob.send(join_with_password("secret", "hunter2")).await;
+    assert!(bob.drain().iter().any(|m| matches!(m, ServerMessage::JoinedRoom { room_name } if room_name == "secret")));
+
+    // オーナーはパスワードなしで参加できる
+    alice.send(join_room("secret")).await;
+    assert!(alice.drain().iter().any(|m| matches!(m, ServerMessage::JoinedRoom { .. })));
+}
+
+#[tokio::test]
+async fn checking_a_room_password_does_not_hold_up_other_users() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    alice.send(create_private_room("secret", RoomVisibility::Password, Some("hunter2"), false)).await;
+    alice.drain();
+    bob.drain();
+
+    // 照合はランタイムのスレッドの外で行われるので、その間も他のユーザーの発言は処理される
+    let started = Instant::now();
+    let join = tokio::spawn(async move {
+        bob.send(join_with_password("secret", "hunter3")).await;
+        bob
+    });
+    tokio::task::yield_now().await;
+    alice.send(send_message("general", "still here")).await;
+    let alice_took = started.elapsed();
+    let mut bob = join.await.unwrap();
+    let bob_took = started.elapsed();
+
+    assert!(alice.drain().iter().any(|m| matches!(m, ServerMessage::NewMessage { content, .. } if content == "still here")));
+    assert!(matches!(bob.drain().last(), Some(ServerMessage::Error { message, .. }) if message == "Wrong password"));
+    assert!(alice_took * 2 < bob_took, "alice waited {:?} while bob's join took {:?}", alice_took, bob_took);
+}
+
+#[tokio::test]
+async fn password_rooms_cannot_be_created_without_a_password() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    alice.drain();
+
+    alice.send(create_private_room("secret", RoomVisibility::Password, Some(""), false)).await;
+    assert_eq!(error_message(&alice.drain()), Some("Password required"));
+
+    alice.send(ClientMessage::ListRooms).await;
+    assert_eq!(room_list(&alice.drain()), vec!["general"]);
+}
+
+#[tokio::test]
+async fn invite_only_rooms_admit_invited_users() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    let mut carol = TestClient::login(&server, "carol").await;
+    alice.send(create_private_room("club", RoomVisibility::InviteOnly, None, false)).await;
+    alice.send(join_room("club")).await;
+    for client in [&mut alice, &mut bob, &mut carol] {
+        client.drain();
+    }
+
+    bob.send(join_room("club")).await;
+    assert_eq!(error_message(&bob.drain()), Some("This room is invite-only"));
+
+    // メンバーと招待された本人に通知される
+    alice.send(invite("club", "BOB")).await;
+    for client in [&mut alice, &mut bob] {
+        assert!(matches!(
+            client.drain().as_slice(),
+            [ServerMessage::UserInvited { room_name, username, by }] if room_name == "club" && username == "bob" && by == "alice"
+        ));
+    }
+    assert!(carol.drain().is_empty());
+
+    bob.send(join_room("club")).await;
+    assert!(bob.drain().iter().any(|m| matches!(m, ServerMessage::JoinedRoom { room_name } if room_name == "club")));
+    alice.drain();
+
+    // モデレーター以外は招待できない
+    bob.send(invite("club", "carol")).await;
+    assert_eq!(error_message(&bob.drain()), Some("Permission denied"));
+    carol.send(join_room("club")).await;
+    assert_eq!(error_message(&carol.drain()), Some("This room is invite-only"));
+
+    alice.send(invite("club", "bob")).await;
+    assert_eq!(error_message(&alice.drain()), Some("User already in room"));
+    alice.send(invite("club", "nobody")).await;
+    assert_eq!(error_message(&alice.drain()), Some("User not found"));
+}
+
+#[tokio::test]
+async fn hidden_rooms_are_listed_only_for_members() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    alice.send(create_private_room("hideout", RoomVisibility::Public, None, true)).await;
+    alice.send(create_room("rust")).await;
+    alice.send(join_room("hideout")).await;
+    alice.drain();
+    bob.drain();
+
+    alice.send(ClientMessage::ListRooms).await;
+    assert_eq!(room_list(&alice.drain()), vec!["general", "hideout", "rust"]);
+    bob.send(ClientMessage::ListRooms).await;
+    assert_eq!(room_list(&bob.drain()), vec!["general", "rust"]);
+
+    let mut public = server.room_list().await;
+    public.sort();
+    assert_eq!(public, vec!["general", "rust"]);
+
+    // 名前を知っていれば参加でき、参加後は一覧に出る
+    bob.send(join_room("hideout")).await;
+    bob.drain();
+    bob.send(ClientMessage::ListRooms).await;
+    assert_eq!(room_list(&bob.drain()), vec!["general", "hideout", "rust"]);
+}
+
+#[tokio::test]
+async fn guest_invitations_are_not_inherited_by_the_same_name() {
+    let server = server_without_resume();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let bob = TestClient::login(&server, "bob").await;
+    alice.send(create_private_room("club", RoomVisibility::InviteOnly, None, true)).await;
+    alice.send(join_room("club")).await;
+    alice.send(invite("club", "bob")).await;
+    bob.close().await;
+
+    // 招待されたゲストが抜けたあとに同じ名前を名乗っても参加できない
+    let mut mallory = TestClient::login(&server, "mallory").await;
+    mallory.send(ClientMessage::ChangeNick { username: "bob".to_string() }).await;
+    mallory.drain();
+    mallory.send(join_room("club")).await;
+    assert_eq!(error_message(&mallory.drain()), Some("This room is invite-only"));
+}
+
+#[tokio::test]
+async fn invitations_follow_guest_nick_changes() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    alice.send(create_private_room("club", RoomVisibility::InviteOnly, None, false)).await;
+    alice.send(join_room("club")).await;
+    alice.send(invite("club", "bob")).await;
+    bob.send(ClientMessage::ChangeNick { username: "robert".to_string() }).await;
+    bob.drain();
+
+    bob.send(join_room("club")).await;
+    assert!(bob.drain().iter().any(|m| matches!(m, ServerMessage::JoinedRoom { room_name } if room_name == "club")));
+}
+
+#[tokio::test]
+async fn invited_users_skip_the_password() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    alice.send(create_private_room("secret", RoomVisibility::Password, Some("hunter2"), false)).await;
+    alice.send(join_room("secret")).await;
+    alice.send(invite("secret", "Bob")).await;
+    bob.drain();
+
+    bob.send(join_room("secret")).await;
+    assert!(bob.drain().iter().any(|m| matches!(m, ServerMessage::JoinedRoom { room_name } if room_name == "secret")));
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::{login_with_password, register, TestClient};
+use chat_protocol::{ClientMessage, ErrorCode, ServerMessage};
+use chat_core::config::ServerConfig;
+use chat_core::server::ChatServer;
+
+fn error_code(messages: &[ServerMessage]) -> Option<ErrorCode> {
+    match messages {
+        [ServerMessage::Error { code, .. }] => *code,
+        other => panic!("expected a single Error, got {:?}", other),
+    }
+}
+
+fn welcomed(messages: &[ServerMessage]) -> bool {
+    messages.iter().any(|m| matches!(m, ServerMessage::Welcome { .. }))
+}
+
+async fn general_users(client: &mut TestClient) -> Vec<String> {
+    client.send(ClientMessage::ListUsers { room_name: "general".to_string() }).await;
+    match client.drain().as_slice() {
+        [ServerMessage::UserList { users, .. }] => {
+            let mut users = users.clone();
+            users.sort();
+            users
+        }
+        other => panic!("expected UserList, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn registered_users_log_in_with_their_password() {
+    let server = ChatServer::new();
+    let mut alice = register(&server, "Alice", "correct horse").await;
+    assert!(welcomed(&alice.drain()));
+    alice.close().await;
+
+    // 名前は大文字小文字を区別せず、登録時の表記でログインする
+    let mut client = TestClient::connect(&server);
+    client.send(login_with_password("alice", "correct horse")).await;
+    assert!(welcomed(&client.drain()));
+    assert_eq!(general_users(&mut client).await, vec!["Alice"]);
+}
+
+#[tokio::test]
+async fn wrong_passwords_and_unknown_accounts_are_indistinguishable() {
+    let server = ChatServer::new();
+    register(&server, "alice", "correct horse").await;
+
+    let mut client = TestClient::connect(&server);
+    client.send(login_with_password("bob", "correct horse")).await;
+    assert_eq!(error_code(&client.drain()), Some(ErrorCode::InvalidCredentials));
+    client.send(login_with_password("alice", "wrong horse")).await;
+    assert_eq!(error_code(&client.drain()), Some(ErrorCode::InvalidCredentials));
+
+    // ログイン前のまま再試行できる
+    client.send(login_with_password("bob", "")).await;
+    client.send(ClientMessage::Login { username: "carol".to_string(), password: None }).await;
+    let messages = client.drain();
+    assert_eq!(error_code(&messages[..1]), Some(ErrorCode::InvalidCredentials));
+    assert!(welcomed(&messages[1..]));
+}
+
+#[tokio::test]
+async fn registration_is_validated() {
+    let server = ChatServer::new();
+    let _alice = register(&server, "alice", "correct horse").await;
+    let _guest = TestClient::login(&server, "bob").await;
+    let mut client = TestClient::connect(&server);
+
+    let attempts = [
+        ("carol", "short", ErrorCode::WeakPassword),
+        ("carol smith", "correct horse", ErrorCode::InvalidUsername),
+        ("ALICE", "another horse", ErrorCode::UsernameTaken),
+        ("bob", "correct horse", ErrorCode::UsernameTaken),
+    ];
+    for (username, password, code) in attempts {
+        client.send(ClientMessage::Register { username: username.to_string(), password: password.to_string() }).await;
+        assert_eq!(error_code(&client.drain()), Some(code), "registering {}", username);
+    }
+}
+
+#[tokio::test]
+async fn guests_cannot_use_registered_names() {
+    let server = ChatServer::new();
+    register(&server, "alice", "correct horse").await.close().await;
+
+    let mut guest = TestClient::connect(&server);
+    guest.send(ClientMessage::Login { username: "Alice".to_string(), password: None }).await;
+    assert_eq!(error_code(&guest.drain()), Some(ErrorCode::UsernameTaken));
+
+    guest.send(ClientMessage::Login { username: "mallory".to_string(), password: None }).await;
+    guest.drain();
+    guest.send(ClientMessage::ChangeNick { username: "alice".to_string() }).await;
+    assert_eq!(error_code(&guest.drain()), Some(ErrorCode::UsernameTaken));
+}
+
+#[tokio::test]
+async fn an_account_can_only_be_logged_in_once() {
+    let server = ChatServer::new();
+    let mut alice = register(&server, "alice", "correct horse").await;
+    alice.drain();
+
+    let mut client = TestClient::connect(&server);
+    client.send(login_with_password("alice", "correct horse")).await;
+    assert_eq!(error_code(&client.drain()), Some(ErrorCode::AccountInUse));
+
+    // アカウントの名前は変えられない
+    alice.send(ClientMessage::ChangeNick { username: "alicia".to_string() }).await;
+    assert!(matches!(
+        alice.drain().as_slice(),
+        [ServerMessage::Error { message, code: None }] if message == "Registered users cannot change their username"
+    ));
+}
+
+#[tokio::test]
+async fn guest_logins_can_be_disabled() {
+    let server = ChatServer::with_config(ServerConfig { allow_guests: false, ..Default::default() });
+    let mut guest = TestClient::login(&server, "bob").await;
+    assert_eq!(error_code(&guest.drain()), Some(ErrorCode::GuestsDisabled));
+
+    let mut alice = register(&server, "alice", "correct horse").await;
+    assert!(welcomed(&alice.drain()));
+}
@@ -0,0 +1,130 @@
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use chrono::TimeDelta;
+use chat_core::config::ServerConfig;
+use chat_core::server::ChatServer;
+use chat_core::session::{Flow, Session};
+use chat_protocol::{Capability, ClientMessage, RoomVisibility, SequencedMessage, ServerMessage, PROTOCOL_VERSION};
+use tokio::sync::mpsc;
+
+// チャネルを接続に見立てたテスト用クライアント
+pub struct TestClient {
+    session: Session,
+    rx: mpsc::UnboundedReceiver<SequencedMessage>,
+}
+
+impl TestClient {
+    pub fn connect(server: &ChatServer) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self { session: server.connect(Arc::new(tx)), rx }
+    }
+
+    pub async fn login(server: &ChatServer, username: &str) -> Self {
+        let mut client = Self::connect(server);
+        client.send(ClientMessage::Login { username: username.to_string(), password: None }).await;
+        client
+    }
+
+    pub async fn login_with(server: &ChatServer, username: &str, capabilities: Vec<Capability>) -> Self {
+        let mut client = Self::connect(server);
+        client.send(ClientMessage::Hello { protocol_version: PROTOCOL_VERSION, capabilities }).await;
+        client.send(ClientMessage::Login { username: username.to_string(), password: None }).await;
+        client
+    }
+
+    pub async fn send(&mut self, message: ClientMessage) -> Flow {
+        self.session.handle(message).await
+    }
+
+    pub fn drain(&mut self) -> Vec<ServerMessage> {
+        self.drain_sequenced().into_iter().map(|m| m.message).collect()
+    }
+
+    // セッションの通し番号も見る
+    pub fn drain_sequenced(&mut self) -> Vec<SequencedMessage> {
+        let mut messages = Vec::new();
+        while let Ok(message) = self.rx.try_recv() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    pub async fn close(self) {
+        self.session.close().await;
+    }
+}
+
+// 切断したユーザーをすぐに削除するサーバー (再開の猶予なし)
+pub fn server_without_resume() -> ChatServer {
+    ChatServer::with_config(ServerConfig { resume_grace: TimeDelta::zero(), ..Default::default() })
+}
+
+pub fn send_message(room_name: &str, content: &str) -> ClientMessage {
+    ClientMessage::SendMessage { room_name: room_name.to_string(), content: content.to_string(), reply_to: None }
+}
+
+pub fn join_room(room_name: &str) -> ClientMessage {
+    ClientMessage::JoinRoom { room_name: room_name.to_string(), password: None }
+}
+
+// 公開ルームを作成する
+pub fn create_room(room_name: &str) -> ClientMessage {
+    ClientMessage::CreateRoom { room_name: room_name.to_string(), visibility: RoomVisibility::Public, password: None, hidden: false }
+}
+
+// アカウントを登録してそのままログインする
+pub async fn register(server: &ChatServer, username: &str, password: &str) -> TestClient {
+    let mut client = TestClient::connect(server);
+    client.send(ClientMessage::Register { username: username.to_string(), password: password.to_string() }).await;
+    client
+}
+
+pub fn login_with_password(username: &str, password: &str) -> ClientMessage {
+    ClientMessage::Login { username: username.to_string(), password: Some(password.to_string()) }
+}
+
+// 先頭のユーザーが room_name を作成し (オーナー)、全員がログインして参加する。それまでに届いたメッセージは捨てる
+pub async fn join_all<const N: usize>(server: &ChatServer, room_name: &str, usernames: [&str; N]) -> [TestClient; N] {
+    let mut clients = Vec::new();
+    for username in usernames {
+        clients.push(TestClient::login(server, username).await);
+    }
+    clients[0].send(create_room(room_name)).await;
+    for client in &mut clients {
+        client.send(join_room(room_name)).await;
+    }
+    for client in &mut clients {
+        client.drain();
+    }
+    clients.try_into().unwrap_or_else(|_| unreachable!())
+}
+
+// capabilities だけを合意してログインし、既存のルームに参加する。それまでに届いたメッセージは捨てる
+pub async fn join_with(server: &ChatServer, room_name: &str, username: &str, capabilities: Vec<Capability>) -> TestClient {
+    let mut client = TestClient::login_with(server, username, capabilities).await;
+    client.send(join_room(room_name)).await;
+    client.drain();
+    client
+}
+
+// エラーだけが 1 件届いていればそのメッセージ
+pub fn error_message(messages: &[ServerMessage]) -> Option<&str> {
+    match messages {
+        [ServerMessage::Error { message, .. }] => Some(message),
+        _ => None,
+    }
+}
+
+// 最後に届いた NewMessage の ID・通し番号・返信先
+pub fn last_new_message(messages: &[ServerMessage]) -> (String, u64, Option<String>) {
+    messages.iter().rev().find_map(|m| match m {
+        ServerMessage::NewMessage { id, seq, reply_to, .. } => Some((id.clone(), *seq, reply_to.clone())),
+        _ => None,
+    }).expect("expected NewMessage")
+}
+
+pub fn last_message_id(messages: &[ServerMessage]) -> String {
+    last_new_message(messages).0
+}
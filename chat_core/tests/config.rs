@@ -0,0 +1,19 @@
+use chrono::TimeDelta;
+use chat_core::config::ServerConfig;
+
+// 環境変数はプロセス全体で共有されるので、このファイルのテストは 1 つにまとめる
+#[test]
+fn message_ttl_from_the_environment_must_be_in_range() {
+    let ttl = |value: &str| {
+        // SAFETY: このテストバイナリで環境変数に触るのはこのテストだけ
+        unsafe { std::env::set_var("CHAT_MESSAGE_TTL_SECS", value) };
+        ServerConfig::from_env().retention.ttl
+    };
+
+    assert_eq!(ttl("3600"), Some(TimeDelta::hours(1)));
+    assert_eq!(ttl("-5"), None);
+    // DateTime の範囲を越える長さは受け付ける (ストア側で期限なしとして扱う)
+    assert_eq!(ttl("10000000000000"), Some(TimeDelta::seconds(10_000_000_000_000)));
+    assert_eq!(ttl("9223372036854775807"), None);
+    assert_eq!(ttl("18446744073709551615"), None);
+}
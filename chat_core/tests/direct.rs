@@ -0,0 +1,117 @@
+mod common;
+
+use std::time::Duration;
+
+use chrono::TimeDelta;
+use common::{create_room, join_room, server_without_resume, TestClient};
+use chat_protocol::{Capability, ClientMessage, ServerMessage};
+use chat_core::config::ServerConfig;
+use chat_core::server::ChatServer;
+
+fn direct_message(to: &str, content: &str) -> ClientMessage {
+    ClientMessage::DirectMessage { to: to.to_string(), content: content.to_string() }
+}
+
+#[tokio::test]
+async fn direct_message_reaches_only_the_recipient() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    let mut carol = TestClient::login(&server, "carol").await;
+    alice.drain();
+    bob.drain();
+    carol.drain();
+
+    alice.send(direct_message("bob", "hi bob")).await;
+
+    assert!(matches!(
+        bob.drain().as_slice(),
+        [ServerMessage::DirectMessage { from, content, .. }] if from == "alice" && content == "hi bob"
+    ));
+    assert!(alice.drain().is_empty());
+    assert!(carol.drain().is_empty());
+}
+
+#[tokio::test]
+async fn direct_message_recipient_is_case_insensitive() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bobby = TestClient::login(&server, "bobby").await;
+    alice.drain();
+    bobby.drain();
+
+    alice.send(direct_message("BOBBY", "hi")).await;
+
+    assert!(matches!(bobby.drain().as_slice(), [ServerMessage::DirectMessage { from, .. }] if from == "alice"));
+    assert!(alice.drain().is_empty());
+}
+
+#[tokio::test]
+async fn direct_message_crosses_rooms() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    alice.send(create_room("rust")).await;
+    alice.send(join_room("rust")).await;
+    bob.drain();
+
+    alice.send(direct_message("bob", "over here")).await;
+
+    assert!(matches!(bob.drain().as_slice(), [ServerMessage::DirectMessage { .. }]));
+}
+
+#[tokio::test]
+async fn direct_message_to_unknown_or_offline_user_is_an_error() {
+    let server = server_without_resume();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let bob = TestClient::login(&server, "bob").await;
+    bob.close().await;
+    alice.drain();
+
+    for to in ["nobody", "bob"] {
+        alice.send(direct_message(to, "hello?")).await;
+
+        assert!(matches!(
+            alice.drain().as_slice(),
+            [ServerMessage::Error { message, .. }] if message == "User not found"
+        ));
+    }
+}
+
+#[tokio::test]
+async fn direct_message_to_a_user_awaiting_resume_is_queued_until_the_grace_period_ends() {
+    let config = ServerConfig { resume_grace: TimeDelta::milliseconds(100), ..Default::default() };
+    let server = ChatServer::with_config(config);
+    let mut alice = TestClient::login(&server, "alice").await;
+    let bob = TestClient::login(&server, "bob").await;
+    bob.close().await;
+    alice.drain();
+
+    // 再開待ちの間はエラーにならない (届くかどうかは bob が再開するかで決まる)
+    alice.send(direct_message("bob", "are you back?")).await;
+    assert!(alice.drain().is_empty());
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    alice.drain();
+    alice.send(direct_message("bob", "hello?")).await;
+    assert!(matches!(
+        alice.drain().as_slice(),
+        [ServerMessage::Error { message, .. }] if message == "User not found"
+    ));
+}
+
+#[tokio::test]
+async fn direct_messages_only_reach_sessions_that_negotiated_them() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login_with(&server, "bob", vec![Capability::History]).await;
+    alice.drain();
+    bob.drain();
+
+    alice.send(direct_message("bob", "hi")).await;
+    assert!(matches!(
+        alice.drain().as_slice(),
+        [ServerMessage::Error { message, .. }] if message == "User does not accept direct messages"
+    ));
+    assert!(bob.drain().is_empty());
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use common::{error_message, join_all, join_room, last_message_id, send_message, TestClient};
+use chat_protocol::{ClientMessage, HistoryMessage, ServerMessage};
+use chat_core::server::ChatServer;
+
+fn edit(message_id: &str, content: &str) -> ClientMessage {
+    ClientMessage::EditMessage { room_name: "rust".to_string(), message_id: message_id.to_string(), content: content.to_string() }
+}
+
+fn delete(message_id: &str) -> ClientMessage {
+    ClientMessage::DeleteMessage { room_name: "rust".to_string(), message_id: message_id.to_string() }
+}
+
+async fn history(server: &ChatServer) -> Vec<HistoryMessage> {
+    let mut reader = TestClient::login(server, "reader").await;
+    reader.send(join_room("rust")).await;
+    match reader.drain().last() {
+        Some(ServerMessage::History { messages, .. }) => messages.clone(),
+        other => panic!("expected History, got {:?}", other),
+    }
+}
+
+// alice が rust を作成し (オーナー)、bob と carol が参加する。bob が 1 件投稿する
+async fn setup(server: &ChatServer) -> (TestClient, TestClient, TestClient, String) {
+    let [mut alice, mut bob, mut carol] = join_all(server, "rust", ["alice", "bob", "carol"]).await;
+    bob.send(send_message("rust", "helo")).await;
+    let message_id = last_message_id(&alice.drain());
+    bob.drain();
+    carol.drain();
+    (alice, bob, carol, message_id)
+}
+
+#[tokio::test]
+async fn authors_can_edit_and_the_room_sees_it() {
+    let server = ChatServer::new();
+    let (mut alice, mut bob, _carol, message_id) = setup(&server).await;
+
+    bob.send(edit(&message_id, "hello")).await;
+    for client in [&mut alice, &mut bob] {
+        match client.drain().as_slice() {
+            [ServerMessage::MessageEdited { room_name, message_id: id, content, by, .. }] => {
+                assert_eq!((room_name.as_str(), id, content.as_str(), by.as_str()), ("rust", &message_id, "hello", "bob"));
+            }
+            other => panic!("expected MessageEdited, got {:?}", other),
+        }
+    }
+
+    let history = history(&server).await;
+    assert_eq!(history[0].content, "hello");
+    assert!(history[0].edited_at.is_some());
+    assert!(!history[0].deleted);
+}
+
+#[tokio::test]
+async fn only_the_author_or_a_moderator_can_change_a_message() {
+    let server = ChatServer::new();
+    let (mut alice, mut bob, mut carol, message_id) = setup(&server).await;
+
+    carol.send(edit(&message_id, "hijacked")).await;
+    assert_eq!(error_message(&carol.drain()), Some("Permission denied"));
+    carol.send(delete(&message_id)).await;
+    assert_eq!(error_message(&carol.drain()), Some("Permission denied"));
+    assert!(bob.drain().is_empty());
+
+    // オーナーはモデレーターとして削除できる
+    alice.send(delete(&message_id)).await;
+    assert!(matches!(
+        bob.drain().as_slice(),
+        [ServerMessage::MessageDeleted { message_id: id, by, .. }] if *id == message_id && by == "alice"
+    ));
+}
+
+#[tokio::test]
+async fn deleted_messages_stay_in_history_as_tombstones() {
+    let server = ChatServer::new();
+    let (_alice, mut bob, _carol, message_id) = setup(&server).await;
+    bob.send(send_message("rust", "still here")).await;
+
+    bob.send(delete(&message_id)).await;
+    bob.drain();
+    bob.send(edit(&message_id, "back again")).await;
+    assert_eq!(error_message(&bob.drain()), Some("Message not found"));
+
+    let history = history(&server).await;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].id, message_id);
+    assert!(history[0].deleted);
+    assert!(history[0].content.is_empty());
+    assert_eq!(history[1].content, "still here");
+}
+
+#[tokio::test]
+async fn muted_authors_cannot_edit_but_can_delete() {
+    let server = ChatServer::new();
+    let (mut alice, mut bob, _carol, message_id) = setup(&server).await;
+    alice.send(ClientMessage::Mute { room_name: "rust".to_string(), username: "bob".to_string() }).await;
+    bob.drain();
+
+    bob.send(edit(&message_id, "sneaky")).await;
+    assert_eq!(error_message(&bob.drain()), Some("You are muted in this room"));
+    bob.send(delete(&message_id)).await;
+    assert!(matches!(bob.drain().as_slice(), [ServerMessage::MessageDeleted { .. }]));
+}
+
+#[tokio::test]
+async fn editing_requires_membership_and_a_known_message() {
+    let server = ChatServer::new();
+    let (_alice, mut bob, _carol, message_id) = setup(&server).await;
+
+    bob.send(edit("no-such-message", "hello")).await;
+    assert_eq!(error_message(&bob.drain()), Some("Message not found"));
+
+    let mut outsider = TestClient::login(&server, "outsider").await;
+    outsider.drain();
+    outsider.send(delete(&message_id)).await;
+    assert!(matches!(outsider.drain().as_slice(), [ServerMessage::Error { .. }]));
+}
+
+#[tokio::test]
+async fn a_guest_taking_over_an_old_name_cannot_change_its_messages() {
+    let server = ChatServer::new();
+    let (_alice, mut bob, _carol, message_id) = setup(&server).await;
+
+    // bob が名前を変えると "bob" は誰でも使えるようになる
+    bob.send(ClientMessage::ChangeNick { username: "bobby".to_string() }).await;
+    bob.drain();
+    let mut impostor = TestClient::login(&server, "bob").await;
+    impostor.send(join_room("rust")).await;
+    impostor.drain();
+    bob.drain();
+
+    impostor.send(edit(&message_id, "hijacked")).await;
+    assert_eq!(error_message(&impostor.drain()), Some("Permission denied"));
+    impostor.send(delete(&message_id)).await;
+    assert_eq!(error_message(&impostor.drain()), Some("Permission denied"));
+
+    bob.send(edit(&message_id, "hello")).await;
+    assert!(matches!(bob.drain().as_slice(), [ServerMessage::MessageEdited { by, .. }] if by == "bobby"));
+    bob.send(delete(&message_id)).await;
+    assert!(matches!(bob.drain().as_slice(), [ServerMessage::MessageDeleted { by, .. }] if by == "bobby"));
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use common::{create_room, join_room, send_message, TestClient};
+use chat_protocol::ServerMessage;
+use chat_core::config::ServerConfig;
+use chat_core::server::ChatServer;
+
+#[tokio::test]
+async fn login_replays_recent_general_history() {
+    let server = ChatServer::with_config(ServerConfig { history_limit: 2, ..Default::default() });
+    let mut alice = TestClient::login(&server, "alice").await;
+    for content in ["one", "two", "three"] {
+        alice.send(send_message("general", content)).await;
+    }
+
+    let mut bob = TestClient::login(&server, "bob").await;
+    let received = bob.drain();
+    match received.last() {
+        Some(ServerMessage::History { room_name, messages }) => {
+            assert_eq!(room_name, "general");
+            let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+            assert_eq!(contents, vec!["two", "three"]);
+            assert!(messages.iter().all(|m| m.sender == "alice"));
+        }
+        other => panic!("expected History, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn joining_a_room_replays_history_after_joined_room() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    alice.send(create_room("rust")).await;
+    alice.send(join_room("rust")).await;
+    alice.send(send_message("rust", "earlier")).await;
+    bob.drain();
+
+    bob.send(join_room("rust")).await;
+    alice.send(send_message("rust", "later")).await;
+
+    let received = bob.drain();
+    let joined = received.iter().position(|m| matches!(m, ServerMessage::JoinedRoom { .. })).unwrap();
+    match &received[joined + 1] {
+        ServerMessage::History { room_name, messages } => {
+            assert_eq!(room_name, "rust");
+            assert_eq!(messages.len(), 1);
+            assert_eq!(messages[0].content, "earlier");
+        }
+        other => panic!("expected History, got {:?}", other),
+    }
+    assert!(matches!(&received[joined + 2], ServerMessage::NewMessage { content, .. } if content == "later"));
+}
+
+#[tokio::test]
+async fn messages_keep_their_id_seq_and_timestamp_in_history() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    alice.send(create_room("rust")).await;
+    alice.send(join_room("rust")).await;
+    for content in ["one", "two"] {
+        alice.send(send_message("rust", content)).await;
+    }
+    let live: Vec<(String, u64, String)> = alice.drain().into_iter().filter_map(|m| match m {
+        ServerMessage::NewMessage { id, seq, timestamp, .. } => Some((id, seq, timestamp)),
+        _ => None,
+    }).collect();
+    // 通し番号はルームごとに 0 から振られる
+    assert_eq!(live.iter().map(|(_, seq, _)| *seq).collect::<Vec<_>>(), vec![0, 1]);
+    assert_ne!(live[0].0, live[1].0);
+
+    let mut bob = TestClient::login(&server, "bob").await;
+    bob.send(join_room("rust")).await;
+    match bob.drain().last() {
+        Some(ServerMessage::History { messages, .. }) => {
+            let replayed: Vec<(String, u64, String)> = messages.iter().map(|m| (m.id.clone(), m.seq, m.timestamp.clone())).collect();
+            assert_eq!(replayed, live);
+        }
+        other => panic!("expected History, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn clients_without_history_capability_get_no_replay() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    alice.send(send_message("general", "hello")).await;
+
+    let mut bob = TestClient::login_with(&server, "bob", vec![]).await;
+    bob.send(create_room("rust")).await;
+    bob.send(join_room("rust")).await;
+
+    let received = bob.drain();
+    assert!(received.iter().any(|m| matches!(m, ServerMessage::JoinedRoom { .. })));
+    assert!(!received.iter().any(|m| matches!(m, ServerMessage::History { .. })), "{:?}", received);
+}
@@ -0,0 +1,223 @@
+mod common;
+
+use chrono::{TimeDelta, Utc};
+use common::{error_message, join_all, join_room, send_message, server_without_resume, TestClient};
+use chat_protocol::{ClientMessage, ServerMessage};
+use chat_core::moderation::Moderation;
+use chat_core::server::ChatServer;
+use chat_core::username::identity_key;
+
+fn kick(username: &str) -> ClientMessage {
+    ClientMessage::Kick { room_name: "rust".to_string(), username: username.to_string() }
+}
+
+fn ban(username: &str, duration_secs: Option<u64>) -> ClientMessage {
+    ClientMessage::Ban { room_name: "rust".to_string(), username: username.to_string(), duration_secs }
+}
+
+#[tokio::test]
+async fn owner_can_kick_and_the_room_sees_it() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob, mut carol] = join_all(&server, "rust", ["alice", "bob", "carol"]).await;
+
+    alice.send(kick("bob")).await;
+
+    for client in [&mut alice, &mut carol] {
+        assert!(matches!(
+            client.drain().as_slice(),
+            [ServerMessage::UserKicked { username, by, .. }] if username == "bob" && by == "alice"
+        ));
+    }
+    assert!(matches!(
+        bob.drain().as_slice(),
+        [ServerMessage::UserKicked { .. }, ServerMessage::LeftRoom { room_name }] if room_name == "rust"
+    ));
+
+    alice.send(send_message("rust", "bye bob")).await;
+    assert!(bob.drain().is_empty());
+
+    // キックは BAN ではないので再参加できる
+    bob.send(join_room("rust")).await;
+    assert!(bob.drain().iter().any(|m| matches!(m, ServerMessage::JoinedRoom { .. })));
+}
+
+#[tokio::test]
+async fn members_without_permission_are_refused() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob, _carol] = join_all(&server, "rust", ["alice", "bob", "carol"]).await;
+
+    bob.send(kick("carol")).await;
+    assert_eq!(error_message(&bob.drain()), Some("Permission denied"));
+
+    bob.send(ClientMessage::GrantModerator { room_name: "rust".to_string(), username: "bob".to_string() }).await;
+    assert_eq!(error_message(&bob.drain()), Some("Permission denied"));
+
+    alice.send(kick("dave")).await;
+    assert_eq!(error_message(&alice.drain()), Some("User not in room"));
+}
+
+#[tokio::test]
+async fn banned_users_are_refused_by_join_room_until_unbanned() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob, _carol] = join_all(&server, "rust", ["alice", "bob", "carol"]).await;
+
+    alice.send(ban("bob", None)).await;
+    assert!(matches!(
+        bob.drain().as_slice(),
+        [ServerMessage::UserBanned { expires_at: None, .. }, ServerMessage::LeftRoom { .. }]
+    ));
+
+    bob.send(join_room("rust")).await;
+    assert_eq!(error_message(&bob.drain()), Some("You are banned from this room"));
+
+    // 参加していないので rust には送れない
+    bob.send(send_message("rust", "let me in")).await;
+    assert_eq!(error_message(&bob.drain()), Some("Not in room"));
+
+    alice.send(ClientMessage::Unban { room_name: "rust".to_string(), username: "bob".to_string() }).await;
+    bob.send(join_room("rust")).await;
+    assert!(bob.drain().iter().any(|m| matches!(m, ServerMessage::JoinedRoom { .. })));
+}
+
+#[tokio::test]
+async fn expired_bans_no_longer_apply() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob, _carol] = join_all(&server, "rust", ["alice", "bob", "carol"]).await;
+
+    alice.send(ban("bob", Some(0))).await;
+    assert!(matches!(alice.drain().as_slice(), [ServerMessage::UserBanned { expires_at: Some(_), .. }]));
+
+    bob.send(join_room("rust")).await;
+    assert!(bob.drain().iter().any(|m| matches!(m, ServerMessage::JoinedRoom { .. })));
+}
+
+#[tokio::test]
+async fn out_of_range_ban_durations_are_refused() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob, _carol] = join_all(&server, "rust", ["alice", "bob", "carol"]).await;
+
+    // 権限のないメンバーには期限を計算する前に断る
+    bob.send(ban("carol", Some(10_000_000_000_000))).await;
+    assert_eq!(error_message(&bob.drain()), Some("Permission denied"));
+
+    for duration_secs in [10_000_000_000_000, u64::MAX] {
+        alice.send(ban("bob", Some(duration_secs))).await;
+        assert_eq!(error_message(&alice.drain()), Some("Ban duration is out of range"));
+    }
+    assert!(bob.drain().is_empty());
+
+    // サーバーは止まっていない
+    let mut dave = TestClient::login(&server, "dave").await;
+    assert!(matches!(dave.drain().first(), Some(ServerMessage::Welcome { .. })));
+}
+
+#[tokio::test]
+async fn moderators_can_mute_but_not_touch_the_owner() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob, mut carol] = join_all(&server, "rust", ["alice", "bob", "carol"]).await;
+
+    alice.send(ClientMessage::GrantModerator { room_name: "rust".to_string(), username: "bob".to_string() }).await;
+    assert!(matches!(carol.drain().as_slice(), [ServerMessage::ModeratorGranted { username, .. }] if username == "bob"));
+    alice.drain();
+    bob.drain();
+
+    bob.send(ClientMessage::Mute { room_name: "rust".to_string(), username: "carol".to_string() }).await;
+    assert!(matches!(alice.drain().as_slice(), [ServerMessage::UserMuted { username, by, .. }] if username == "carol" && by == "bob"));
+    carol.drain();
+
+    carol.send(send_message("rust", "can you hear me?")).await;
+    assert_eq!(error_message(&carol.drain()), Some("You are muted in this room"));
+    assert!(alice.drain().is_empty());
+
+    bob.send(ClientMessage::Unmute { room_name: "rust".to_string(), username: "carol".to_string() }).await;
+    carol.drain();
+    carol.send(send_message("rust", "back")).await;
+    assert!(matches!(alice.drain().last(), Some(ServerMessage::NewMessage { content, .. }) if content == "back"));
+
+    bob.drain();
+    bob.send(kick("alice")).await;
+    assert_eq!(error_message(&bob.drain()), Some("Cannot moderate the room owner"));
+}
+
+#[tokio::test]
+async fn guest_roles_are_not_inherited_by_the_same_name() {
+    let server = server_without_resume();
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+
+    // 名前を変えてもオーナーのまま
+    alice.send(ClientMessage::ChangeNick { username: "alicia".to_string() }).await;
+    alice.send(ClientMessage::Mute { room_name: "rust".to_string(), username: "bob".to_string() }).await;
+    assert!(matches!(alice.drain().last(), Some(ServerMessage::UserMuted { by, .. }) if by == "alicia"));
+    alice.close().await;
+    bob.drain();
+
+    let mut impostor = TestClient::login(&server, "alicia").await;
+    impostor.send(join_room("rust")).await;
+    impostor.drain();
+    impostor.send(kick("bob")).await;
+    assert_eq!(error_message(&impostor.drain()), Some("Permission denied"));
+}
+
+#[tokio::test]
+async fn muted_guests_stay_muted_after_leaving_and_changing_nick() {
+    let server = server_without_resume();
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+
+    alice.send(ClientMessage::Mute { room_name: "rust".to_string(), username: "bob".to_string() }).await;
+    bob.send(ClientMessage::LeaveRoom { room_name: "rust".to_string() }).await;
+    bob.send(ClientMessage::ChangeNick { username: "robert".to_string() }).await;
+    bob.send(join_room("rust")).await;
+    bob.drain();
+    alice.drain();
+
+    bob.send(send_message("rust", "unmuted?")).await;
+    assert_eq!(error_message(&bob.drain()), Some("You are muted in this room"));
+    assert!(alice.drain().is_empty());
+
+    // 同じ名前の別のゲストはミュートされていない
+    bob.close().await;
+    let mut other = TestClient::login(&server, "robert").await;
+    other.send(join_room("rust")).await;
+    other.send(send_message("rust", "hello")).await;
+    assert!(matches!(alice.drain().last(), Some(ServerMessage::NewMessage { content, .. }) if content == "hello"));
+}
+
+#[tokio::test]
+async fn banned_guests_stay_banned_after_changing_nick() {
+    let server = server_without_resume();
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+
+    alice.send(ban("bob", None)).await;
+    bob.send(ClientMessage::ChangeNick { username: "robert".to_string() }).await;
+    bob.drain();
+    bob.send(join_room("rust")).await;
+    assert_eq!(error_message(&bob.drain()), Some("You are banned from this room"));
+
+    // 接続中のゲストは今の名前で BAN を解除できる
+    alice.send(ClientMessage::Unban { room_name: "rust".to_string(), username: "robert".to_string() }).await;
+    assert!(matches!(alice.drain().last(), Some(ServerMessage::UserUnbanned { .. })));
+    bob.send(join_room("rust")).await;
+    assert!(bob.drain().iter().any(|m| matches!(m, ServerMessage::JoinedRoom { .. })));
+}
+
+#[test]
+fn roles_and_sanctions_belong_to_identities() {
+    let mut moderation = Moderation::with_owner(&identity_key("id-1", "alice", false));
+    moderation.grant_moderator(&identity_key("id-2", "Bob", true));
+    moderation.mute(&identity_key("id-3", "carol", false));
+
+    assert!(moderation.is_owner(&identity_key("id-1", "alicia", false)));
+    assert!(!moderation.is_owner(&identity_key("id-3", "alice", false)));
+    assert!(moderation.is_moderator(&identity_key("id-4", "bob", true)));
+    assert!(!moderation.is_moderator(&identity_key("id-5", "bob", false)));
+
+    assert!(moderation.is_muted(&identity_key("id-3", "caroline", false)));
+    assert!(!moderation.is_muted(&identity_key("id-6", "carol", false)));
+
+    let now = Utc::now();
+    let dave = identity_key("id-7", "Dave", true);
+    moderation.ban(&dave, Some(now + TimeDelta::seconds(60)));
+    assert!(moderation.is_banned(&identity_key("id-8", "dave", true), now));
+    assert!(!moderation.is_banned(&dave, now + TimeDelta::seconds(61)));
+    assert!(!moderation.unban(&dave));
+}
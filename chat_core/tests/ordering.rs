@@ -0,0 +1,63 @@
+mod common;
+
+use common::{send_message, TestClient};
+use chat_protocol::ServerMessage;
+use chat_core::config::ServerConfig;
+use chat_core::server::ChatServer;
+use chat_core::store::RetentionPolicy;
+
+fn new_messages(received: &[ServerMessage]) -> Vec<String> {
+    received
+        .iter()
+        .filter_map(|m| match m {
+            ServerMessage::NewMessage { content, .. } => Some(content.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_senders_are_seen_in_the_same_order_by_every_member() {
+    let retention = RetentionPolicy { capacity: 1000, ..Default::default() };
+    let server = ChatServer::with_config(ServerConfig { history_limit: 1000, retention, ..Default::default() });
+
+    let mut clients = Vec::new();
+    for i in 0..4 {
+        clients.push(TestClient::login(&server, &format!("user{}", i)).await);
+    }
+    for client in clients.iter_mut() {
+        client.drain();
+    }
+
+    // 全員が同時に送信する
+    let handles: Vec<_> = clients
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut client)| {
+            tokio::spawn(async move {
+                for n in 0..50 {
+                    client.send(send_message("general", &format!("{}-{}", i, n))).await;
+                }
+                client
+            })
+        })
+        .collect();
+
+    let mut clients = Vec::new();
+    for handle in handles {
+        clients.push(handle.await.unwrap());
+    }
+    let orders: Vec<Vec<String>> = clients.iter_mut().map(|client| new_messages(&client.drain())).collect();
+
+    assert_eq!(orders[0].len(), 200);
+    assert!(orders.iter().all(|order| order == &orders[0]));
+
+    // 履歴も配送と同じ順序になる
+    let mut late = TestClient::login(&server, "late").await;
+    let history = late.drain().into_iter().find_map(|m| match m {
+        ServerMessage::History { messages, .. } => Some(messages),
+        _ => None,
+    });
+    let history: Vec<String> = history.unwrap().into_iter().map(|m| m.content).collect();
+    assert_eq!(history, orders[0]);
+}
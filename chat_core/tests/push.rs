@@ -0,0 +1,52 @@
+mod common;
+
+use common::{send_message, server_without_resume, TestClient};
+use chat_protocol::{ClientMessage, ServerMessage};
+use chat_core::server::ChatServer;
+
+#[tokio::test]
+async fn room_messages_are_pushed_to_idle_members() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    alice.drain();
+    bob.drain();
+
+    // bob は何も送信していないが、alice のメッセージが届く
+    alice.send(send_message("general", "hello")).await;
+
+    let received = bob.drain();
+    assert!(matches!(
+        received.as_slice(),
+        [ServerMessage::NewMessage { sender, content, .. }] if sender == "alice" && content == "hello"
+    ));
+}
+
+#[tokio::test]
+async fn direct_responses_are_pushed_to_the_requester_only() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    alice.drain();
+    bob.drain();
+
+    alice.send(ClientMessage::ListRooms).await;
+
+    assert!(matches!(alice.drain().as_slice(), [ServerMessage::RoomList { .. }]));
+    assert!(bob.drain().is_empty());
+}
+
+#[tokio::test]
+async fn disconnect_pushes_user_left() {
+    let server = server_without_resume();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let bob = TestClient::login(&server, "bob").await;
+    alice.drain();
+
+    bob.close().await;
+
+    assert!(matches!(
+        alice.drain().as_slice(),
+        [ServerMessage::UserLeft { username, .. }] if username == "bob"
+    ));
+}
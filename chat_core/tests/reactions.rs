@@ -0,0 +1,161 @@
+mod common;
+
+use common::{error_message, join_all, join_room, join_with, last_message_id, send_message, TestClient};
+use chat_protocol::{Capability, ClientMessage, Reaction, ServerMessage};
+use chat_core::server::ChatServer;
+
+fn react(message_id: &str, emoji: &str) -> ClientMessage {
+    ClientMessage::React { room_name: "rust".to_string(), message_id: message_id.to_string(), emoji: emoji.to_string() }
+}
+
+fn unreact(message_id: &str, emoji: &str) -> ClientMessage {
+    ClientMessage::Unreact { room_name: "rust".to_string(), message_id: message_id.to_string(), emoji: emoji.to_string() }
+}
+
+// 最後に届いた ReactionsUpdated の集計
+fn last_reactions(messages: &[ServerMessage]) -> Vec<Reaction> {
+    messages.iter().rev().find_map(|m| match m {
+        ServerMessage::ReactionsUpdated { reactions, .. } => Some(reactions.clone()),
+        _ => None,
+    }).expect("expected ReactionsUpdated")
+}
+
+fn summary(reactions: &[Reaction]) -> Vec<(&str, u32, Vec<&str>)> {
+    reactions.iter().map(|r| (r.emoji.as_str(), r.count, r.users.iter().map(String::as_str).collect())).collect()
+}
+
+// alice が rust を作成して bob と参加し、1 件投稿する
+async fn setup(server: &ChatServer) -> (TestClient, TestClient, String) {
+    let [mut alice, mut bob] = join_all(server, "rust", ["alice", "bob"]).await;
+    alice.send(send_message("rust", "shipped!")).await;
+    let message_id = last_message_id(&bob.drain());
+    alice.drain();
+    (alice, bob, message_id)
+}
+
+#[tokio::test]
+async fn reactions_are_aggregated_and_broadcast_to_the_room() {
+    let server = ChatServer::new();
+    let (mut alice, mut bob, message_id) = setup(&server).await;
+
+    bob.send(react(&message_id, "🎉")).await;
+    alice.send(react(&message_id, "🎉")).await;
+    bob.send(react(&message_id, "👍")).await;
+    bob.drain();
+    match alice.drain().last() {
+        Some(ServerMessage::ReactionsUpdated { room_name, message_id: id, reactions }) => {
+            assert_eq!((room_name.as_str(), id), ("rust", &message_id));
+            assert_eq!(summary(reactions), vec![("🎉", 2, vec!["bob", "alice"]), ("👍", 1, vec!["bob"])]);
+        }
+        other => panic!("expected ReactionsUpdated, got {:?}", other),
+    }
+
+    // 最後の 1 人が外すと絵文字ごと消える
+    bob.send(unreact(&message_id, "👍")).await;
+    assert_eq!(summary(&last_reactions(&alice.drain())), vec![("🎉", 2, vec!["bob", "alice"])]);
+}
+
+#[tokio::test]
+async fn repeated_reactions_and_missing_unreacts_are_ignored() {
+    let server = ChatServer::new();
+    let (mut alice, mut bob, message_id) = setup(&server).await;
+    bob.send(react(&message_id, "🎉")).await;
+    alice.drain();
+    bob.drain();
+
+    bob.send(react(&message_id, "🎉")).await;
+    bob.send(unreact(&message_id, "👍")).await;
+    alice.send(unreact(&message_id, "🎉")).await;
+    assert!(alice.drain().is_empty());
+    assert!(bob.drain().is_empty());
+}
+
+#[tokio::test]
+async fn reactions_follow_nick_changes() {
+    let server = ChatServer::new();
+    let (mut alice, mut bob, message_id) = setup(&server).await;
+    alice.send(react(&message_id, "👍")).await;
+    alice.send(ClientMessage::ChangeNick { username: "alicia".to_string() }).await;
+    bob.drain();
+
+    // 名前を変えても同じユーザーのリアクションとして扱い、今の名前で表す
+    alice.send(react(&message_id, "👍")).await;
+    assert!(bob.drain().is_empty());
+    bob.send(react(&message_id, "👍")).await;
+    assert_eq!(summary(&last_reactions(&alice.drain())), vec![("👍", 2, vec!["alicia", "bob"])]);
+
+    alice.send(unreact(&message_id, "👍")).await;
+    assert_eq!(summary(&last_reactions(&bob.drain())), vec![("👍", 1, vec!["bob"])]);
+}
+
+#[tokio::test]
+async fn reactions_are_included_in_history() {
+    let server = ChatServer::new();
+    let (mut alice, mut bob, message_id) = setup(&server).await;
+    bob.send(react(&message_id, "🎉")).await;
+    alice.send(send_message("rust", "no reactions here")).await;
+
+    let mut carol = TestClient::login(&server, "carol").await;
+    carol.send(join_room("rust")).await;
+    match carol.drain().last() {
+        Some(ServerMessage::History { messages, .. }) => {
+            assert_eq!(summary(&messages[0].reactions), vec![("🎉", 1, vec!["bob"])]);
+            assert!(messages[1].reactions.is_empty());
+        }
+        other => panic!("expected History, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn invalid_reactions_are_refused() {
+    let server = ChatServer::new();
+    let (mut alice, mut bob, message_id) = setup(&server).await;
+
+    bob.send(react("no-such-message", "🎉")).await;
+    assert_eq!(error_message(&bob.drain()), Some("Message not found"));
+    for emoji in ["", "thumbs up", "🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉🎉"] {
+        bob.send(react(&message_id, emoji)).await;
+        assert_eq!(error_message(&bob.drain()), Some("Invalid emoji"));
+    }
+
+    alice.send(ClientMessage::Mute { room_name: "rust".to_string(), username: "bob".to_string() }).await;
+    bob.drain();
+    bob.send(react(&message_id, "🎉")).await;
+    assert_eq!(error_message(&bob.drain()), Some("You are muted in this room"));
+
+    alice.send(ClientMessage::DeleteMessage { room_name: "rust".to_string(), message_id: message_id.clone() }).await;
+    alice.drain();
+    alice.send(react(&message_id, "🎉")).await;
+    assert_eq!(error_message(&alice.drain()), Some("Message not found"));
+}
+
+#[tokio::test]
+async fn messages_have_a_limited_number_of_distinct_reactions() {
+    let server = ChatServer::new();
+    let (mut alice, mut bob, message_id) = setup(&server).await;
+    let emojis: Vec<String> = (0..chat_core::room::MAX_REACTIONS_PER_MESSAGE).map(|i| char::from_u32(0x1F600 + i as u32).unwrap().to_string()).collect();
+    for emoji in &emojis {
+        bob.send(react(&message_id, emoji)).await;
+    }
+    alice.drain();
+    bob.drain();
+
+    alice.send(react(&message_id, "🎉")).await;
+    assert_eq!(error_message(&alice.drain()), Some("Too many different reactions on this message"));
+    // 既にある絵文字には追加できる
+    alice.send(react(&message_id, &emojis[0])).await;
+    assert_eq!(last_reactions(&alice.drain())[0].count, 2);
+}
+
+#[tokio::test]
+async fn reaction_updates_only_reach_sessions_that_negotiated_them() {
+    let server = ChatServer::new();
+    let (mut alice, mut bob, message_id) = setup(&server).await;
+    let mut carol = join_with(&server, "rust", "carol", vec![Capability::History]).await;
+    alice.drain();
+    bob.drain();
+
+    bob.send(react(&message_id, "🎉")).await;
+    assert_eq!(summary(&last_reactions(&alice.drain())), vec![("🎉", 1, vec!["bob"])]);
+    assert!(carol.drain().is_empty());
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{create_room, join_all, join_room, join_with, last_message_id, last_new_message, send_message, server_without_resume, TestClient};
+use chat_protocol::{Capability, ClientMessage, ServerMessage};
+use chat_core::server::ChatServer;
+
+fn mark_read(seq: u64) -> ClientMessage {
+    ClientMessage::MarkRead { room_name: "rust".to_string(), seq }
+}
+
+async fn unread(client: &mut TestClient) -> BTreeMap<String, u64> {
+    client.send(ClientMessage::ListRooms).await;
+    match client.drain().last() {
+        Some(ServerMessage::RoomList { unread, .. }) => unread.clone(),
+        other => panic!("expected RoomList, got {:?}", other),
+    }
+}
+
+// alice が投稿し、受け取った NewMessage の通し番号を返す
+async fn post(alice: &mut TestClient, content: &str) -> u64 {
+    alice.send(send_message("rust", content)).await;
+    last_new_message(&alice.drain()).1
+}
+
+#[tokio::test]
+async fn unread_counts_follow_the_read_marker() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+    post(&mut alice, "one").await;
+    let second = post(&mut alice, "two").await;
+    post(&mut alice, "three").await;
+
+    assert_eq!(unread(&mut bob).await, BTreeMap::from([("general".to_string(), 0), ("rust".to_string(), 3)]));
+    // 自分の投稿は未読に数えない
+    assert_eq!(unread(&mut alice).await["rust"], 0);
+
+    bob.send(mark_read(second)).await;
+    assert_eq!(unread(&mut bob).await["rust"], 1);
+}
+
+#[tokio::test]
+async fn history_from_before_joining_counts_as_read() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    alice.send(create_room("rust")).await;
+    alice.send(join_room("rust")).await;
+    post(&mut alice, "before bob").await;
+
+    let mut bob = TestClient::login(&server, "bob").await;
+    bob.send(join_room("rust")).await;
+    assert_eq!(unread(&mut bob).await["rust"], 0);
+
+    post(&mut alice, "after bob").await;
+    assert_eq!(unread(&mut bob).await["rust"], 1);
+}
+
+#[tokio::test]
+async fn read_markers_only_move_forward() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+    let first = post(&mut alice, "one").await;
+    let second = post(&mut alice, "two").await;
+
+    // まだない番号は最新に丸められる
+    bob.send(mark_read(second + 100)).await;
+    assert!(matches!(alice.drain().as_slice(), [ServerMessage::ReadBy { seq, .. }] if *seq == second));
+
+    bob.send(mark_read(first)).await;
+    assert!(alice.drain().is_empty());
+    assert_eq!(unread(&mut bob).await["rust"], 0);
+}
+
+#[tokio::test]
+async fn read_receipts_go_to_the_other_members() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+    let seq = post(&mut alice, "one").await;
+    bob.drain();
+
+    bob.send(mark_read(seq)).await;
+    match alice.drain().as_slice() {
+        [ServerMessage::ReadBy { room_name, username, seq: read }] => {
+            assert_eq!((room_name.as_str(), username.as_str(), *read), ("rust", "bob", seq));
+        }
+        other => panic!("expected ReadBy, got {:?}", other),
+    }
+    assert!(bob.drain().is_empty());
+}
+
+#[tokio::test]
+async fn read_receipts_only_reach_sessions_that_negotiated_them() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+    let mut carol = join_with(&server, "rust", "carol", vec![Capability::History]).await;
+    let seq = post(&mut alice, "one").await;
+    bob.drain();
+    carol.drain();
+
+    bob.send(mark_read(seq)).await;
+    assert!(matches!(alice.drain().as_slice(), [ServerMessage::ReadBy { .. }]));
+    assert!(carol.drain().is_empty());
+}
+
+#[tokio::test]
+async fn deleted_messages_are_not_unread() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+    post(&mut alice, "oops").await;
+    let message_id = last_message_id(&bob.drain());
+
+    alice.send(ClientMessage::DeleteMessage { room_name: "rust".to_string(), message_id }).await;
+    assert_eq!(unread(&mut bob).await["rust"], 0);
+}
+
+#[tokio::test]
+async fn own_messages_stay_read_after_a_nick_change() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+    post(&mut alice, "one").await;
+    post(&mut alice, "two").await;
+    bob.drain();
+
+    alice.send(ClientMessage::ChangeNick { username: "alicia".to_string() }).await;
+    assert_eq!(unread(&mut alice).await["rust"], 0);
+    assert_eq!(unread(&mut bob).await["rust"], 2);
+}
+
+#[tokio::test]
+async fn a_new_guest_does_not_inherit_the_read_marker_of_the_same_name() {
+    let server = server_without_resume();
+    let [mut alice, mut carol] = join_all(&server, "rust", ["alice", "carol"]).await;
+    let first = post(&mut alice, "one").await;
+    carol.send(mark_read(first)).await;
+    carol.close().await;
+    post(&mut alice, "two").await;
+    post(&mut alice, "three").await;
+
+    // 参加する前のメッセージは既読として始まる
+    let mut carol = TestClient::login(&server, "carol").await;
+    carol.send(join_room("rust")).await;
+    assert_eq!(unread(&mut carol).await["rust"], 0);
+}
+
+#[tokio::test]
+async fn marking_a_room_read_requires_membership() {
+    let server = ChatServer::new();
+    let mut bob = TestClient::login(&server, "bob").await;
+    bob.drain();
+
+    bob.send(mark_read(0)).await;
+    assert!(matches!(bob.drain().as_slice(), [ServerMessage::Error { .. }]));
+}
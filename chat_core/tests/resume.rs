@@ -0,0 +1,180 @@
+mod common;
+
+use std::time::Duration;
+
+use chrono::TimeDelta;
+use common::{create_room, join_room, login_with_password, register, send_message, server_without_resume, TestClient};
+use chat_protocol::{ClientMessage, ErrorCode, SequencedMessage, ServerMessage};
+use chat_core::config::ServerConfig;
+use chat_core::server::ChatServer;
+
+// ログイン直後のメッセージを読み、(トークン, 最後の通し番号) を返す
+fn session_of(client: &mut TestClient) -> (String, u64) {
+    let messages = client.drain_sequenced();
+    let token = messages.iter().find_map(|m| match &m.message {
+        ServerMessage::Welcome { session_token, .. } => session_token.clone(),
+        _ => None,
+    }).expect("no session token");
+    (token, messages.last().and_then(|m| m.session_seq).unwrap())
+}
+
+fn resume(token: &str, last_seen_seq: u64) -> ClientMessage {
+    ClientMessage::Resume { token: token.to_string(), last_seen_seq }
+}
+
+fn error_code(messages: &[ServerMessage]) -> Option<ErrorCode> {
+    match messages {
+        [ServerMessage::Error { code, .. }] => *code,
+        other => panic!("expected a single Error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn resume_restores_rooms_and_replays_missed_messages() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    alice.send(create_room("rust")).await;
+    alice.send(join_room("rust")).await;
+    let (token, last_seen_seq) = session_of(&mut alice);
+    let mut bob = TestClient::login(&server, "bob").await;
+    bob.send(join_room("rust")).await;
+    alice.close().await;
+
+    // 切断中の発言と DM は溜めておかれる
+    bob.send(send_message("rust", "missed")).await;
+    bob.send(ClientMessage::DirectMessage { to: "alice".to_string(), content: "psst".to_string() }).await;
+    bob.drain();
+
+    let mut alice = TestClient::connect(&server);
+    alice.send(resume(&token, last_seen_seq)).await;
+    let messages = alice.drain_sequenced();
+    match messages.first() {
+        Some(SequencedMessage { session_seq: None, message: ServerMessage::Resumed { username, rooms, .. } }) => {
+            assert_eq!(username, "alice");
+            assert_eq!(rooms, &vec!["general".to_string(), "rust".to_string()]);
+        }
+        other => panic!("expected Resumed, got {:?}", other),
+    }
+
+    // 番号は途切れずに続く
+    let replayed = &messages[1..];
+    let seqs: Vec<u64> = replayed.iter().map(|m| m.session_seq.unwrap()).collect();
+    assert_eq!(seqs, (last_seen_seq + 1..=last_seen_seq + replayed.len() as u64).collect::<Vec<_>>());
+    assert!(replayed.iter().any(|m| matches!(&m.message, ServerMessage::NewMessage { content, .. } if content == "missed")));
+    assert!(matches!(&replayed.last().unwrap().message, ServerMessage::DirectMessage { content, .. } if content == "psst"));
+
+    // 再開後はそのまま届き、bob には退出も参加も見えていない
+    bob.send(send_message("rust", "welcome back")).await;
+    assert_eq!(alice.drain_sequenced().last().and_then(|m| m.session_seq), Some(seqs.last().unwrap() + 1));
+    assert!(bob.drain().iter().all(|m| !matches!(m, ServerMessage::UserLeft { .. } | ServerMessage::UserJoined { .. })));
+}
+
+#[tokio::test]
+async fn resume_resends_messages_the_client_had_not_seen_before_the_drop() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let (token, last_seen_seq) = session_of(&mut alice);
+    let mut bob = TestClient::login(&server, "bob").await;
+    bob.send(send_message("general", "lost in transit")).await;
+    alice.close().await;
+
+    // 接続が切れる直前に送られたものも、受け取った番号より後なら送り直す
+    let mut alice = TestClient::connect(&server);
+    alice.send(resume(&token, last_seen_seq)).await;
+    let replayed = alice.drain();
+    assert!(matches!(&replayed[1], ServerMessage::UserJoined { username, .. } if username == "bob"));
+    assert!(matches!(&replayed[2], ServerMessage::NewMessage { content, .. } if content == "lost in transit"));
+}
+
+#[tokio::test]
+async fn sessions_expire_after_the_grace_period() {
+    let config = ServerConfig { resume_grace: TimeDelta::milliseconds(50), ..Default::default() };
+    let server = ChatServer::with_config(config);
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    let (token, last_seen_seq) = session_of(&mut bob);
+    alice.drain();
+
+    bob.close().await;
+    assert!(alice.drain().is_empty());
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(matches!(alice.drain().as_slice(), [ServerMessage::UserLeft { username, .. }] if username == "bob"));
+
+    let mut bob = TestClient::connect(&server);
+    bob.send(resume(&token, last_seen_seq)).await;
+    assert_eq!(error_code(&bob.drain()), Some(ErrorCode::InvalidSession));
+}
+
+#[tokio::test]
+async fn a_stale_connection_closing_late_does_not_detach_the_resumed_one() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let (token, last_seen_seq) = session_of(&mut alice);
+
+    // 古い接続が切断を検知する前に再開する
+    let mut resumed = TestClient::connect(&server);
+    resumed.send(resume(&token, last_seen_seq)).await;
+    resumed.drain();
+    alice.close().await;
+
+    let mut bob = TestClient::login(&server, "bob").await;
+    bob.send(send_message("general", "hi alice")).await;
+    assert!(resumed.drain().iter().any(|m| matches!(m, ServerMessage::NewMessage { content, .. } if content == "hi alice")));
+}
+
+#[tokio::test]
+async fn unknown_tokens_and_logged_in_sessions_cannot_resume() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let (token, last_seen_seq) = session_of(&mut alice);
+
+    alice.send(resume(&token, last_seen_seq)).await;
+    assert_eq!(error_code(&alice.drain()), Some(ErrorCode::AlreadyLoggedIn));
+
+    let mut client = TestClient::connect(&server);
+    client.send(resume("no-such-token", 0)).await;
+    assert_eq!(error_code(&client.drain()), Some(ErrorCode::InvalidSession));
+}
+
+#[tokio::test]
+async fn resuming_past_the_last_sent_message_is_refused() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let (token, last_seen_seq) = session_of(&mut alice);
+    alice.close().await;
+
+    for bogus in [last_seen_seq + 1, u64::MAX] {
+        let mut client = TestClient::connect(&server);
+        client.send(resume(&token, bogus)).await;
+        assert_eq!(error_code(&client.drain()), Some(ErrorCode::InvalidSession));
+    }
+
+    // 断られてもセッションは残っていて、正しい番号なら再開できる
+    let mut alice = TestClient::connect(&server);
+    alice.send(resume(&token, last_seen_seq)).await;
+    assert!(matches!(alice.drain().first(), Some(ServerMessage::Resumed { .. })));
+}
+
+#[tokio::test]
+async fn logging_in_again_replaces_a_detached_account_session() {
+    let server = ChatServer::new();
+    let mut alice = register(&server, "alice", "correct horse").await;
+    let (token, last_seen_seq) = session_of(&mut alice);
+    alice.close().await;
+
+    let mut again = TestClient::connect(&server);
+    again.send(login_with_password("alice", "correct horse")).await;
+    assert!(again.drain().iter().any(|m| matches!(m, ServerMessage::Welcome { .. })));
+
+    let mut client = TestClient::connect(&server);
+    client.send(resume(&token, last_seen_seq)).await;
+    assert_eq!(error_code(&client.drain()), Some(ErrorCode::InvalidSession));
+}
+
+#[tokio::test]
+async fn servers_without_a_grace_period_hand_out_no_token() {
+    let server = server_without_resume();
+    let mut alice = TestClient::login(&server, "alice").await;
+    assert!(matches!(alice.drain().first(), Some(ServerMessage::Welcome { session_token: None, .. })));
+}
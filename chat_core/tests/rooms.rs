@@ -0,0 +1,121 @@
+mod common;
+
+use common::{create_room, join_room, send_message, server_without_resume, TestClient};
+use chat_protocol::{ClientMessage, ServerMessage};
+use chat_core::server::ChatServer;
+
+fn leave_room(room_name: &str) -> ClientMessage {
+    ClientMessage::LeaveRoom { room_name: room_name.to_string() }
+}
+
+#[tokio::test]
+async fn leaving_a_room_notifies_members_and_keeps_other_rooms() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+
+    alice.send(create_room("rust")).await;
+    alice.send(join_room("rust")).await;
+    bob.send(join_room("rust")).await;
+    alice.drain();
+    bob.drain();
+
+    bob.send(leave_room("rust")).await;
+
+    assert!(matches!(
+        alice.drain().as_slice(),
+        [ServerMessage::UserLeft { username, room_name }] if username == "bob" && room_name == "rust"
+    ));
+    assert!(matches!(bob.drain().as_slice(), [ServerMessage::LeftRoom { room_name }] if room_name == "rust"));
+
+    // general には参加したまま
+    alice.send(send_message("general", "still there?")).await;
+    assert!(matches!(bob.drain().as_slice(), [ServerMessage::NewMessage { room_name, .. }] if room_name == "general"));
+}
+
+#[tokio::test]
+async fn members_of_several_rooms_receive_each_room_separately() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    alice.send(create_room("rust")).await;
+    alice.send(join_room("rust")).await;
+    alice.drain();
+    bob.drain();
+
+    alice.send(send_message("rust", "in rust")).await;
+    alice.send(send_message("general", "in general")).await;
+
+    let rooms: Vec<String> = alice.drain().into_iter().filter_map(|m| match m {
+        ServerMessage::NewMessage { room_name, .. } => Some(room_name),
+        _ => None,
+    }).collect();
+    assert_eq!(rooms, vec!["rust", "general"]);
+    assert!(matches!(bob.drain().as_slice(), [ServerMessage::NewMessage { content, .. }] if content == "in general"));
+
+    // 参加していないルームには送れない
+    bob.send(send_message("rust", "let me in")).await;
+    assert!(matches!(bob.drain().as_slice(), [ServerMessage::Error { message, .. }] if message == "Not in room"));
+    assert!(alice.drain().is_empty());
+}
+
+#[tokio::test]
+async fn joining_a_room_twice_is_an_error() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    alice.drain();
+
+    alice.send(join_room("general")).await;
+    assert!(matches!(
+        alice.drain().as_slice(),
+        [ServerMessage::Error { message, .. }] if message == "Already in room"
+    ));
+}
+
+#[tokio::test]
+async fn disconnect_leaves_every_joined_room() {
+    let server = server_without_resume();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    alice.send(create_room("rust")).await;
+    alice.send(join_room("rust")).await;
+    bob.send(join_room("rust")).await;
+    alice.drain();
+
+    bob.close().await;
+
+    let mut rooms: Vec<String> = alice.drain().into_iter().filter_map(|m| match m {
+        ServerMessage::UserLeft { username, room_name } if username == "bob" => Some(room_name),
+        _ => None,
+    }).collect();
+    rooms.sort();
+    assert_eq!(rooms, vec!["general", "rust"]);
+}
+
+#[tokio::test]
+async fn leaving_general_leaves_the_user_roomless() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    alice.drain();
+    bob.drain();
+
+    bob.send(leave_room("general")).await;
+    assert!(matches!(bob.drain().as_slice(), [ServerMessage::LeftRoom { .. }]));
+
+    alice.send(send_message("general", "anyone?")).await;
+    assert!(bob.drain().is_empty());
+}
+
+#[tokio::test]
+async fn leaving_a_room_the_user_is_not_in_is_an_error() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    alice.drain();
+
+    alice.send(leave_room("rust")).await;
+    assert!(matches!(
+        alice.drain().as_slice(),
+        [ServerMessage::Error { message, .. }] if message == "Not in room"
+    ));
+}
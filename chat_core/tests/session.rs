@@ -0,0 +1,41 @@
+mod common;
+
+use common::TestClient;
+use chat_protocol::{Capability, ClientMessage, ServerMessage, PROTOCOL_VERSION};
+use chat_core::server::ChatServer;
+use chat_core::session::Flow;
+
+#[tokio::test]
+async fn messages_before_login_are_ignored() {
+    let server = ChatServer::new();
+    let mut client = TestClient::connect(&server);
+
+    assert_eq!(client.send(ClientMessage::ListRooms).await, Flow::Continue);
+    assert!(client.drain().is_empty());
+}
+
+#[tokio::test]
+async fn second_login_is_rejected() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    alice.drain();
+
+    alice.send(ClientMessage::Login { username: "mallory".to_string(), password: None }).await;
+    assert!(matches!(
+        alice.drain().as_slice(),
+        [ServerMessage::Error { message, .. }] if message == "Already logged in"
+    ));
+}
+
+#[tokio::test]
+async fn incompatible_hello_asks_the_transport_to_close() {
+    let server = ChatServer::new();
+    let mut client = TestClient::connect(&server);
+
+    let flow = client.send(ClientMessage::Hello { protocol_version: 0, capabilities: vec![Capability::History] }).await;
+    assert_eq!(flow, Flow::Close);
+    assert!(matches!(client.drain().as_slice(), [ServerMessage::Error { .. }]));
+
+    let flow = client.send(ClientMessage::Hello { protocol_version: PROTOCOL_VERSION, capabilities: vec![] }).await;
+    assert_eq!(flow, Flow::Continue);
+}
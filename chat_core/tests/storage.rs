@@ -0,0 +1,298 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{TimeZone, Utc};
+use common::{create_room, error_message, join_room, login_with_password, register, send_message, TestClient};
+use chat_protocol::{Capability, ClientMessage, RoomVisibility, ServerMessage};
+use chat_core::config::ServerConfig;
+use chat_core::room::ChatMessage;
+use chat_core::server::ChatServer;
+use chat_core::storage::{Account, MemoryStorage, RoomRecord, SqliteStorage, Storage};
+
+// テストごとの SQLite ファイル (終わったら消す)
+struct TempDatabase(PathBuf);
+
+impl TempDatabase {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("chat-core-{}.db", uuid::Uuid::new_v4())))
+    }
+
+    fn open(&self) -> Box<dyn Storage> {
+        Box::new(SqliteStorage::open(&self.0).unwrap())
+    }
+}
+
+impl Drop for TempDatabase {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{}", self.0.display(), suffix));
+        }
+    }
+}
+
+fn history(messages: &[ServerMessage], room: &str) -> Vec<String> {
+    messages.iter().find_map(|m| match m {
+        ServerMessage::History { room_name, messages } if room_name == room => {
+            Some(messages.iter().map(|m| m.content.clone()).collect())
+        }
+        _ => None,
+    }).unwrap_or_else(|| panic!("no History for {} in {:?}", room, messages))
+}
+
+// 1 回目のサーバーでアカウントの alice がルームを作って発言し、書き込みが終わるまで待つ
+async fn populate(server: &ChatServer) {
+    let mut alice = register(server, "alice", "correct horse").await;
+    alice.send(create_room("rust")).await;
+    alice.send(ClientMessage::CreateRoom {
+        room_name: "secret".to_string(),
+        visibility: RoomVisibility::Password,
+        password: Some("hunter2".to_string()),
+        hidden: true,
+    }).await;
+    alice.send(join_room("rust")).await;
+    alice.send(send_message("rust", "first")).await;
+    alice.send(send_message("rust", "second")).await;
+    alice.send(send_message("general", "hello general")).await;
+    alice.send(ClientMessage::Mute { room_name: "rust".to_string(), username: "troll".to_string() }).await;
+    alice.close().await;
+    server.flush().await;
+}
+
+#[tokio::test]
+async fn rooms_and_history_survive_a_restart_with_sqlite() {
+    let database = TempDatabase::new();
+    populate(&ChatServer::with_storage(ServerConfig::default(), database.open())).await;
+
+    // 同じファイルから起動し直す
+    let server = ChatServer::with_storage(ServerConfig::default(), database.open());
+    let mut bob = TestClient::login_with(&server, "bob", vec![Capability::History]).await;
+    assert_eq!(history(&bob.drain(), "general"), vec!["hello general"]);
+
+    bob.send(ClientMessage::ListRooms).await;
+    match bob.drain().as_slice() {
+        [ServerMessage::RoomList { rooms, .. }] => {
+            let mut rooms = rooms.clone();
+            rooms.sort();
+            assert_eq!(rooms, vec!["general", "rust"]);
+        }
+        other => panic!("expected RoomList, got {:?}", other),
+    }
+
+    bob.send(join_room("rust")).await;
+    assert_eq!(history(&bob.drain(), "rust"), vec!["first", "second"]);
+
+    // 通し番号は続きから振られ、保存も続く
+    bob.send(send_message("rust", "third")).await;
+    bob.close().await;
+    server.flush().await;
+
+    let server = ChatServer::with_storage(ServerConfig::default(), database.open());
+    let mut carol = TestClient::login_with(&server, "carol", vec![Capability::History]).await;
+    carol.send(join_room("rust")).await;
+    assert_eq!(history(&carol.drain(), "rust"), vec!["first", "second", "third"]);
+
+    // パスワードとオーナーも残っている
+    carol.send(join_room("secret")).await;
+    assert_eq!(error_message(&carol.drain()), Some("Password required"));
+    let mut alice = TestClient::connect(&server);
+    alice.send(login_with_password("alice", "correct horse")).await;
+    alice.send(join_room("secret")).await;
+    assert!(alice.drain().iter().any(|m| matches!(m, ServerMessage::JoinedRoom { room_name } if room_name == "secret")));
+}
+
+#[tokio::test]
+async fn memory_storage_is_shared_between_clones() {
+    let storage = MemoryStorage::new(ServerConfig::default().retention.capacity);
+    populate(&ChatServer::with_storage(ServerConfig::default(), Box::new(storage.clone()))).await;
+    assert!(storage.last_seen("Alice").is_some());
+
+    let server = ChatServer::with_storage(ServerConfig::default(), Box::new(storage));
+    let mut bob = TestClient::login_with(&server, "bob", vec![Capability::History]).await;
+    bob.send(join_room("rust")).await;
+    assert_eq!(history(&bob.drain(), "rust"), vec!["first", "second"]);
+}
+
+#[test]
+fn room_records_round_trip_through_sqlite() {
+    let mut storage = SqliteStorage::open_in_memory().unwrap();
+    let record = RoomRecord {
+        name: "rust".to_string(),
+        owner: Some("alice".to_string()),
+        moderators: vec!["bob".to_string()],
+        bans: vec![("eve".to_string(), Some(Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap())), ("mallory".to_string(), None)],
+        muted: vec!["troll".to_string()],
+        visibility: RoomVisibility::InviteOnly,
+        password_hash: None,
+        hidden: true,
+        invited: vec!["carol".to_string()],
+    };
+    // 保存し直すと前の内容を置き換える
+    storage.save_room(&RoomRecord { muted: vec!["someone".to_string()], ..record.clone() }).unwrap();
+    storage.save_room(&record).unwrap();
+
+    let mut messages: Vec<ChatMessage> = (0..5).map(|seq| ChatMessage { seq, ..ChatMessage::new("alice".to_string(), "guest:1234".to_string(), "hi".to_string()) }).collect();
+    messages[4].reply_to = Some(messages[3].id.clone());
+    for message in &messages {
+        storage.append_message("rust", message).unwrap();
+    }
+
+    let rooms = storage.load_rooms(2).unwrap();
+    assert_eq!(rooms.len(), 1);
+    assert_eq!(rooms[0].record, record);
+    assert_eq!(rooms[0].messages.iter().map(|m| m.seq).collect::<Vec<_>>(), vec![3, 4]);
+    assert_eq!(rooms[0].messages[1].id, messages[4].id);
+    assert_eq!(rooms[0].messages[1].reply_to, messages[4].reply_to);
+    assert_eq!(rooms[0].messages[1].author, "guest:1234");
+}
+
+#[test]
+fn edited_and_deleted_messages_are_updated_in_place() {
+    let mut storages: Vec<Box<dyn Storage>> = vec![Box::new(SqliteStorage::open_in_memory().unwrap()), Box::new(MemoryStorage::new(10))];
+    for storage in &mut storages {
+        storage.save_room(&RoomRecord { name: "rust".to_string(), ..Default::default() }).unwrap();
+        let mut messages: Vec<ChatMessage> = (0..2).map(|seq| ChatMessage { seq, ..ChatMessage::new("alice".to_string(), "alice".to_string(), "hi".to_string()) }).collect();
+        for message in &messages {
+            storage.append_message("rust", message).unwrap();
+        }
+
+        messages[0].edit("hello".to_string(), Utc::now());
+        messages[1].delete();
+        for message in &messages {
+            storage.update_message("rust", message).unwrap();
+        }
+
+        let rooms = storage.load_rooms(10).unwrap();
+        let loaded = &rooms[0].messages;
+        assert_eq!(loaded[0].content, "hello");
+        assert!(loaded[0].edited_at.is_some());
+        assert!(loaded[1].deleted);
+        assert!(loaded[1].content.is_empty());
+    }
+}
+
+#[test]
+fn reactions_are_stored_in_order() {
+    let mut storages: Vec<Box<dyn Storage>> = vec![Box::new(SqliteStorage::open_in_memory().unwrap()), Box::new(MemoryStorage::new(10))];
+    for storage in &mut storages {
+        storage.save_room(&RoomRecord { name: "rust".to_string(), ..Default::default() }).unwrap();
+        let mut message = ChatMessage { seq: 0, ..ChatMessage::new("alice".to_string(), "alice".to_string(), "hi".to_string()) };
+        storage.append_message("rust", &message).unwrap();
+
+        message.react("🎉", "guest:id-3", "carol");
+        message.react("👍", "bob", "Bob");
+        message.react("🎉", "alice", "Alice");
+        storage.update_message("rust", &message).unwrap();
+        message.unreact("👍", "bob");
+        storage.update_message("rust", &message).unwrap();
+
+        let rooms = storage.load_rooms(10).unwrap();
+        assert_eq!(rooms[0].messages[0].reactions, message.reactions);
+        let users: Vec<&str> = rooms[0].messages[0].reactions[0].users.iter().map(|u| u.username.as_str()).collect();
+        assert_eq!(users, vec!["carol", "Alice"]);
+    }
+}
+
+#[test]
+fn read_markers_are_stored_per_room_and_user() {
+    let mut storages: Vec<Box<dyn Storage>> = vec![Box::new(SqliteStorage::open_in_memory().unwrap()), Box::new(MemoryStorage::new(10))];
+    for storage in &mut storages {
+        storage.save_room(&RoomRecord { name: "rust".to_string(), ..Default::default() }).unwrap();
+        storage.save_read_marker("rust", "bob", 3).unwrap();
+        storage.save_read_marker("rust", "alice", 1).unwrap();
+        storage.save_read_marker("rust", "bob", 5).unwrap();
+
+        let rooms = storage.load_rooms(10).unwrap();
+        assert_eq!(rooms[0].read_markers, vec![("alice".to_string(), 1), ("bob".to_string(), 5)]);
+    }
+}
+
+#[tokio::test]
+async fn read_markers_survive_a_restart() {
+    let storage = MemoryStorage::new(10);
+    let server = ChatServer::with_storage(ServerConfig::default(), Box::new(storage.clone()));
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = register(&server, "Bob", "correct horse").await;
+    alice.send(send_message("general", "one")).await;
+    alice.send(send_message("general", "two")).await;
+    bob.send(ClientMessage::MarkRead { room_name: "general".to_string(), seq: 0 }).await;
+    server.flush().await;
+
+    // 再起動後もアカウントの既読位置から数える (参加し直しても最新にはならない)
+    let server = ChatServer::with_storage(ServerConfig::default(), Box::new(storage));
+    let mut bob = TestClient::connect(&server);
+    bob.send(login_with_password("bob", "correct horse")).await;
+    bob.send(ClientMessage::ListRooms).await;
+    match bob.drain().last() {
+        Some(ServerMessage::RoomList { unread, .. }) => assert_eq!(unread["general"], 1),
+        other => panic!("expected RoomList, got {:?}", other),
+    }
+}
+
+#[test]
+fn accounts_are_unique_regardless_of_case() {
+    let mut storages: Vec<Box<dyn Storage>> = vec![Box::new(SqliteStorage::open_in_memory().unwrap()), Box::new(MemoryStorage::new(10))];
+    for storage in &mut storages {
+        let account = Account { username: "Alice".to_string(), password_hash: "hash".to_string() };
+        assert!(storage.create_account(&account, Utc::now()).unwrap());
+        assert!(!storage.create_account(&Account { username: "alice".to_string(), ..account.clone() }, Utc::now()).unwrap());
+
+        assert_eq!(storage.find_account("ALICE").unwrap(), Some(account));
+        assert_eq!(storage.find_account("bob").unwrap(), None);
+    }
+}
+
+#[test]
+fn account_names_fold_non_ascii_case() {
+    let mut storages: Vec<Box<dyn Storage>> = vec![Box::new(SqliteStorage::open_in_memory().unwrap()), Box::new(MemoryStorage::new(10))];
+    for storage in &mut storages {
+        let account = Account { username: "Émile".to_string(), password_hash: "hash".to_string() };
+        assert!(storage.create_account(&account, Utc::now()).unwrap());
+        assert!(!storage.create_account(&Account { username: "émile".to_string(), ..account.clone() }, Utc::now()).unwrap());
+
+        assert_eq!(storage.find_account("émile").unwrap(), Some(account.clone()));
+        assert_eq!(storage.find_account("ÉMILE").unwrap(), Some(account));
+    }
+}
+
+#[tokio::test]
+async fn guests_cannot_use_a_registered_name_in_another_case_with_sqlite() {
+    let database = TempDatabase::new();
+    let server = ChatServer::with_storage(ServerConfig::default(), database.open());
+    register(&server, "Émile", "correct horse").await.close().await;
+
+    let mut guest = TestClient::login(&server, "émile").await;
+    assert!(!guest.drain().iter().any(|m| matches!(m, ServerMessage::Welcome { .. })));
+}
+
+#[tokio::test]
+async fn accounts_survive_a_restart_with_sqlite() {
+    let database = TempDatabase::new();
+    let server = ChatServer::with_storage(ServerConfig::default(), database.open());
+    register(&server, "alice", "correct horse").await.close().await;
+    server.flush().await;
+
+    let server = ChatServer::with_storage(ServerConfig::default(), database.open());
+    let mut client = TestClient::connect(&server);
+    client.send(login_with_password("alice", "correct horse")).await;
+    assert!(client.drain().iter().any(|m| matches!(m, ServerMessage::Welcome { .. })));
+}
+
+#[test]
+fn migrations_run_once_and_refuse_newer_schemas() {
+    let database = TempDatabase::new();
+    let storage = SqliteStorage::open(&database.0).unwrap();
+    assert_eq!(storage.schema_version().unwrap(), SqliteStorage::latest_schema_version());
+    drop(storage);
+
+    // 2 回目は何もしない
+    let storage = SqliteStorage::open(&database.0).unwrap();
+    assert_eq!(storage.schema_version().unwrap(), SqliteStorage::latest_schema_version());
+    drop(storage);
+
+    let conn = rusqlite::Connection::open(&database.0).unwrap();
+    conn.pragma_update(None, "user_version", SqliteStorage::latest_schema_version() + 1).unwrap();
+    drop(conn);
+    assert!(SqliteStorage::open(&database.0).is_err());
+}
@@ -0,0 +1,88 @@
+use chrono::{TimeDelta, Utc};
+use chat_core::room::ChatMessage;
+use chat_core::store::{MessageStore, RetentionPolicy};
+
+fn message(content: &str, age_secs: i64) -> ChatMessage {
+    ChatMessage {
+        timestamp: Utc::now() - TimeDelta::seconds(age_secs),
+        ..ChatMessage::new("alice".to_string(), "alice".to_string(), content.to_string())
+    }
+}
+
+fn contents(messages: &[ChatMessage]) -> Vec<&str> {
+    messages.iter().map(|m| m.content.as_str()).collect()
+}
+
+#[test]
+fn drops_oldest_messages_beyond_capacity() {
+    let mut store = MessageStore::new(RetentionPolicy { capacity: 3, ttl: None });
+    for content in ["1", "2", "3", "4", "5"] {
+        store.push(message(content, 0));
+    }
+
+    assert_eq!(store.len(), 3);
+    assert_eq!(store.first_seq(), 2);
+    assert_eq!(contents(&store.last_n(10)), vec!["3", "4", "5"]);
+    assert_eq!(contents(&store.last_n(2)), vec!["4", "5"]);
+    assert!(store.last_n(0).is_empty());
+}
+
+#[test]
+fn assigns_sequential_numbers() {
+    let mut store = MessageStore::new(RetentionPolicy { capacity: 2, ttl: None });
+    let seqs: Vec<u64> = ["a", "b", "c"].iter().map(|c| store.push(message(c, 0))).collect();
+    assert_eq!(seqs, vec![0, 1, 2]);
+    assert_eq!(store.last_n(2).iter().map(|m| m.seq).collect::<Vec<_>>(), vec![1, 2]);
+
+    assert_eq!(contents(&store.since_seq(1)), vec!["c"]);
+    // 既に捨てられた番号を指定した場合は残っている分すべて
+    assert_eq!(contents(&store.since_seq(0)), vec!["b", "c"]);
+    assert!(store.since_seq(2).is_empty());
+    assert!(store.since_seq(100).is_empty());
+}
+
+#[test]
+fn queries_messages_since_timestamp() {
+    let mut store = MessageStore::new(RetentionPolicy::default());
+    store.push(message("old", 30));
+    store.push(message("mid", 20));
+    store.push(message("new", 10));
+
+    let since = Utc::now() - TimeDelta::seconds(25);
+    assert_eq!(contents(&store.since_timestamp(since)), vec!["mid", "new"]);
+}
+
+#[test]
+fn expires_messages_older_than_ttl() {
+    let mut store = MessageStore::new(RetentionPolicy {
+        capacity: 10,
+        ttl: Some(TimeDelta::seconds(60)),
+    });
+    store.push(message("stale", 120));
+    store.push(message("fresh", 5));
+
+    assert_eq!(store.len(), 1);
+    assert_eq!(contents(&store.last_n(10)), vec!["fresh"]);
+    assert_eq!(store.first_seq(), 1);
+}
+
+#[test]
+fn very_long_ttls_keep_everything() {
+    let mut store = MessageStore::new(RetentionPolicy {
+        capacity: 10,
+        ttl: Some(TimeDelta::MAX),
+    });
+    store.push(message("old", 120));
+    store.push(message("new", 5));
+
+    assert_eq!(contents(&store.last_n(10)), vec!["old", "new"]);
+    assert!(store.find(&store.last_n(1)[0].id).is_some());
+}
+
+#[test]
+fn zero_capacity_keeps_nothing() {
+    let mut store = MessageStore::new(RetentionPolicy { capacity: 0, ttl: None });
+    assert_eq!(store.push(message("gone", 0)), 0);
+    assert!(store.is_empty());
+    assert!(store.last_n(5).is_empty());
+}
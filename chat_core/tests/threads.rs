@@ -0,0 +1,120 @@
+mod common;
+
+use common::{join_all, join_room, last_message_id, last_new_message, send_message, TestClient};
+use chat_protocol::{ClientMessage, ServerMessage};
+use chat_core::config::ServerConfig;
+use chat_core::server::ChatServer;
+use chat_core::store::RetentionPolicy;
+
+fn reply(content: &str, reply_to: &str) -> ClientMessage {
+    ClientMessage::SendMessage { room_name: "rust".to_string(), content: content.to_string(), reply_to: Some(reply_to.to_string()) }
+}
+
+fn get_thread(message_id: &str) -> ClientMessage {
+    ClientMessage::GetThread { room_name: "rust".to_string(), message_id: message_id.to_string() }
+}
+
+// alice が rust を作成して bob と参加し、最初のメッセージを投稿する
+async fn setup(server: &ChatServer) -> (TestClient, TestClient, String) {
+    let [mut alice, mut bob] = join_all(server, "rust", ["alice", "bob"]).await;
+    alice.send(send_message("rust", "which editor?")).await;
+    let root_id = last_message_id(&bob.drain());
+    alice.drain();
+    (alice, bob, root_id)
+}
+
+#[tokio::test]
+async fn replies_to_replies_join_the_same_thread() {
+    let server = ChatServer::new();
+    let (mut alice, mut bob, root_id) = setup(&server).await;
+
+    bob.send(reply("vim", &root_id)).await;
+    let (reply_id, _, reply_to) = last_new_message(&alice.drain());
+    assert_eq!(reply_to.as_deref(), Some(root_id.as_str()));
+
+    alice.send(reply("really?", &reply_id)).await;
+    assert_eq!(last_new_message(&bob.drain()).2.as_deref(), Some(root_id.as_str()));
+}
+
+#[tokio::test]
+async fn get_thread_returns_the_root_and_its_replies() {
+    let server = ChatServer::new();
+    let (mut alice, mut bob, root_id) = setup(&server).await;
+    bob.send(reply("vim", &root_id)).await;
+    alice.send(send_message("rust", "unrelated")).await;
+    alice.send(reply("emacs", &root_id)).await;
+    let reply_id = last_message_id(&bob.drain());
+
+    // 返信を指定しても同じスレッドが返る
+    for message_id in [&root_id, &reply_id] {
+        bob.send(get_thread(message_id)).await;
+        match bob.drain().as_slice() {
+            [ServerMessage::Thread { room_name, root, replies }] => {
+                assert_eq!(room_name, "rust");
+                assert_eq!(root.as_ref().map(|m| m.id.as_str()), Some(root_id.as_str()));
+                let contents: Vec<&str> = replies.iter().map(|m| m.content.as_str()).collect();
+                assert_eq!(contents, vec!["vim", "emacs"]);
+            }
+            other => panic!("expected Thread, got {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn threads_whose_root_was_evicted_still_return_their_replies() {
+    let retention = RetentionPolicy { capacity: 3, ..Default::default() };
+    let server = ChatServer::with_config(ServerConfig { retention, ..Default::default() });
+    let (mut alice, mut bob, root_id) = setup(&server).await;
+    bob.send(reply("vim", &root_id)).await;
+    alice.send(reply("emacs", &root_id)).await;
+    let reply_id = last_message_id(&bob.drain());
+    alice.send(send_message("rust", "unrelated")).await;
+    bob.drain();
+
+    for message_id in [&root_id, &reply_id] {
+        bob.send(get_thread(message_id)).await;
+        match bob.drain().as_slice() {
+            [ServerMessage::Thread { root: None, replies, .. }] => {
+                let contents: Vec<&str> = replies.iter().map(|m| m.content.as_str()).collect();
+                assert_eq!(contents, vec!["vim", "emacs"]);
+            }
+            other => panic!("expected Thread without a root, got {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn replying_to_an_unknown_or_deleted_message_is_refused() {
+    let server = ChatServer::new();
+    let (mut alice, mut bob, root_id) = setup(&server).await;
+
+    bob.send(reply("hello?", "no-such-message")).await;
+    assert!(matches!(bob.drain().as_slice(), [ServerMessage::Error { message, .. }] if message == "Message not found"));
+
+    alice.send(ClientMessage::DeleteMessage { room_name: "rust".to_string(), message_id: root_id.clone() }).await;
+    alice.drain();
+    bob.drain();
+    bob.send(reply("too late", &root_id)).await;
+    assert!(matches!(bob.drain().as_slice(), [ServerMessage::Error { message, .. }] if message == "Message not found"));
+    assert!(alice.drain().is_empty());
+
+    bob.send(get_thread("no-such-message")).await;
+    assert!(matches!(bob.drain().as_slice(), [ServerMessage::Error { message, .. }] if message == "Message not found"));
+}
+
+#[tokio::test]
+async fn history_keeps_reply_context() {
+    let server = ChatServer::new();
+    let (_alice, mut bob, root_id) = setup(&server).await;
+    bob.send(reply("vim", &root_id)).await;
+
+    let mut carol = TestClient::login(&server, "carol").await;
+    carol.send(join_room("rust")).await;
+    match carol.drain().last() {
+        Some(ServerMessage::History { messages, .. }) => {
+            assert_eq!(messages[0].reply_to, None);
+            assert_eq!(messages[1].reply_to.as_deref(), Some(root_id.as_str()));
+        }
+        other => panic!("expected History, got {:?}", other),
+    }
+}
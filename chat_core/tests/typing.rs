@@ -0,0 +1,136 @@
+mod common;
+
+use std::time::Duration;
+
+use chrono::TimeDelta;
+use common::{join_all, join_with, send_message, TestClient};
+use chat_core::config::ServerConfig;
+use chat_core::server::ChatServer;
+use chat_protocol::{Capability, ClientMessage, ServerMessage};
+
+fn typing() -> ClientMessage {
+    ClientMessage::Typing { room_name: "rust".to_string() }
+}
+
+fn server_with_typing_timeout(millis: i64) -> ChatServer {
+    ChatServer::with_config(ServerConfig { typing_timeout: TimeDelta::milliseconds(millis), ..Default::default() })
+}
+
+// 止めた時計を進め、期限が切れたタイマーからルームのタスクへの通知が配送まで進むのを待つ
+// (ストレージのスレッドがあると時計は自動では進まないので、手で進める)
+async fn advance(millis: u64) {
+    tokio::time::advance(Duration::from_millis(millis)).await;
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+}
+
+fn is_typing(message: &ServerMessage, who: &str) -> bool {
+    matches!(message, ServerMessage::UserTyping { room_name, username } if room_name == "rust" && username == who)
+}
+
+fn is_stopped(message: &ServerMessage, who: &str) -> bool {
+    matches!(message, ServerMessage::UserStoppedTyping { room_name, username } if room_name == "rust" && username == who)
+}
+
+#[tokio::test]
+async fn typing_is_relayed_once_to_the_other_members() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+
+    alice.send(typing()).await;
+    alice.send(typing()).await;
+    let received = bob.drain();
+    assert_eq!(received.len(), 1);
+    assert!(is_typing(&received[0], "alice"));
+    assert!(alice.drain().is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn typing_stops_when_it_is_not_refreshed() {
+    let server = server_with_typing_timeout(100);
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+
+    alice.send(typing()).await;
+    advance(250).await;
+    let received = bob.drain();
+    assert_eq!(received.len(), 2);
+    assert!(is_typing(&received[0], "alice"));
+    assert!(is_stopped(&received[1], "alice"));
+
+    // 止まった後にまた入力すれば再び通知される
+    alice.send(typing()).await;
+    assert!(bob.drain().iter().any(|m| is_typing(m, "alice")));
+}
+
+#[tokio::test(start_paused = true)]
+async fn refreshing_keeps_the_user_typing() {
+    let server = server_with_typing_timeout(200);
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+
+    alice.send(typing()).await;
+    advance(120).await;
+    alice.send(typing()).await;
+    advance(120).await;
+    let received = bob.drain();
+    assert_eq!(received.len(), 1);
+    assert!(is_typing(&received[0], "alice"));
+
+    advance(250).await;
+    assert!(matches!(bob.drain().as_slice(), [message] if is_stopped(message, "alice")));
+}
+
+#[tokio::test(start_paused = true)]
+async fn sending_a_message_stops_typing() {
+    let server = server_with_typing_timeout(100);
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+
+    alice.send(typing()).await;
+    alice.send(send_message("rust", "done")).await;
+    let received = bob.drain();
+    assert_eq!(received.len(), 3);
+    assert!(is_typing(&received[0], "alice"));
+    assert!(is_stopped(&received[1], "alice"));
+    assert!(matches!(&received[2], ServerMessage::NewMessage { content, .. } if content == "done"));
+
+    // タイマーが切れても二度は送られない
+    advance(250).await;
+    assert!(bob.drain().is_empty());
+}
+
+#[tokio::test]
+async fn leaving_the_room_stops_typing() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+
+    alice.send(typing()).await;
+    alice.send(ClientMessage::LeaveRoom { room_name: "rust".to_string() }).await;
+    let received = bob.drain();
+    assert!(is_stopped(&received[1], "alice"));
+    assert!(matches!(&received[2], ServerMessage::UserLeft { username, .. } if username == "alice"));
+}
+
+#[tokio::test]
+async fn typing_requires_membership() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    alice.drain();
+
+    alice.send(ClientMessage::Typing { room_name: "elsewhere".to_string() }).await;
+    assert!(matches!(alice.drain().as_slice(), [ServerMessage::Error { .. }]));
+}
+
+#[tokio::test]
+async fn typing_only_reaches_sessions_that_negotiated_it() {
+    let server = ChatServer::new();
+    let [mut alice, mut bob] = join_all(&server, "rust", ["alice", "bob"]).await;
+    let mut carol = join_with(&server, "rust", "carol", vec![Capability::History]).await;
+    alice.drain();
+    bob.drain();
+
+    alice.send(typing()).await;
+    alice.send(send_message("rust", "hi")).await;
+    let received = bob.drain();
+    assert!(received.iter().any(|m| is_typing(m, "alice")) && received.iter().any(|m| is_stopped(m, "alice")));
+    assert!(carol.drain().iter().all(|m| !is_typing(m, "alice") && !is_stopped(m, "alice")));
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::{create_room, error_message, join_room, server_without_resume, TestClient};
+use chat_protocol::{ClientMessage, ServerMessage};
+use chat_core::server::ChatServer;
+use chat_core::username::validate_username;
+
+fn change_nick(username: &str) -> ClientMessage {
+    ClientMessage::ChangeNick { username: username.to_string() }
+}
+
+#[test]
+fn usernames_are_validated() {
+    assert!(validate_username("alice").is_ok());
+    assert!(validate_username("bob_2-x").is_ok());
+    assert!(validate_username("たろう").is_ok());
+
+    assert!(validate_username("").is_err());
+    assert!(validate_username(&"a".repeat(33)).is_err());
+    assert!(validate_username("alice smith").is_err());
+    assert!(validate_username("<script>").is_err());
+    assert!(validate_username("Admin").is_err());
+}
+
+#[tokio::test]
+async fn invalid_or_duplicate_login_is_rejected_and_can_be_retried() {
+    let server = ChatServer::new();
+    let _alice = TestClient::login(&server, "alice").await;
+    let mut client = TestClient::connect(&server);
+
+    client.send(ClientMessage::Login { username: "".to_string(), password: None }).await;
+    assert_eq!(error_message(&client.drain()), Some("Username must not be empty"));
+
+    client.send(ClientMessage::Login { username: "ALICE".to_string(), password: None }).await;
+    assert_eq!(error_message(&client.drain()), Some("Username already taken"));
+
+    client.send(ClientMessage::Login { username: "bob".to_string(), password: None }).await;
+    assert!(matches!(client.drain().first(), Some(ServerMessage::Welcome { .. })));
+}
+
+#[tokio::test]
+async fn username_is_free_again_after_disconnect() {
+    let server = server_without_resume();
+    let alice = TestClient::login(&server, "alice").await;
+    alice.close().await;
+
+    let mut again = TestClient::login(&server, "alice").await;
+    assert!(matches!(again.drain().first(), Some(ServerMessage::Welcome { .. })));
+}
+
+#[tokio::test]
+async fn change_nick_notifies_room_members_and_updates_user_list() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    let mut carol = TestClient::login(&server, "carol").await;
+    carol.send(create_room("rust")).await;
+    carol.send(join_room("rust")).await;
+    carol.send(ClientMessage::LeaveRoom { room_name: "general".to_string() }).await;
+    alice.drain();
+    bob.drain();
+    carol.drain();
+
+    alice.send(change_nick("alicia")).await;
+
+    for client in [&mut alice, &mut bob] {
+        assert!(matches!(
+            client.drain().as_slice(),
+            [ServerMessage::NickChanged { old_username, new_username }] if old_username == "alice" && new_username == "alicia"
+        ));
+    }
+    // 別のルームのユーザーには届かない
+    assert!(carol.drain().is_empty());
+
+    bob.send(ClientMessage::ListUsers { room_name: "general".to_string() }).await;
+    assert!(matches!(
+        bob.drain().as_slice(),
+        [ServerMessage::UserList { users, .. }] if users.contains(&"alicia".to_string()) && !users.contains(&"alice".to_string())
+    ));
+
+    alice.send(common::send_message("general", "hi")).await;
+    assert!(matches!(
+        bob.drain().as_slice(),
+        [ServerMessage::NewMessage { sender, .. }] if sender == "alicia"
+    ));
+}
+
+#[tokio::test]
+async fn change_nick_to_a_taken_or_invalid_name_is_rejected() {
+    let server = ChatServer::new();
+    let mut alice = TestClient::login(&server, "alice").await;
+    let mut bob = TestClient::login(&server, "bob").await;
+    alice.drain();
+    bob.drain();
+
+    alice.send(change_nick("Bob")).await;
+    assert_eq!(error_message(&alice.drain()), Some("Username already taken"));
+
+    alice.send(change_nick("system")).await;
+    assert_eq!(error_message(&alice.drain()), Some("Username is reserved"));
+    assert!(bob.drain().is_empty());
+
+    // 自分の名前の大文字小文字だけを変えるのは許す
+    alice.send(change_nick("Alice")).await;
+    assert!(matches!(alice.drain().as_slice(), [ServerMessage::NickChanged { .. }]));
+}
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+// 現在のプロトコルバージョン (2: SendMessage / ListUsers / UserList にルーム名を付けた)
+pub const PROTOCOL_VERSION: u32 = 2;
+// サーバーが受け入れる最も古いバージョン
+pub const MIN_PROTOCOL_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    History,
+    // 以下は合意したセッションにだけ届くイベント
+    Typing,         // UserTyping / UserStoppedTyping
+    Reactions,      // ReactionsUpdated
+    ReadReceipts,   // ReadBy
+    DirectMessages, // DirectMessage
+    // 新しいクライアントが送ってくる未知の機能名
+    #[serde(other)]
+    Unknown,
+}
+
+// クライアントの Hello を検査し、双方が対応している機能を返す
+pub fn negotiate(
+    protocol_version: u32,
+    requested: &[Capability],
+    supported: &[Capability],
+) -> Result<Vec<Capability>, String> {
+    if protocol_version < MIN_PROTOCOL_VERSION {
+        return Err(format!(
+            "Unsupported protocol version {} (server supports {} to {})",
+            protocol_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+        ));
+    }
+
+    let mut negotiated: Vec<Capability> = supported
+        .iter()
+        .copied()
+        .filter(|c| requested.contains(c))
+        .collect();
+    negotiated.dedup();
+    Ok(negotiated)
+}
@@ -0,0 +1,8 @@
+pub mod handshake;
+pub mod message;
+
+pub use handshake::{Capability, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+pub use message::{
+    ClientMessage, ErrorCode, HistoryMessage, Reaction, RoomVisibility, SequencedMessage,
+    ServerMessage,
+};
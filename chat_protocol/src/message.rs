@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::handshake::Capability;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+    Hello {
+        protocol_version: u32,
+        capabilities: Vec<Capability>,
+    },
+    // password を省略するとゲストとしてログインする
+    Login {
+        username: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        password: Option<String>,
+    },
+    // アカウントを作成し、そのままログインする
+    Register {
+        username: String,
+        password: String,
+    },
+    // 切断したセッションを Welcome の session_token で再開する
+    // (last_seen_seq は最後に受け取った SequencedMessage の session_seq。それより後を再送する)
+    Resume {
+        token: String,
+        last_seen_seq: u64,
+    },
+    // reply_to を付けると、そのメッセージのスレッドへの返信になる
+    SendMessage {
+        room_name: String,
+        content: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reply_to: Option<String>,
+    },
+    JoinRoom {
+        room_name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        password: Option<String>,
+    },
+    LeaveRoom {
+        room_name: String,
+    },
+    CreateRoom {
+        room_name: String,
+        #[serde(default, skip_serializing_if = "RoomVisibility::is_public")]
+        visibility: RoomVisibility,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        password: Option<String>, // visibility が password のときに使う
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        hidden: bool, // メンバー以外の RoomList に出さない
+    },
+    ListRooms,
+    ListUsers {
+        room_name: String,
+    },
+    DirectMessage {
+        to: String,
+        content: String,
+    },
+    ChangeNick {
+        username: String,
+    },
+    GrantModerator {
+        room_name: String,
+        username: String,
+    },
+    Kick {
+        room_name: String,
+        username: String,
+    },
+    Ban {
+        room_name: String,
+        username: String,
+        duration_secs: Option<u64>, // None は無期限
+    },
+    Unban {
+        room_name: String,
+        username: String,
+    },
+    Mute {
+        room_name: String,
+        username: String,
+    },
+    Unmute {
+        room_name: String,
+        username: String,
+    },
+    Invite {
+        room_name: String,
+        username: String,
+    },
+    // 投稿者本人かルームのモデレーターだけができる
+    EditMessage {
+        room_name: String,
+        message_id: String,
+        content: String,
+    },
+    DeleteMessage {
+        room_name: String,
+        message_id: String,
+    },
+    // スレッド内のどのメッセージを指定しても、元のメッセージと返信すべてが返る
+    GetThread {
+        room_name: String,
+        message_id: String,
+    },
+    // 同じ絵文字で二度リアクションしても一度として数える
+    React {
+        room_name: String,
+        message_id: String,
+        emoji: String,
+    },
+    Unreact {
+        room_name: String,
+        message_id: String,
+        emoji: String,
+    },
+    // 入力中の間は数秒おきに送り直す。途絶えるとサーバーが UserStoppedTyping を送る
+    Typing {
+        room_name: String,
+    },
+    // seq (NewMessage の seq) までを既読にする。既読位置は戻らない
+    MarkRead {
+        room_name: String,
+        seq: u64,
+    },
+}
+
+// ルームに参加できる条件
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomVisibility {
+    #[default]
+    Public,
+    Password,
+    InviteOnly,
+}
+
+impl RoomVisibility {
+    pub fn is_public(&self) -> bool {
+        *self == RoomVisibility::Public
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ServerMessage {
+    Hello {
+        protocol_version: u32,
+        capabilities: Vec<Capability>,
+    },
+    Welcome {
+        user_id: String,
+        // 再接続の猶予がないサーバーは送らない
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        session_token: Option<String>,
+    },
+    // 再開したセッションの状態。このあと取りこぼしたメッセージが再送される
+    Resumed {
+        user_id: String,
+        username: String,
+        rooms: Vec<String>,
+    },
+    UserJoined {
+        username: String,
+        room_name: String,
+    },
+    UserLeft {
+        username: String,
+        room_name: String,
+    },
+    // id はメッセージごとに一意、seq はルーム内で 0 から連続する
+    NewMessage {
+        id: String,
+        seq: u64,
+        sender: String,
+        content: String,
+        room_name: String,
+        timestamp: String,
+        // 返信先のスレッドの元のメッセージ
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reply_to: Option<String>,
+    },
+    RoomCreated {
+        room_name: String,
+    },
+    JoinedRoom {
+        room_name: String,
+    },
+    LeftRoom {
+        room_name: String,
+    },
+    RoomList {
+        rooms: Vec<String>,
+        // 参加中のルームごとの未読数 (自分の投稿と削除済みのメッセージは数えない)
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        unread: BTreeMap<String, u64>,
+    },
+    UserList {
+        room_name: String,
+        users: Vec<String>,
+    },
+    History {
+        room_name: String,
+        messages: Vec<HistoryMessage>,
+    },
+    DirectMessage {
+        from: String,
+        content: String,
+        timestamp: String,
+    },
+    NickChanged {
+        old_username: String,
+        new_username: String,
+    },
+    ModeratorGranted {
+        room_name: String,
+        username: String,
+        by: String,
+    },
+    UserKicked {
+        room_name: String,
+        username: String,
+        by: String,
+    },
+    UserBanned {
+        room_name: String,
+        username: String,
+        by: String,
+        expires_at: Option<String>,
+    },
+    UserUnbanned {
+        room_name: String,
+        username: String,
+        by: String,
+    },
+    UserMuted {
+        room_name: String,
+        username: String,
+        by: String,
+    },
+    UserUnmuted {
+        room_name: String,
+        username: String,
+        by: String,
+    },
+    // ルームのメンバーと招待された本人に届く
+    UserInvited {
+        room_name: String,
+        username: String,
+        by: String,
+    },
+    MessageEdited {
+        room_name: String,
+        message_id: String,
+        content: String,
+        edited_at: String,
+        by: String,
+    },
+    // 履歴には中身を消した削除済みのメッセージとして残る
+    MessageDeleted {
+        room_name: String,
+        message_id: String,
+        by: String,
+    },
+    // replies は古い順。履歴に残っている分だけが返る (元のメッセージが消えていれば root は省かれる)
+    Thread {
+        room_name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        root: Option<HistoryMessage>,
+        replies: Vec<HistoryMessage>,
+    },
+    // 本人以外のメンバーに届く
+    UserTyping {
+        room_name: String,
+        username: String,
+    },
+    // 投稿・退出したときと、Typing が途絶えたときに届く
+    UserStoppedTyping {
+        room_name: String,
+        username: String,
+    },
+    // メンバーの少ないルームでだけ、本人以外のメンバーに届く
+    ReadBy {
+        room_name: String,
+        username: String,
+        seq: u64,
+    },
+    // 変更後のリアクション全体。空なら最後のリアクションが外された
+    ReactionsUpdated {
+        room_name: String,
+        message_id: String,
+        reactions: Vec<Reaction>,
+    },
+    Error {
+        message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        code: Option<ErrorCode>,
+    },
+}
+
+// クライアントが処理を分けられるよう Error に付けるコード
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    AlreadyLoggedIn,
+    InvalidUsername,
+    UsernameTaken,
+    WeakPassword,
+    InvalidCredentials,
+    AccountInUse,
+    GuestsDisabled,
+    InvalidSession,
+    ServerUnavailable,
+}
+
+// 送信されるメッセージの外側。ログイン後のメッセージにはセッション内の通し番号 (1 から) が付く
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SequencedMessage {
+    // ルーム内の番号 (NewMessage の seq) とは別物
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_seq: Option<u64>,
+    #[serde(flatten)]
+    pub message: ServerMessage,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryMessage {
+    pub id: String,
+    pub seq: u64,
+    pub sender: String,
+    pub content: String,
+    pub timestamp: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool, // 削除済み (content は空)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reactions: Vec<Reaction>,
+}
+
+// 絵文字ごとの集計。users はリアクションした順
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reaction {
+    pub emoji: String,
+    pub count: u32,
+    pub users: Vec<String>,
+}
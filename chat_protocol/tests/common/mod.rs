@@ -0,0 +1,398 @@
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+
+use chat_protocol::{
+    Capability, ClientMessage, ErrorCode, HistoryMessage, Reaction, RoomVisibility, ServerMessage,
+};
+
+// 各バリアントの代表値 (ゴールデンファイルと 1 対 1 に対応する)
+pub fn client_messages() -> Vec<ClientMessage> {
+    vec![
+        ClientMessage::Hello {
+            protocol_version: 2,
+            capabilities: vec![Capability::History],
+        },
+        ClientMessage::Login {
+            username: "alice".to_string(),
+            password: Some("correct horse".to_string()),
+        },
+        ClientMessage::Register {
+            username: "alice".to_string(),
+            password: "correct horse".to_string(),
+        },
+        ClientMessage::Resume {
+            token: "9b2e7c1a-0000-4000-8000-000000000002".to_string(),
+            last_seen_seq: 42,
+        },
+        ClientMessage::SendMessage {
+            room_name: "general".to_string(),
+            content: "hello".to_string(),
+            reply_to: Some("2b7e9a44-1c3d-4f0e-8a6b-5d9c2e1f7a30".to_string()),
+        },
+        ClientMessage::JoinRoom {
+            room_name: "rust".to_string(),
+            password: Some("hunter2".to_string()),
+        },
+        ClientMessage::LeaveRoom {
+            room_name: "rust".to_string(),
+        },
+        ClientMessage::CreateRoom {
+            room_name: "rust".to_string(),
+            visibility: RoomVisibility::Password,
+            password: Some("hunter2".to_string()),
+            hidden: true,
+        },
+        ClientMessage::ListRooms,
+        ClientMessage::ListUsers {
+            room_name: "general".to_string(),
+        },
+        ClientMessage::DirectMessage {
+            to: "bob".to_string(),
+            content: "hi bob".to_string(),
+        },
+        ClientMessage::ChangeNick {
+            username: "alicia".to_string(),
+        },
+        ClientMessage::GrantModerator {
+            room_name: "rust".to_string(),
+            username: "bob".to_string(),
+        },
+        ClientMessage::Kick {
+            room_name: "rust".to_string(),
+            username: "bob".to_string(),
+        },
+        ClientMessage::Ban {
+            room_name: "rust".to_string(),
+            username: "bob".to_string(),
+            duration_secs: Some(600),
+        },
+        ClientMessage::Unban {
+            room_name: "rust".to_string(),
+            username: "bob".to_string(),
+        },
+        ClientMessage::Mute {
+            room_name: "rust".to_string(),
+            username: "bob".to_string(),
+        },
+        ClientMessage::Unmute {
+            room_name: "rust".to_string(),
+            username: "bob".to_string(),
+        },
+        ClientMessage::Invite {
+            room_name: "rust".to_string(),
+            username: "bob".to_string(),
+        },
+        ClientMessage::EditMessage {
+            room_name: "rust".to_string(),
+            message_id: "5f0c6b1e-8d2a-4c61-9a57-2f4e1b7d3c90".to_string(),
+            content: "hello, world".to_string(),
+        },
+        ClientMessage::DeleteMessage {
+            room_name: "rust".to_string(),
+            message_id: "5f0c6b1e-8d2a-4c61-9a57-2f4e1b7d3c90".to_string(),
+        },
+        ClientMessage::GetThread {
+            room_name: "general".to_string(),
+            message_id: "c81d4e2f-6a0b-4b97-b3e5-0f2a9d8c6e14".to_string(),
+        },
+        ClientMessage::React {
+            room_name: "general".to_string(),
+            message_id: "2b7e9a44-1c3d-4f0e-8a6b-5d9c2e1f7a30".to_string(),
+            emoji: "👍".to_string(),
+        },
+        ClientMessage::Unreact {
+            room_name: "general".to_string(),
+            message_id: "2b7e9a44-1c3d-4f0e-8a6b-5d9c2e1f7a30".to_string(),
+            emoji: "👍".to_string(),
+        },
+        ClientMessage::Typing {
+            room_name: "general".to_string(),
+        },
+        ClientMessage::MarkRead {
+            room_name: "general".to_string(),
+            seq: 41,
+        },
+    ]
+}
+
+pub fn server_messages() -> Vec<ServerMessage> {
+    vec![
+        ServerMessage::Hello {
+            protocol_version: 2,
+            capabilities: vec![
+                Capability::History,
+                Capability::Typing,
+                Capability::Reactions,
+                Capability::ReadReceipts,
+                Capability::DirectMessages,
+            ],
+        },
+        ServerMessage::Welcome {
+            user_id: "3f1c2d4e-0000-4000-8000-000000000001".to_string(),
+            session_token: Some("9b2e7c1a-0000-4000-8000-000000000002".to_string()),
+        },
+        ServerMessage::Resumed {
+            user_id: "3f1c2d4e-0000-4000-8000-000000000001".to_string(),
+            username: "alice".to_string(),
+            rooms: vec!["general".to_string(), "rust".to_string()],
+        },
+        ServerMessage::UserJoined {
+            username: "alice".to_string(),
+            room_name: "general".to_string(),
+        },
+        ServerMessage::UserLeft {
+            username: "alice".to_string(),
+            room_name: "general".to_string(),
+        },
+        ServerMessage::NewMessage {
+            id: "5f0c6b1e-8d2a-4c61-9a57-2f4e1b7d3c90".to_string(),
+            seq: 41,
+            sender: "alice".to_string(),
+            content: "hello".to_string(),
+            room_name: "general".to_string(),
+            timestamp: "2025-01-01T12:00:00+00:00".to_string(),
+            reply_to: None,
+        },
+        ServerMessage::RoomCreated {
+            room_name: "rust".to_string(),
+        },
+        ServerMessage::JoinedRoom {
+            room_name: "rust".to_string(),
+        },
+        ServerMessage::LeftRoom {
+            room_name: "rust".to_string(),
+        },
+        ServerMessage::RoomList {
+            rooms: vec!["general".to_string(), "rust".to_string()],
+            unread: BTreeMap::from([("general".to_string(), 0), ("rust".to_string(), 3)]),
+        },
+        ServerMessage::UserList {
+            room_name: "general".to_string(),
+            users: vec!["alice".to_string(), "bob".to_string()],
+        },
+        ServerMessage::History {
+            room_name: "general".to_string(),
+            messages: vec![
+                HistoryMessage {
+                    id: "2b7e9a44-1c3d-4f0e-8a6b-5d9c2e1f7a30".to_string(),
+                    seq: 7,
+                    sender: "alice".to_string(),
+                    content: "hello".to_string(),
+                    timestamp: "2025-01-01T12:00:00+00:00".to_string(),
+                    edited_at: None,
+                    deleted: false,
+                    reply_to: None,
+                    reactions: vec![
+                        Reaction {
+                            emoji: "👍".to_string(),
+                            count: 2,
+                            users: vec!["bob".to_string(), "carol".to_string()],
+                        },
+                        Reaction {
+                            emoji: "🎉".to_string(),
+                            count: 1,
+                            users: vec!["bob".to_string()],
+                        },
+                    ],
+                },
+                HistoryMessage {
+                    id: "c81d4e2f-6a0b-4b97-b3e5-0f2a9d8c6e14".to_string(),
+                    seq: 8,
+                    sender: "bob".to_string(),
+                    content: "hi!".to_string(),
+                    timestamp: "2025-01-01T12:00:05+00:00".to_string(),
+                    edited_at: Some("2025-01-01T12:00:30+00:00".to_string()),
+                    deleted: false,
+                    reply_to: Some("2b7e9a44-1c3d-4f0e-8a6b-5d9c2e1f7a30".to_string()),
+                    reactions: Vec::new(),
+                },
+                HistoryMessage {
+                    id: "e4a09f3b-7c25-4d18-9b6e-3a1f0c8d2b57".to_string(),
+                    seq: 9,
+                    sender: "carol".to_string(),
+                    content: String::new(),
+                    timestamp: "2025-01-01T12:00:10+00:00".to_string(),
+                    edited_at: None,
+                    deleted: true,
+                    reply_to: None,
+                    reactions: Vec::new(),
+                },
+            ],
+        },
+        ServerMessage::DirectMessage {
+            from: "alice".to_string(),
+            content: "hi bob".to_string(),
+            timestamp: "2025-01-01T12:00:00+00:00".to_string(),
+        },
+        ServerMessage::NickChanged {
+            old_username: "alice".to_string(),
+            new_username: "alicia".to_string(),
+        },
+        ServerMessage::ModeratorGranted {
+            room_name: "rust".to_string(),
+            username: "bob".to_string(),
+            by: "alice".to_string(),
+        },
+        ServerMessage::UserKicked {
+            room_name: "rust".to_string(),
+            username: "bob".to_string(),
+            by: "alice".to_string(),
+        },
+        ServerMessage::UserBanned {
+            room_name: "rust".to_string(),
+            username: "bob".to_string(),
+            by: "alice".to_string(),
+            expires_at: Some("2025-01-01T12:10:00+00:00".to_string()),
+        },
+        ServerMessage::UserUnbanned {
+            room_name: "rust".to_string(),
+            username: "bob".to_string(),
+            by: "alice".to_string(),
+        },
+        ServerMessage::UserMuted {
+            room_name: "rust".to_string(),
+            username: "bob".to_string(),
+            by: "alice".to_string(),
+        },
+        ServerMessage::UserUnmuted {
+            room_name: "rust".to_string(),
+            username: "bob".to_string(),
+            by: "alice".to_string(),
+        },
+        ServerMessage::UserInvited {
+            room_name: "rust".to_string(),
+            username: "bob".to_string(),
+            by: "alice".to_string(),
+        },
+        ServerMessage::MessageEdited {
+            room_name: "rust".to_string(),
+            message_id: "5f0c6b1e-8d2a-4c61-9a57-2f4e1b7d3c90".to_string(),
+            content: "hello, world".to_string(),
+            edited_at: "2025-01-01T12:01:00+00:00".to_string(),
+            by: "alice".to_string(),
+        },
+        ServerMessage::MessageDeleted {
+            room_name: "rust".to_string(),
+            message_id: "5f0c6b1e-8d2a-4c61-9a57-2f4e1b7d3c90".to_string(),
+            by: "bob".to_string(),
+        },
+        ServerMessage::Thread {
+            room_name: "general".to_string(),
+            root: Some(HistoryMessage {
+                id: "2b7e9a44-1c3d-4f0e-8a6b-5d9c2e1f7a30".to_string(),
+                seq: 7,
+                sender: "alice".to_string(),
+                content: "hello".to_string(),
+                timestamp: "2025-01-01T12:00:00+00:00".to_string(),
+                edited_at: None,
+                deleted: false,
+                reply_to: None,
+                reactions: Vec::new(),
+            }),
+            replies: vec![HistoryMessage {
+                id: "c81d4e2f-6a0b-4b97-b3e5-0f2a9d8c6e14".to_string(),
+                seq: 8,
+                sender: "bob".to_string(),
+                content: "hi!".to_string(),
+                timestamp: "2025-01-01T12:00:05+00:00".to_string(),
+                edited_at: None,
+                deleted: false,
+                reply_to: Some("2b7e9a44-1c3d-4f0e-8a6b-5d9c2e1f7a30".to_string()),
+                reactions: Vec::new(),
+            }],
+        },
+        ServerMessage::UserTyping {
+            room_name: "general".to_string(),
+            username: "alice".to_string(),
+        },
+        ServerMessage::UserStoppedTyping {
+            room_name: "general".to_string(),
+            username: "alice".to_string(),
+        },
+        ServerMessage::ReadBy {
+            room_name: "general".to_string(),
+            username: "bob".to_string(),
+            seq: 41,
+        },
+        ServerMessage::ReactionsUpdated {
+            room_name: "general".to_string(),
+            message_id: "2b7e9a44-1c3d-4f0e-8a6b-5d9c2e1f7a30".to_string(),
+            reactions: vec![Reaction {
+                emoji: "👍".to_string(),
+                count: 2,
+                users: vec!["bob".to_string(), "carol".to_string()],
+            }],
+        },
+        ServerMessage::Error {
+            message: "Invalid username or password".to_string(),
+            code: Some(ErrorCode::InvalidCredentials),
+        },
+    ]
+}
+
+// バリアントを追加するとここがコンパイルエラーになり、フィクスチャの追加を強制する
+pub fn client_fixture_name(message: &ClientMessage) -> &'static str {
+    match message {
+        ClientMessage::Hello { .. } => "hello",
+        ClientMessage::Login { .. } => "login",
+        ClientMessage::Register { .. } => "register",
+        ClientMessage::Resume { .. } => "resume",
+        ClientMessage::SendMessage { .. } => "send_message",
+        ClientMessage::JoinRoom { .. } => "join_room",
+        ClientMessage::LeaveRoom { .. } => "leave_room",
+        ClientMessage::CreateRoom { .. } => "create_room",
+        ClientMessage::ListRooms => "list_rooms",
+        ClientMessage::ListUsers { .. } => "list_users",
+        ClientMessage::DirectMessage { .. } => "direct_message",
+        ClientMessage::ChangeNick { .. } => "change_nick",
+        ClientMessage::GrantModerator { .. } => "grant_moderator",
+        ClientMessage::Kick { .. } => "kick",
+        ClientMessage::Ban { .. } => "ban",
+        ClientMessage::Unban { .. } => "unban",
+        ClientMessage::Mute { .. } => "mute",
+        ClientMessage::Unmute { .. } => "unmute",
+        ClientMessage::Invite { .. } => "invite",
+        ClientMessage::EditMessage { .. } => "edit_message",
+        ClientMessage::DeleteMessage { .. } => "delete_message",
+        ClientMessage::GetThread { .. } => "get_thread",
+        ClientMessage::React { .. } => "react",
+        ClientMessage::Unreact { .. } => "unreact",
+        ClientMessage::Typing { .. } => "typing",
+        ClientMessage::MarkRead { .. } => "mark_read",
+    }
+}
+
+pub fn server_fixture_name(message: &ServerMessage) -> &'static str {
+    match message {
+        ServerMessage::Hello { .. } => "hello",
+        ServerMessage::Welcome { .. } => "welcome",
+        ServerMessage::Resumed { .. } => "resumed",
+        ServerMessage::UserJoined { .. } => "user_joined",
+        ServerMessage::UserLeft { .. } => "user_left",
+        ServerMessage::NewMessage { .. } => "new_message",
+        ServerMessage::RoomCreated { .. } => "room_created",
+        ServerMessage::JoinedRoom { .. } => "joined_room",
+        ServerMessage::LeftRoom { .. } => "left_room",
+        ServerMessage::RoomList { .. } => "room_list",
+        ServerMessage::UserList { .. } => "user_list",
+        ServerMessage::History { .. } => "history",
+        ServerMessage::DirectMessage { .. } => "direct_message",
+        ServerMessage::NickChanged { .. } => "nick_changed",
+        ServerMessage::ModeratorGranted { .. } => "moderator_granted",
+        ServerMessage::UserKicked { .. } => "user_kicked",
+        ServerMessage::UserBanned { .. } => "user_banned",
+        ServerMessage::UserUnbanned { .. } => "user_unbanned",
+        ServerMessage::UserMuted { .. } => "user_muted",
+        ServerMessage::UserUnmuted { .. } => "user_unmuted",
+        ServerMessage::UserInvited { .. } => "user_invited",
+        ServerMessage::MessageEdited { .. } => "message_edited",
+        ServerMessage::MessageDeleted { .. } => "message_deleted",
+        ServerMessage::Thread { .. } => "thread",
+        ServerMessage::UserTyping { .. } => "user_typing",
+        ServerMessage::UserStoppedTyping { .. } => "user_stopped_typing",
+        ServerMessage::ReadBy { .. } => "read_by",
+        ServerMessage::ReactionsUpdated { .. } => "reactions_updated",
+        ServerMessage::Error { .. } => "error",
+    }
+}
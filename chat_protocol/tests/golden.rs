@@ -0,0 +1,84 @@
+mod common;
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chat_protocol::{ClientMessage, ServerMessage};
+use common::{client_fixture_name, client_messages, server_fixture_name, server_messages};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+fn fixtures_dir(kind: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(kind)
+}
+
+fn read_fixture(kind: &str, name: &str) -> serde_json::Value {
+    let path = fixtures_dir(kind).join(format!("{}.json", name));
+    let text = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("missing fixture {}: {}", path.display(), e));
+    serde_json::from_str(&text).unwrap()
+}
+
+fn fixture_names(kind: &str) -> BTreeSet<String> {
+    fs::read_dir(fixtures_dir(kind))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .map(|path| path.file_stem().unwrap().to_string_lossy().into_owned())
+        .collect()
+}
+
+fn assert_matches_fixture<T>(kind: &str, name: &str, message: &T)
+where
+    T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug,
+{
+    let fixture = read_fixture(kind, name);
+
+    // エンコード結果がフィクスチャと一致する
+    assert_eq!(
+        serde_json::to_value(message).unwrap(),
+        fixture,
+        "{}/{}.json no longer matches the encoded message",
+        kind,
+        name
+    );
+
+    // フィクスチャをデコードすると同じ値になる
+    let decoded: T = serde_json::from_value(fixture).unwrap();
+    assert_eq!(
+        &decoded, message,
+        "{}/{}.json decodes differently",
+        kind, name
+    );
+}
+
+#[test]
+fn client_messages_match_golden_fixtures() {
+    let messages = client_messages();
+    for message in &messages {
+        assert_matches_fixture::<ClientMessage>("client", client_fixture_name(message), message);
+    }
+
+    let covered: BTreeSet<String> = messages
+        .iter()
+        .map(|m| client_fixture_name(m).to_string())
+        .collect();
+    assert_eq!(covered, fixture_names("client"));
+}
+
+#[test]
+fn server_messages_match_golden_fixtures() {
+    let messages = server_messages();
+    for message in &messages {
+        assert_matches_fixture::<ServerMessage>("server", server_fixture_name(message), message);
+    }
+
+    let covered: BTreeSet<String> = messages
+        .iter()
+        .map(|m| server_fixture_name(m).to_string())
+        .collect();
+    assert_eq!(covered, fixture_names("server"));
+}
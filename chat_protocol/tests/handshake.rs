@@ -0,0 +1,58 @@
+use chat_protocol::handshake::negotiate;
+use chat_protocol::{Capability, ClientMessage, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+
+#[test]
+fn negotiates_common_capabilities() {
+    let negotiated = negotiate(
+        PROTOCOL_VERSION,
+        &[Capability::History, Capability::Unknown],
+        &[Capability::History],
+    )
+    .unwrap();
+    assert_eq!(negotiated, vec![Capability::History]);
+
+    let negotiated = negotiate(PROTOCOL_VERSION, &[], &[Capability::History]).unwrap();
+    assert!(negotiated.is_empty());
+}
+
+#[test]
+fn rejects_versions_older_than_minimum() {
+    let error = negotiate(MIN_PROTOCOL_VERSION - 1, &[], &[Capability::History]).unwrap_err();
+    assert!(error.contains("Unsupported protocol version"), "{}", error);
+}
+
+#[test]
+fn accepts_newer_clients() {
+    assert!(negotiate(PROTOCOL_VERSION + 1, &[], &[]).is_ok());
+}
+
+#[test]
+fn unknown_capabilities_do_not_break_decoding() {
+    let json = r#"{"type":"Hello","protocol_version":2,"capabilities":["history","hologram"]}"#;
+    let message: ClientMessage = serde_json::from_str(json).unwrap();
+    assert_eq!(
+        message,
+        ClientMessage::Hello {
+            protocol_version: 2,
+            capabilities: vec![Capability::History, Capability::Unknown],
+        }
+    );
+}
+
+#[test]
+fn capabilities_use_snake_case_names() {
+    let json = r#"{"type":"Hello","protocol_version":2,"capabilities":["typing","reactions","read_receipts","direct_messages"]}"#;
+    let message: ClientMessage = serde_json::from_str(json).unwrap();
+    assert_eq!(
+        message,
+        ClientMessage::Hello {
+            protocol_version: 2,
+            capabilities: vec![
+                Capability::Typing,
+                Capability::Reactions,
+                Capability::ReadReceipts,
+                Capability::DirectMessages,
+            ],
+        }
+    );
+}
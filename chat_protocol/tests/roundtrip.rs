@@ -0,0 +1,163 @@
+mod common;
+
+use chat_protocol::{ClientMessage, RoomVisibility, SequencedMessage, ServerMessage};
+use common::{client_messages, server_messages};
+
+#[test]
+fn client_messages_round_trip() {
+    for message in client_messages() {
+        let json = serde_json::to_string(&message).unwrap();
+        let decoded: ClientMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, message, "round trip of {}", json);
+    }
+}
+
+#[test]
+fn server_messages_round_trip() {
+    for message in server_messages() {
+        let json = serde_json::to_string(&message).unwrap();
+        let decoded: ServerMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, message, "round trip of {}", json);
+    }
+}
+
+#[test]
+fn messages_are_tagged_with_their_variant_name() {
+    let json = serde_json::to_value(ClientMessage::ListRooms).unwrap();
+    assert_eq!(json, serde_json::json!({ "type": "ListRooms" }));
+
+    let json = serde_json::to_value(ServerMessage::Welcome {
+        user_id: "u1".to_string(),
+        session_token: None,
+    })
+    .unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({ "type": "Welcome", "user_id": "u1" })
+    );
+}
+
+#[test]
+fn rejects_unknown_types_and_missing_fields() {
+    assert!(serde_json::from_str::<ClientMessage>(r#"{"type":"Shout","content":"hi"}"#).is_err());
+    assert!(serde_json::from_str::<ClientMessage>(r#"{"type":"JoinRoom"}"#).is_err());
+    assert!(serde_json::from_str::<ClientMessage>(r#"{"username":"alice"}"#).is_err());
+}
+
+#[test]
+fn room_options_default_to_a_public_room() {
+    let create: ClientMessage =
+        serde_json::from_str(r#"{"type":"CreateRoom","room_name":"rust"}"#).unwrap();
+    assert_eq!(
+        create,
+        ClientMessage::CreateRoom {
+            room_name: "rust".to_string(),
+            visibility: RoomVisibility::Public,
+            password: None,
+            hidden: false,
+        }
+    );
+
+    // 既定値は送らない (古いクライアントと同じ形になる)
+    assert_eq!(
+        serde_json::to_value(&create).unwrap(),
+        serde_json::json!({ "type": "CreateRoom", "room_name": "rust" })
+    );
+
+    let join: ClientMessage =
+        serde_json::from_str(r#"{"type":"JoinRoom","room_name":"rust"}"#).unwrap();
+    assert_eq!(
+        join,
+        ClientMessage::JoinRoom {
+            room_name: "rust".to_string(),
+            password: None,
+        }
+    );
+}
+
+#[test]
+fn guest_logins_and_plain_errors_omit_optional_fields() {
+    let login: ClientMessage =
+        serde_json::from_str(r#"{"type":"Login","username":"alice"}"#).unwrap();
+    assert_eq!(
+        login,
+        ClientMessage::Login {
+            username: "alice".to_string(),
+            password: None,
+        }
+    );
+
+    let error = ServerMessage::Error {
+        message: "Room not found".to_string(),
+        code: None,
+    };
+    assert_eq!(
+        serde_json::to_value(&error).unwrap(),
+        serde_json::json!({ "type": "Error", "message": "Room not found" })
+    );
+}
+
+#[test]
+fn messages_without_reply_to_are_not_replies() {
+    let send: ClientMessage =
+        serde_json::from_str(r#"{"type":"SendMessage","room_name":"general","content":"hello"}"#)
+            .unwrap();
+    assert_eq!(
+        send,
+        ClientMessage::SendMessage {
+            room_name: "general".to_string(),
+            content: "hello".to_string(),
+            reply_to: None,
+        }
+    );
+    assert_eq!(
+        serde_json::to_value(&send).unwrap(),
+        serde_json::json!({ "type": "SendMessage", "room_name": "general", "content": "hello" })
+    );
+}
+
+#[test]
+fn room_lists_without_unread_counts_still_parse() {
+    let list: ServerMessage =
+        serde_json::from_str(r#"{"type":"RoomList","rooms":["general"]}"#).unwrap();
+    assert_eq!(
+        list,
+        ServerMessage::RoomList {
+            rooms: vec!["general".to_string()],
+            unread: Default::default(),
+        }
+    );
+    assert_eq!(
+        serde_json::to_value(&list).unwrap(),
+        serde_json::json!({ "type": "RoomList", "rooms": ["general"] })
+    );
+}
+
+#[test]
+fn sequence_numbers_sit_next_to_the_message_fields() {
+    let message = SequencedMessage {
+        session_seq: Some(7),
+        message: ServerMessage::RoomCreated {
+            room_name: "rust".to_string(),
+        },
+    };
+    let json = serde_json::to_value(&message).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({ "session_seq": 7, "type": "RoomCreated", "room_name": "rust" })
+    );
+    assert_eq!(
+        serde_json::from_value::<SequencedMessage>(json.clone()).unwrap(),
+        message
+    );
+
+    // 番号を知らないクライアントはそのまま ServerMessage として読める
+    assert_eq!(
+        serde_json::from_value::<ServerMessage>(json).unwrap(),
+        message.message
+    );
+
+    let unnumbered: SequencedMessage =
+        serde_json::from_str(r#"{"type":"RoomCreated","room_name":"rust"}"#).unwrap();
+    assert_eq!(unnumbered.session_seq, None);
+}
@@ -1 +0,0 @@
-pub mod entity;
\ No newline at end of file
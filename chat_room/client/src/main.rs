@@ -1,35 +1,240 @@
-use client::entity::message::{ClientMessage, ServerMessage};
+use chat_protocol::{Capability, ClientMessage, ErrorCode, HistoryMessage, Reaction, RoomVisibility, ServerMessage, PROTOCOL_VERSION};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::io;
+use std::sync::{Arc, Mutex};
 use tokio::task;
 
+// 参加中のルームと、入力したメッセージの送り先 (受信タスクが更新する)
+#[derive(Debug, Default)]
+struct Rooms {
+    joined: Vec<String>,
+    active: Option<String>,
+    last_sent: HashMap<String, String>, // ルームごとに自分が最後に送ったメッセージの ID (/edit と /delete の対象)
+    seen: HashMap<String, BTreeMap<u64, String>>, // ルームごとに受け取ったメッセージの通し番号 -> ID (/reply /thread /react で番号を使う)
+}
+
+impl Rooms {
+    fn join(&mut self, room_name: String) {
+        if !self.joined.contains(&room_name) {
+            self.joined.push(room_name.clone());
+        }
+        self.active = Some(room_name);
+    }
+
+    fn leave(&mut self, room_name: &str) {
+        self.joined.retain(|r| r != room_name);
+        self.last_sent.remove(room_name);
+        self.seen.remove(room_name);
+        if self.active.as_deref() == Some(room_name) {
+            self.active = self.joined.first().cloned();
+        }
+    }
+
+    fn remember(&mut self, room_name: &str, seq: u64, id: String) {
+        self.seen.entry(room_name.to_string()).or_default().insert(seq, id);
+    }
+
+    // 受け取った最新のメッセージの通し番号
+    fn latest_seq(&self, room_name: &str) -> Option<u64> {
+        self.seen.get(room_name)?.keys().next_back().copied()
+    }
+
+    fn message_id(&self, room_name: &str, seq: u64) -> Option<String> {
+        self.seen.get(room_name)?.get(&seq).cloned()
+    }
+
+    fn message_seq(&self, room_name: &str, message_id: &str) -> Option<u64> {
+        self.seen.get(room_name)?.iter().find(|(_, id)| id.as_str() == message_id).map(|(seq, _)| *seq)
+    }
+
+    // 返信先を番号で示す (知らないメッセージなら番号なし)
+    fn reply_marker(&self, room_name: &str, reply_to: Option<&str>) -> String {
+        let Some(reply_to) = reply_to else {
+            return String::new();
+        };
+        match self.message_seq(room_name, reply_to) {
+            Some(seq) => format!(" (reply to #{})", seq),
+            None => " (reply)".to_string(),
+        }
+    }
+
+    // 履歴やスレッドの 1 行
+    fn history_line(&mut self, room_name: &str, message: HistoryMessage) -> String {
+        let reply = self.reply_marker(room_name, message.reply_to.as_deref());
+        self.remember(room_name, message.seq, message.id);
+        let content = match (message.deleted, message.edited_at.is_some()) {
+            (true, _) => "(deleted)".to_string(),
+            (false, true) => format!("{} (edited)", message.content),
+            (false, false) => message.content,
+        };
+        let reactions = if message.reactions.is_empty() { String::new() } else { format!(" [{}]", reaction_summary(&message.reactions)) };
+        format!("[#{} {}] {}: {}{}{}", message.seq, message.timestamp, message.sender, content, reply, reactions)
+    }
+}
+
+// "🎉 2 (bob, alice), 👍 1 (bob)" の形にまとめる
+fn reaction_summary(reactions: &[Reaction]) -> String {
+    let parts: Vec<String> = reactions.iter().map(|r| format!("{} {} ({})", r.emoji, r.count, r.users.join(", "))).collect();
+    parts.join(", ")
+}
+
+// モデレーション用のコマンド (/mod /kick /ban /unban /mute /unmute) を送り先のルーム宛てに解釈する
+fn moderation_command(input: &str, room_name: String) -> Option<ClientMessage> {
+    let (command, args) = input.split_once(' ')?;
+    let mut args = args.split_whitespace();
+    let username = args.next()?.to_string();
+
+    let message = match command {
+        "/mod" => ClientMessage::GrantModerator { room_name, username },
+        "/kick" => ClientMessage::Kick { room_name, username },
+        "/ban" => {
+            // /ban <user> [秒数]
+            let duration_secs = args.next().and_then(|secs| secs.parse().ok());
+            ClientMessage::Ban { room_name, username, duration_secs }
+        }
+        "/unban" => ClientMessage::Unban { room_name, username },
+        "/mute" => ClientMessage::Mute { room_name, username },
+        "/unmute" => ClientMessage::Unmute { room_name, username },
+        _ => return None,
+    };
+    Some(message)
+}
+
+// /create <room_name> [password <password> | invite] [hidden]
+fn create_command(args: &str) -> Option<ClientMessage> {
+    let mut args = args.split_whitespace();
+    let room_name = args.next()?.to_string();
+    let (mut visibility, mut password, mut hidden) = (RoomVisibility::Public, None, false);
+    while let Some(arg) = args.next() {
+        match arg {
+            "password" => {
+                visibility = RoomVisibility::Password;
+                password = Some(args.next()?.to_string());
+            }
+            "invite" => visibility = RoomVisibility::InviteOnly,
+            "hidden" => hidden = true,
+            _ => return None,
+        }
+    }
+    Some(ClientMessage::CreateRoom { room_name, visibility, password, hidden })
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let stream = TcpStream::connect("127.0.0.1:8080").await?;
+    // 接続先は第 1 引数で変更できる (既定は 127.0.0.1:8080)
+    let addr = env::args().nth(1).unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let stream = TcpStream::connect(&addr).await?;
     let (reader, mut writer) = stream.into_split();
-    let reader = BufReader::new(reader);
-
-    // ログイン
-    print!("Enter your username: ");
-    io::Write::flush(&mut io::stdout())?;
-    let mut username = String::new();
-    io::stdin().read_line(&mut username)?;
-    
-    let login_msg = ClientMessage::Login { username: username.trim().to_string() };
-    let json = serde_json::to_string(&login_msg)?;
+    let mut lines = BufReader::new(reader).lines();
+
+    // ハンドシェイク (入力中の表示と既読は端末では出さないので受け取らない)
+    let capabilities = vec![Capability::History, Capability::Reactions, Capability::DirectMessages];
+    let hello_msg = ClientMessage::Hello { protocol_version: PROTOCOL_VERSION, capabilities };
+    let json = serde_json::to_string(&hello_msg)?;
     writer.write_all(json.as_bytes()).await?;
     writer.write_all(b"\n").await?;
 
-    // 受信用タスク
-    task::spawn(async move {
-        let mut lines = reader.lines();
+    // ログイン (名前やパスワードが通らなければ入力し直す)
+    let username = 'login: loop {
+        print!("Enter your username: ");
+        io::Write::flush(&mut io::stdout())?;
+        let mut username = String::new();
+        io::stdin().read_line(&mut username)?;
+        let username = username.trim().to_string();
+        let password = rpassword::prompt_password("Password (leave empty to log in as a guest): ")?;
+
+        let login_msg = ClientMessage::Login {
+            username: username.clone(),
+            password: (!password.is_empty()).then(|| password.clone()),
+        };
+        let json = serde_json::to_string(&login_msg)?;
+        writer.write_all(json.as_bytes()).await?;
+        writer.write_all(b"\n").await?;
 
+        while let Some(line) = lines.next_line().await? {
+            match serde_json::from_str::<ServerMessage>(line.trim()) {
+                Ok(ServerMessage::Hello { protocol_version, .. }) => {
+                    println!("*** Connected (protocol v{})", protocol_version);
+                }
+                Ok(ServerMessage::Welcome { .. }) => break 'login username,
+                Ok(ServerMessage::Error { code: Some(ErrorCode::InvalidCredentials), .. }) => {
+                    // アカウントがなければその場で登録できる
+                    print!("*** No account matched. Register {} with this password? [y/N]: ", username);
+                    io::Write::flush(&mut io::stdout())?;
+                    let mut answer = String::new();
+                    io::stdin().read_line(&mut answer)?;
+                    if !answer.trim().eq_ignore_ascii_case("y") {
+                        continue 'login;
+                    }
+
+                    let register_msg = ClientMessage::Register { username: username.clone(), password: password.clone() };
+                    let json = serde_json::to_string(&register_msg)?;
+                    writer.write_all(json.as_bytes()).await?;
+                    writer.write_all(b"\n").await?;
+                }
+                Ok(ServerMessage::Error { message, .. }) => {
+                    println!("*** Error: {}", message);
+                    continue 'login;
+                }
+                _ => {}
+            }
+        }
+        return Err("Connection closed by server".into());
+    };
+
+    // ログインすると general に参加している
+    let mut me = username.clone();
+    let rooms = Arc::new(Mutex::new(Rooms::default()));
+    rooms.lock().unwrap().join("general".to_string());
+
+    // 受信用タスク (メッセージにはルーム名を付けて表示する)
+    let receiver_rooms = Arc::clone(&rooms);
+    task::spawn(async move {
         while let Ok(Some(line)) = lines.next_line().await {
-            if let Ok(message) = serde_json::from_str::<ServerMessage>(&line.trim()) {
+            if let Ok(message) = serde_json::from_str::<ServerMessage>(line.trim()) {
                 match message {
-                    ServerMessage::NewMessage { sender, content, .. } => {
-                        println!("{}: {}", sender, content);
+                    ServerMessage::Hello { protocol_version, .. } => {
+                        println!("*** Connected (protocol v{})", protocol_version);
+                    }
+                    ServerMessage::Error { message, .. } => {
+                        println!("*** Error: {}", message);
+                    }
+                    ServerMessage::NewMessage { id, seq, sender, content, room_name, reply_to, .. } => {
+                        let mut rooms = receiver_rooms.lock().unwrap();
+                        let reply = rooms.reply_marker(&room_name, reply_to.as_deref());
+                        println!("[{} #{}] {}: {}{}", room_name, seq, sender, content, reply);
+                        rooms.remember(&room_name, seq, id.clone());
+                        if sender.eq_ignore_ascii_case(&me) {
+                            rooms.last_sent.insert(room_name, id);
+                        }
+                    }
+                    ServerMessage::MessageEdited { room_name, content, by, .. } => {
+                        println!("*** [{}] {} edited a message: {}", room_name, by, content);
+                    }
+                    ServerMessage::MessageDeleted { room_name, message_id, by } => {
+                        println!("*** [{}] {} deleted a message", room_name, by);
+                        receiver_rooms.lock().unwrap().last_sent.retain(|_, id| *id != message_id);
+                    }
+                    ServerMessage::ReactionsUpdated { room_name, message_id, reactions } => {
+                        let seq = receiver_rooms.lock().unwrap().message_seq(&room_name, &message_id);
+                        let target = seq.map(|seq| format!("#{}", seq)).unwrap_or_else(|| "a message".to_string());
+                        if reactions.is_empty() {
+                            println!("*** [{}] reactions on {} cleared", room_name, target);
+                        } else {
+                            println!("*** [{}] reactions on {}: {}", room_name, target, reaction_summary(&reactions));
+                        }
+                    }
+                    ServerMessage::DirectMessage { from, content, .. } => {
+                        println!("[DM from {}] {}", from, content);
+                    }
+                    ServerMessage::NickChanged { old_username, new_username } => {
+                        println!("*** {} is now known as {}", old_username, new_username);
+                        if old_username.eq_ignore_ascii_case(&me) {
+                            me = new_username;
+                        }
                     }
                     ServerMessage::UserJoined { username, room_name } => {
                         println!("*** {} joined {}", username, room_name);
@@ -37,6 +242,77 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     ServerMessage::UserLeft { username, room_name } => {
                         println!("*** {} left {}", username, room_name);
                     }
+                    ServerMessage::History { room_name, messages } => {
+                        if !messages.is_empty() {
+                            println!("--- Recent messages in {} ---", room_name);
+                            let mut rooms = receiver_rooms.lock().unwrap();
+                            for message in messages {
+                                println!("{}", rooms.history_line(&room_name, message));
+                            }
+                            println!("---");
+                        }
+                    }
+                    ServerMessage::Thread { room_name, root, replies } => {
+                        println!("--- Thread in {} ---", room_name);
+                        let mut rooms = receiver_rooms.lock().unwrap();
+                        if root.is_none() {
+                            println!("(the original message is no longer in the history)");
+                        }
+                        for message in root.into_iter().chain(replies) {
+                            println!("{}", rooms.history_line(&room_name, message));
+                        }
+                        println!("---");
+                    }
+                    ServerMessage::ModeratorGranted { room_name, username, by } => {
+                        println!("*** [{}] {} made {} a moderator", room_name, by, username);
+                    }
+                    ServerMessage::UserKicked { room_name, username, by } => {
+                        println!("*** [{}] {} was kicked by {}", room_name, username, by);
+                    }
+                    ServerMessage::UserBanned { room_name, username, by, expires_at } => {
+                        match expires_at {
+                            Some(expires_at) => println!("*** [{}] {} was banned by {} until {}", room_name, username, by, expires_at),
+                            None => println!("*** [{}] {} was banned by {}", room_name, username, by),
+                        }
+                    }
+                    ServerMessage::UserUnbanned { room_name, username, by } => {
+                        println!("*** [{}] {} was unbanned by {}", room_name, username, by);
+                    }
+                    ServerMessage::UserMuted { room_name, username, by } => {
+                        println!("*** [{}] {} was muted by {}", room_name, username, by);
+                    }
+                    ServerMessage::UserUnmuted { room_name, username, by } => {
+                        println!("*** [{}] {} was unmuted by {}", room_name, username, by);
+                    }
+                    ServerMessage::UserInvited { room_name, username, by } => {
+                        println!("*** [{}] {} invited {} (use /join {} to enter)", room_name, by, username, room_name);
+                    }
+                    ServerMessage::UserList { room_name, users } => {
+                        println!("*** Users in {}: {}", room_name, users.join(", "));
+                    }
+                    ServerMessage::RoomList { rooms, unread } => {
+                        // 参加中で未読のあるルームには件数を付ける
+                        let rooms: Vec<String> = rooms.into_iter().map(|room_name| match unread.get(&room_name) {
+                            Some(&count) if count > 0 => format!("{} ({} unread)", room_name, count),
+                            _ => room_name,
+                        }).collect();
+                        println!("*** Rooms: {}", rooms.join(", "));
+                    }
+                    ServerMessage::JoinedRoom { room_name } => {
+                        println!("*** You joined {} (now talking in {})", room_name, room_name);
+                        receiver_rooms.lock().unwrap().join(room_name);
+                    }
+                    ServerMessage::LeftRoom { room_name } => {
+                        let mut rooms = receiver_rooms.lock().unwrap();
+                        rooms.leave(&room_name);
+                        match &rooms.active {
+                            Some(active) => println!("*** You left {} (now talking in {})", room_name, active),
+                            None => println!("*** You left {}", room_name),
+                        }
+                    }
+                    ServerMessage::UserTyping { .. } | ServerMessage::UserStoppedTyping { .. } | ServerMessage::ReadBy { .. } => {
+                        // 行単位の端末では入力中や既読の表示は出さない (合意していないので、古いサーバーからしか届かない)
+                    }
                     _ => {
                         println!("{:?}", message);
                     }
@@ -50,19 +326,135 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     loop {
         io::stdin().read_line(&mut input)?;
         let trimmed = input.trim();
+        let active = rooms.lock().unwrap().active.clone();
 
-        let message = if trimmed.starts_with("/join ") {
-            let room_name = trimmed[6..].to_string();
-            ClientMessage::JoinRoom { room_name }
-        } else if trimmed.starts_with("/create ") {
-            let room_name = trimmed[8..].to_string();
-            ClientMessage::CreateRoom { room_name }
+        let message = if let Some(room_name) = trimmed.strip_prefix("/switch ") {
+            // 送り先のルームを切り替え、そこで受け取ったメッセージを既読にする
+            let mut rooms = rooms.lock().unwrap();
+            if rooms.joined.iter().any(|r| r == room_name) {
+                rooms.active = Some(room_name.to_string());
+                println!("*** Now talking in {}", room_name);
+            } else {
+                println!("*** You are not in {} (joined: {})", room_name, rooms.joined.join(", "));
+            }
+            match rooms.latest_seq(room_name).filter(|_| rooms.active.as_deref() == Some(room_name)) {
+                Some(seq) => ClientMessage::MarkRead { room_name: room_name.to_string(), seq },
+                None => {
+                    input.clear();
+                    continue;
+                }
+            }
+        } else if let Some(args) = trimmed.strip_prefix("/join ") {
+            // /join <room_name> [password]
+            let mut args = args.split_whitespace();
+            let room_name = args.next().unwrap_or_default().to_string();
+            ClientMessage::JoinRoom { room_name, password: args.next().map(|p| p.to_string()) }
+        } else if let Some(args) = trimmed.strip_prefix("/create ") {
+            match create_command(args) {
+                Some(message) => message,
+                None => {
+                    println!("*** Usage: /create <room_name> [password <password> | invite] [hidden]");
+                    input.clear();
+                    continue;
+                }
+            }
+        } else if let Some(room_name) = trimmed.strip_prefix("/leave ") {
+            ClientMessage::LeaveRoom { room_name: room_name.to_string() }
+        } else if trimmed == "/leave" {
+            match active {
+                Some(room_name) => ClientMessage::LeaveRoom { room_name },
+                None => {
+                    println!("*** You are not in a room");
+                    input.clear();
+                    continue;
+                }
+            }
+        } else if let Some(rest) = trimmed.strip_prefix("/msg ") {
+            // /msg <user> <text>
+            match rest.trim_start().split_once(' ') {
+                Some((to, content)) if !content.trim().is_empty() => {
+                    println!("[DM to {}] {}", to, content.trim());
+                    ClientMessage::DirectMessage { to: to.to_string(), content: content.trim().to_string() }
+                }
+                _ => {
+                    println!("*** Usage: /msg <user> <text>");
+                    input.clear();
+                    continue;
+                }
+            }
+        } else if trimmed == "/delete" || trimmed.starts_with("/edit ") {
+            // /edit <text> と /delete は送り先のルームで自分が最後に送ったメッセージに対して行う
+            let last_sent = active.as_ref().and_then(|room_name| rooms.lock().unwrap().last_sent.get(room_name).cloned());
+            match (active, last_sent) {
+                (Some(room_name), Some(message_id)) => match trimmed.strip_prefix("/edit ") {
+                    Some(content) => ClientMessage::EditMessage { room_name, message_id, content: content.trim().to_string() },
+                    None => ClientMessage::DeleteMessage { room_name, message_id },
+                },
+                _ => {
+                    println!("*** You have not sent a message in this room yet");
+                    input.clear();
+                    continue;
+                }
+            }
+        } else if let Some(args) = trimmed.strip_prefix("/reply ").or_else(|| trimmed.strip_prefix("/thread ")) {
+            // /reply <番号> <text> と /thread <番号> は送り先のルームのメッセージを通し番号で指定する
+            let replying = trimmed.starts_with("/reply ");
+            let (seq, content) = args.trim_start().split_once(' ').unwrap_or((args.trim(), ""));
+            let seq = seq.trim_start_matches('#').parse().ok();
+            let message_id = active.as_ref().zip(seq).and_then(|(room_name, seq)| rooms.lock().unwrap().message_id(room_name, seq));
+            match (active, message_id) {
+                (Some(room_name), Some(message_id)) if replying && !content.trim().is_empty() => {
+                    ClientMessage::SendMessage { room_name, content: content.trim().to_string(), reply_to: Some(message_id) }
+                }
+                (Some(room_name), Some(message_id)) if !replying => ClientMessage::GetThread { room_name, message_id },
+                _ => {
+                    println!("*** Usage: /reply <number> <text> or /thread <number> (numbers are shown as #n in the current room)");
+                    input.clear();
+                    continue;
+                }
+            }
+        } else if let Some(args) = trimmed.strip_prefix("/react ").or_else(|| trimmed.strip_prefix("/unreact ")) {
+            // /react <番号> <emoji> と /unreact <番号> <emoji>
+            let adding = trimmed.starts_with("/react ");
+            let mut args = args.split_whitespace();
+            let seq = args.next().and_then(|seq| seq.trim_start_matches('#').parse().ok());
+            let emoji = args.next().map(|emoji| emoji.to_string());
+            let message_id = active.as_ref().zip(seq).and_then(|(room_name, seq)| rooms.lock().unwrap().message_id(room_name, seq));
+            match (active, message_id, emoji) {
+                (Some(room_name), Some(message_id), Some(emoji)) if adding => ClientMessage::React { room_name, message_id, emoji },
+                (Some(room_name), Some(message_id), Some(emoji)) => ClientMessage::Unreact { room_name, message_id, emoji },
+                _ => {
+                    println!("*** Usage: /react <number> <emoji> or /unreact <number> <emoji> (numbers are shown as #n in the current room)");
+                    input.clear();
+                    continue;
+                }
+            }
+        } else if let Some(username) = trimmed.strip_prefix("/nick ") {
+            ClientMessage::ChangeNick { username: username.trim().to_string() }
         } else if trimmed == "/rooms" {
             ClientMessage::ListRooms
-        } else if trimmed == "/users" {
-            ClientMessage::ListUsers
+        } else if let Some(message) = active.clone().and_then(|room_name| moderation_command(trimmed, room_name)) {
+            message
+        } else if let Some(username) = trimmed.strip_prefix("/invite ") {
+            match active {
+                Some(room_name) => ClientMessage::Invite { room_name, username: username.trim().to_string() },
+                None => {
+                    println!("*** You are not in a room");
+                    input.clear();
+                    continue;
+                }
+            }
         } else {
-            ClientMessage::SendMessage { content: trimmed.to_string() }
+            // /users と通常のメッセージは送り先のルームに対して行う
+            match active {
+                Some(room_name) if trimmed == "/users" => ClientMessage::ListUsers { room_name },
+                Some(room_name) => ClientMessage::SendMessage { room_name, content: trimmed.to_string(), reply_to: None },
+                None => {
+                    println!("*** You are not in a room (use /join <room_name>)");
+                    input.clear();
+                    continue;
+                }
+            }
         };
 
         let json = serde_json::to_string(&message)?;
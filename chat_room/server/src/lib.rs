@@ -1,4 +0,0 @@
-pub mod entity;
-
-pub mod room;
-pub mod server;
\ No newline at end of file
@@ -1,12 +1,19 @@
-use server::server::ChatServer;
+use chat_core::config::ServerConfig;
+use chat_core::server::ChatServer;
+use chat_core::storage;
+use chat_core::transport::Transport;
+use chat_core::transport::tcp::TcpTransport;
 
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
 
-    let server = ChatServer::new();
-    server.run("127.0.0.1:8080").await?;
+    // CHAT_DATABASE を指定するとルームと履歴を SQLite に保存する
+    let config = ServerConfig::from_env();
+    let storage = storage::open(&config)?;
+    let server = ChatServer::with_storage(config, storage);
+    TcpTransport::bind("127.0.0.1:8080").await?.serve(server).await?;
 
     Ok(())
 }
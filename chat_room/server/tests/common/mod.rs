@@ -0,0 +1,107 @@
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use chat_protocol::{ClientMessage, RoomVisibility, SequencedMessage, ServerMessage};
+use chat_core::server::ChatServer;
+use chat_core::transport::Transport;
+use chat_core::transport::tcp::TcpTransport;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+pub struct TestClient {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+impl TestClient {
+    pub async fn connect(addr: &str) -> Self {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (reader, writer) = stream.into_split();
+        Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+        }
+    }
+
+    pub async fn send(&mut self, message: ClientMessage) {
+        let json = serde_json::to_string(&message).unwrap();
+        self.writer.write_all(json.as_bytes()).await.unwrap();
+        self.writer.write_all(b"\n").await.unwrap();
+    }
+
+    pub async fn recv(&mut self) -> ServerMessage {
+        self.recv_sequenced().await.message
+    }
+
+    // セッションの通し番号も見る
+    pub async fn recv_sequenced(&mut self) -> SequencedMessage {
+        let line = timeout(Duration::from_secs(2), self.lines.next_line())
+            .await
+            .expect("timed out waiting for server message")
+            .unwrap()
+            .expect("connection closed");
+        serde_json::from_str(&line).unwrap()
+    }
+
+    pub async fn recv_until<F: Fn(&ServerMessage) -> bool>(&mut self, pred: F) -> ServerMessage {
+        loop {
+            let message = self.recv().await;
+            if pred(&message) {
+                return message;
+            }
+        }
+    }
+
+    pub async fn assert_silent(&mut self) {
+        let result = timeout(Duration::from_millis(200), self.lines.next_line()).await;
+        assert!(result.is_err(), "unexpected message: {:?}", result);
+    }
+
+    pub async fn assert_closed(&mut self) {
+        let result = timeout(Duration::from_secs(2), self.lines.next_line())
+            .await
+            .expect("timed out waiting for the connection to close");
+        assert!(matches!(result, Ok(None)), "expected closed connection, got {:?}", result);
+    }
+
+    pub async fn login(addr: &str, username: &str) -> Self {
+        let mut client = Self::connect(addr).await;
+        client.send(ClientMessage::Login { username: username.to_string(), password: None }).await;
+        match client.recv().await {
+            ServerMessage::Welcome { .. } => {}
+            other => panic!("expected Welcome, got {:?}", other),
+        }
+        client
+            .recv_until(|m| matches!(m, ServerMessage::UserJoined { username: u, .. } if u == username))
+            .await;
+        client.recv_until(|m| matches!(m, ServerMessage::History { .. })).await;
+        client
+    }
+
+    pub async fn join(&mut self, room_name: &str) {
+        self.send(ClientMessage::JoinRoom { room_name: room_name.to_string(), password: None }).await;
+        self.recv_until(|m| matches!(m, ServerMessage::JoinedRoom { .. })).await;
+        self.recv_until(|m| matches!(m, ServerMessage::History { .. })).await;
+    }
+}
+
+// 公開ルームを作成する
+pub fn create_room(room_name: &str) -> ClientMessage {
+    ClientMessage::CreateRoom { room_name: room_name.to_string(), visibility: RoomVisibility::Public, password: None, hidden: false }
+}
+
+pub async fn start_server() -> String {
+    start_server_with(ChatServer::new()).await
+}
+
+pub async fn start_server_with(server: ChatServer) -> String {
+    let transport = TcpTransport::bind("127.0.0.1:0").await.unwrap();
+    let addr = transport.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        let _ = transport.serve(server).await;
+    });
+    addr
+}
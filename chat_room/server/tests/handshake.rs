@@ -0,0 +1,49 @@
+mod common;
+
+use common::{start_server, TestClient};
+use chat_protocol::{Capability, ClientMessage, ServerMessage, PROTOCOL_VERSION};
+
+#[tokio::test]
+async fn hello_is_answered_with_server_version_and_capabilities() {
+    let addr = start_server().await;
+    let mut client = TestClient::connect(&addr).await;
+    client.send(ClientMessage::Hello { protocol_version: PROTOCOL_VERSION, capabilities: vec![Capability::History, Capability::Unknown] }).await;
+
+    match client.recv().await {
+        ServerMessage::Hello { protocol_version, capabilities } => {
+            assert_eq!(protocol_version, PROTOCOL_VERSION);
+            assert!(capabilities.contains(&Capability::History));
+        }
+        other => panic!("expected Hello, got {:?}", other),
+    }
+
+    // 2 回目の Hello は拒否される
+    client.send(ClientMessage::Hello { protocol_version: PROTOCOL_VERSION, capabilities: vec![] }).await;
+    assert!(matches!(client.recv().await, ServerMessage::Error { .. }));
+}
+
+#[tokio::test]
+async fn incompatible_version_gets_error_and_is_disconnected() {
+    let addr = start_server().await;
+    let mut client = TestClient::connect(&addr).await;
+    client.send(ClientMessage::Hello { protocol_version: 0, capabilities: vec![] }).await;
+
+    match client.recv().await {
+        ServerMessage::Error { message, .. } => assert!(message.contains("Unsupported protocol version"), "{}", message),
+        other => panic!("expected Error, got {:?}", other),
+    }
+    client.assert_closed().await;
+}
+
+#[tokio::test]
+async fn history_is_only_sent_to_clients_that_negotiated_it() {
+    let addr = start_server().await;
+    let mut client = TestClient::connect(&addr).await;
+    client.send(ClientMessage::Hello { protocol_version: PROTOCOL_VERSION, capabilities: vec![] }).await;
+    assert!(matches!(client.recv().await, ServerMessage::Hello { .. }));
+
+    client.send(ClientMessage::Login { username: "alice".to_string(), password: None }).await;
+    assert!(matches!(client.recv().await, ServerMessage::Welcome { .. }));
+    assert!(matches!(client.recv().await, ServerMessage::UserJoined { .. }));
+    client.assert_silent().await;
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use common::{create_room, start_server, start_server_with, TestClient};
+use chat_protocol::{ClientMessage, ServerMessage};
+use chat_core::config::ServerConfig;
+use chat_core::server::ChatServer;
+
+#[tokio::test]
+async fn login_replays_recent_general_history() {
+    let addr = start_server_with(ChatServer::with_config(ServerConfig { history_limit: 2, ..Default::default() })).await;
+    let mut alice = TestClient::login(&addr, "alice").await;
+    for content in ["one", "two", "three"] {
+        alice.send(ClientMessage::SendMessage { room_name: "general".to_string(), content: content.to_string(), reply_to: None }).await;
+        alice.recv_until(|m| matches!(m, ServerMessage::NewMessage { .. })).await;
+    }
+
+    let mut bob = TestClient::connect(&addr).await;
+    bob.send(ClientMessage::Login { username: "bob".to_string(), password: None }).await;
+    let history = bob.recv_until(|m| matches!(m, ServerMessage::History { .. })).await;
+    match history {
+        ServerMessage::History { room_name, messages } => {
+            assert_eq!(room_name, "general");
+            let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+            assert_eq!(contents, vec!["two", "three"]);
+            assert!(messages.iter().all(|m| m.sender == "alice"));
+            assert!(messages.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+        }
+        other => panic!("expected History, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn joining_a_room_replays_its_history_before_live_messages() {
+    let addr = start_server().await;
+    let mut alice = TestClient::login(&addr, "alice").await;
+    alice.send(create_room("rust")).await;
+    alice.recv_until(|m| matches!(m, ServerMessage::RoomCreated { .. })).await;
+    alice.join("rust").await;
+    alice.send(ClientMessage::SendMessage { room_name: "rust".to_string(), content: "earlier".to_string(), reply_to: None }).await;
+    alice.recv_until(|m| matches!(m, ServerMessage::NewMessage { .. })).await;
+
+    let mut bob = TestClient::login(&addr, "bob").await;
+    bob.send(ClientMessage::JoinRoom { room_name: "rust".to_string(), password: None }).await;
+
+    bob.recv_until(|m| matches!(m, ServerMessage::JoinedRoom { .. })).await;
+    match bob.recv().await {
+        ServerMessage::History { room_name, messages } => {
+            assert_eq!(room_name, "rust");
+            assert_eq!(messages.len(), 1);
+            assert_eq!(messages[0].sender, "alice");
+            assert_eq!(messages[0].content, "earlier");
+        }
+        other => panic!("expected History, got {:?}", other),
+    }
+
+    alice.send(ClientMessage::SendMessage { room_name: "rust".to_string(), content: "later".to_string(), reply_to: None }).await;
+    match bob.recv().await {
+        ServerMessage::NewMessage { content, .. } => assert_eq!(content, "later"),
+        other => panic!("expected NewMessage, got {:?}", other),
+    }
+}
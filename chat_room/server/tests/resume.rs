@@ -0,0 +1,57 @@
+mod common;
+
+use common::{start_server, TestClient};
+use chat_protocol::{ClientMessage, SequencedMessage, ServerMessage};
+
+// 切れた TCP 接続の代わりに新しい接続でセッションを再開する
+#[tokio::test]
+async fn a_new_connection_resumes_the_session_and_receives_missed_messages() {
+    let addr = start_server().await;
+    let mut alice = TestClient::connect(&addr).await;
+    alice.send(ClientMessage::Login { username: "alice".to_string(), password: None }).await;
+    let welcome = alice.recv_sequenced().await;
+    let token = match welcome.message {
+        ServerMessage::Welcome { session_token: Some(token), .. } => token,
+        other => panic!("expected Welcome with a token, got {:?}", other),
+    };
+    assert_eq!(welcome.session_seq, Some(1));
+    // ログイン時の UserJoined と History まで受け取ってから切断する
+    let mut last_seen_seq = loop {
+        let message = alice.recv_sequenced().await;
+        if matches!(message.message, ServerMessage::History { .. }) {
+            break message.session_seq.unwrap();
+        }
+    };
+
+    let mut bob = TestClient::login(&addr, "bob").await;
+    drop(alice);
+    bob.send(ClientMessage::SendMessage { room_name: "general".to_string(), content: "are you there?".to_string(), reply_to: None }).await;
+    bob.recv_until(|m| matches!(m, ServerMessage::NewMessage { .. })).await;
+
+    let mut alice = TestClient::connect(&addr).await;
+    alice.send(ClientMessage::Resume { token, last_seen_seq }).await;
+    match alice.recv_sequenced().await {
+        SequencedMessage { session_seq: None, message: ServerMessage::Resumed { username, rooms, .. } } => {
+            assert_eq!(username, "alice");
+            assert_eq!(rooms, vec!["general"]);
+        }
+        other => panic!("expected Resumed, got {:?}", other),
+    }
+
+    // bob の参加と、切断中のメッセージが続きの番号で届く
+    let mut replayed = Vec::new();
+    loop {
+        let message = alice.recv_sequenced().await;
+        assert_eq!(message.session_seq, Some(last_seen_seq + 1));
+        last_seen_seq += 1;
+        let done = matches!(&message.message, ServerMessage::NewMessage { content, .. } if content == "are you there?");
+        replayed.push(message.message);
+        if done {
+            break;
+        }
+    }
+    assert!(matches!(replayed.first(), Some(ServerMessage::UserJoined { username, .. }) if username == "bob"));
+
+    // bob からは退出も再参加も見えない
+    bob.assert_silent().await;
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{create_room, start_server, TestClient};
+use chat_protocol::{ClientMessage, ServerMessage};
+
+#[tokio::test]
+async fn leaving_a_room_notifies_members_and_keeps_other_rooms() {
+    let addr = start_server().await;
+    let mut alice = TestClient::login(&addr, "alice").await;
+    let mut bob = TestClient::login(&addr, "bob").await;
+    alice.recv_until(|m| matches!(m, ServerMessage::UserJoined { .. })).await;
+
+    alice.send(create_room("rust")).await;
+    alice.recv_until(|m| matches!(m, ServerMessage::RoomCreated { .. })).await;
+    alice.join("rust").await;
+    bob.join("rust").await;
+    alice.recv_until(|m| matches!(m, ServerMessage::UserJoined { username, .. } if username == "bob")).await;
+
+    bob.send(ClientMessage::LeaveRoom { room_name: "rust".to_string() }).await;
+
+    match alice.recv().await {
+        ServerMessage::UserLeft { username, room_name } => {
+            assert_eq!(username, "bob");
+            assert_eq!(room_name, "rust");
+        }
+        other => panic!("expected UserLeft, got {:?}", other),
+    }
+
+    match bob.recv().await {
+        ServerMessage::LeftRoom { room_name } => assert_eq!(room_name, "rust"),
+        other => panic!("expected LeftRoom, got {:?}", other),
+    }
+
+    // bob はもう rust のメッセージを受け取らないが、general には残っている
+    alice.send(ClientMessage::SendMessage { room_name: "rust".to_string(), content: "still here".to_string(), reply_to: None }).await;
+    alice.recv_until(|m| matches!(m, ServerMessage::NewMessage { .. })).await;
+    bob.assert_silent().await;
+
+    alice.send(ClientMessage::SendMessage { room_name: "general".to_string(), content: "general".to_string(), reply_to: None }).await;
+    match bob.recv().await {
+        ServerMessage::NewMessage { room_name, .. } => assert_eq!(room_name, "general"),
+        other => panic!("expected NewMessage, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn leaving_general_leaves_the_user_roomless() {
+    let addr = start_server().await;
+    let mut alice = TestClient::login(&addr, "alice").await;
+    let mut bob = TestClient::login(&addr, "bob").await;
+    alice.recv_until(|m| matches!(m, ServerMessage::UserJoined { .. })).await;
+
+    bob.send(ClientMessage::LeaveRoom { room_name: "general".to_string() }).await;
+    alice.recv_until(|m| matches!(m, ServerMessage::UserLeft { username, .. } if username == "bob")).await;
+    match bob.recv().await {
+        ServerMessage::LeftRoom { room_name } => assert_eq!(room_name, "general"),
+        other => panic!("expected LeftRoom, got {:?}", other),
+    }
+
+    alice.send(ClientMessage::SendMessage { room_name: "general".to_string(), content: "anyone?".to_string(), reply_to: None }).await;
+    alice.recv_until(|m| matches!(m, ServerMessage::NewMessage { .. })).await;
+    bob.assert_silent().await;
+}
+
+#[tokio::test]
+async fn leaving_a_room_the_user_is_not_in_is_an_error() {
+    let addr = start_server().await;
+    let mut alice = TestClient::login(&addr, "alice").await;
+
+    alice.send(ClientMessage::LeaveRoom { room_name: "rust".to_string() }).await;
+    match alice.recv().await {
+        ServerMessage::Error { message, .. } => assert_eq!(message, "Not in room"),
+        other => panic!("expected Error, got {:?}", other),
+    }
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use chrono::TimeDelta;
+use common::{create_room, start_server, start_server_with, TestClient};
+use chat_core::config::ServerConfig;
+use chat_core::server::ChatServer;
+use chat_protocol::{ClientMessage, ServerMessage};
+
+#[tokio::test]
+async fn two_clients_receive_each_others_messages() {
+    let addr = start_server().await;
+    let mut alice = TestClient::login(&addr, "alice").await;
+    let mut bob = TestClient::login(&addr, "bob").await;
+
+    // alice は bob の参加通知を受け取る
+    match alice.recv().await {
+        ServerMessage::UserJoined { username, room_name } => {
+            assert_eq!(username, "bob");
+            assert_eq!(room_name, "general");
+        }
+        other => panic!("expected UserJoined, got {:?}", other),
+    }
+
+    alice.send(ClientMessage::SendMessage { room_name: "general".to_string(), content: "hi bob".to_string(), reply_to: None }).await;
+    for client in [&mut alice, &mut bob] {
+        match client.recv().await {
+            ServerMessage::NewMessage { sender, content, room_name, .. } => {
+                assert_eq!(sender, "alice");
+                assert_eq!(content, "hi bob");
+                assert_eq!(room_name, "general");
+            }
+            other => panic!("expected NewMessage, got {:?}", other),
+        }
+    }
+
+    bob.send(ClientMessage::SendMessage { room_name: "general".to_string(), content: "hi alice".to_string(), reply_to: None }).await;
+    for client in [&mut alice, &mut bob] {
+        match client.recv().await {
+            ServerMessage::NewMessage { sender, content, .. } => {
+                assert_eq!(sender, "bob");
+                assert_eq!(content, "hi alice");
+            }
+            other => panic!("expected NewMessage, got {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn room_messages_only_reach_members() {
+    let addr = start_server().await;
+    let mut alice = TestClient::login(&addr, "alice").await;
+    let mut bob = TestClient::login(&addr, "bob").await;
+    alice.recv_until(|m| matches!(m, ServerMessage::UserJoined { .. })).await;
+
+    bob.send(create_room("rust")).await;
+    bob.recv_until(|m| matches!(m, ServerMessage::RoomCreated { .. })).await;
+    bob.join("rust").await;
+
+    // bob は general にも残ったまま rust に送る
+    bob.send(ClientMessage::SendMessage { room_name: "rust".to_string(), content: "only rust".to_string(), reply_to: None }).await;
+    bob.recv_until(|m| matches!(m, ServerMessage::NewMessage { .. })).await;
+    alice.assert_silent().await;
+}
+
+#[tokio::test]
+async fn direct_responses_only_reach_the_requester() {
+    let addr = start_server().await;
+    let mut alice = TestClient::login(&addr, "alice").await;
+    let mut bob = TestClient::login(&addr, "bob").await;
+    alice.recv_until(|m| matches!(m, ServerMessage::UserJoined { .. })).await;
+
+    bob.send(ClientMessage::ListRooms).await;
+    match bob.recv().await {
+        ServerMessage::RoomList { rooms, .. } => assert_eq!(rooms, vec!["general".to_string()]),
+        other => panic!("expected RoomList, got {:?}", other),
+    }
+    alice.assert_silent().await;
+}
+
+#[tokio::test]
+async fn disconnect_notifies_remaining_members_after_the_resume_grace() {
+    let config = ServerConfig { resume_grace: TimeDelta::milliseconds(200), ..Default::default() };
+    let addr = start_server_with(ChatServer::with_config(config)).await;
+    let mut alice = TestClient::login(&addr, "alice").await;
+    let bob = TestClient::login(&addr, "bob").await;
+    alice.recv_until(|m| matches!(m, ServerMessage::UserJoined { .. })).await;
+
+    // 猶予の間は退出扱いにしない
+    drop(bob);
+    alice.assert_silent().await;
+    match alice.recv().await {
+        ServerMessage::UserLeft { username, room_name } => {
+            assert_eq!(username, "bob");
+            assert_eq!(room_name, "general");
+        }
+        other => panic!("expected UserLeft, got {:?}", other),
+    }
+}
@@ -1 +0,0 @@
-pub mod message;
\ No newline at end of file
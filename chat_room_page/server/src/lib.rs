@@ -1,3 +1 @@
-pub mod entity;
-pub mod server;
-pub mod room;
\ No newline at end of file
+pub mod transport;
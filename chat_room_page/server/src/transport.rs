@@ -0,0 +1,98 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use futures::{SinkExt, StreamExt};
+use tokio::sync::mpsc;
+use warp::Filter;
+use warp::ws::{Message, WebSocket};
+
+use chat_core::server::ChatServer;
+use chat_core::session::Flow;
+use chat_core::transport::Transport;
+use chat_protocol::SequencedMessage;
+
+// warp の WebSocket トランスポート (静的ファイルも同じポートで配信する)
+#[derive(Debug)]
+pub struct WarpTransport {
+    addr: SocketAddr,
+}
+
+impl WarpTransport {
+    pub fn new(addr: impl Into<SocketAddr>) -> Self {
+        Self { addr: addr.into() }
+    }
+}
+
+impl Transport for WarpTransport {
+    async fn serve(self, server: ChatServer) -> Result<(), Box<dyn std::error::Error>> {
+        let routes = ws_route(server)
+            .or(static_files())
+            .with(warp::cors().allow_any_origin());
+
+        warp::serve(routes).run(self.addr).await;
+        Ok(())
+    }
+}
+
+// WebSocketハンドラ
+pub fn ws_route(server: ChatServer) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("ws")
+        .and(warp::ws())
+        .map(move |ws: warp::ws::Ws| {
+            let server = server.clone();
+            ws.on_upgrade(move |socket| handle_websocket(socket, server))
+        })
+}
+
+fn static_files() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    // 静的ファイル配信
+    let static_files = warp::path("static")
+        .and(warp::fs::dir("static"));
+
+    // ルートパスでのindex.html配信
+    let index = warp::path::end()
+        .and(warp::fs::file("static/index.html"));
+
+    static_files.or(index)
+}
+
+async fn handle_websocket(ws: WebSocket, server: ChatServer) {
+    // WebSocketストリームを分割
+    let (mut ws_tx, mut ws_rx) = ws.split();
+
+    // セッションごとの送信チャネル
+    let (tx, mut rx) = mpsc::unbounded_channel::<SequencedMessage>();
+
+    // 送信タスク: チャネルに届いたメッセージを即座にブラウザへ送る
+    let send_task = tokio::spawn(async move {
+        while let Some(server_msg) = rx.recv().await {
+            let json = serde_json::to_string(&server_msg).unwrap();
+            if let Err(e) = ws_tx.send(Message::text(json)).await {
+                eprintln!("Error sending message: {}", e);
+                break;
+            }
+        }
+        let _ = ws_tx.close().await;
+    });
+
+    let mut session = server.connect(Arc::new(tx));
+
+    // WebSocketからメッセージを受信
+    while let Some(result) = ws_rx.next().await {
+        match result {
+            Ok(msg) => {
+                if let Ok(text) = msg.to_str()
+                    && session.handle_text(text).await == Flow::Close {
+                    break;
+                }
+            }
+            Err(e) => {
+                eprintln!("WebSocket error: {}", e);
+                break;
+            }
+        }
+    }
+
+    // 接続が切断された場合のクリーンアップ (送信チャネルも閉じ、送信タスクが終了する)
+    session.close().await;
+    let _ = send_task.await;
+}
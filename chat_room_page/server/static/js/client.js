@@ -3,45 +3,134 @@ document.addEventListener("DOMContentLoaded", () => {
   const loginPanel = document.getElementById("loginPanel");
   const chatContainer = document.getElementById("chatContainer");
   const usernameInput = document.getElementById("usernameInput");
+  const passwordInput = document.getElementById("passwordInput");
   const loginButton = document.getElementById("loginButton");
+  const registerButton = document.getElementById("registerButton");
   const messageContainer = document.getElementById("messageContainer");
+  const dmContainer = document.getElementById("dmContainer");
+  const dmList = document.getElementById("dmList");
   const messageInput = document.getElementById("messageInput");
+  const typingIndicator = document.getElementById("typingIndicator");
+  const readReceipt = document.getElementById("readReceipt");
+  const replyBar = document.getElementById("replyBar");
+  const replyPreview = document.getElementById("replyPreview");
+  const cancelReplyButton = document.getElementById("cancelReplyButton");
   const sendButton = document.getElementById("sendButton");
   const roomList = document.getElementById("roomList");
   const userList = document.getElementById("userList");
   const currentRoomHeader = document.getElementById("currentRoom");
   const createRoomButton = document.getElementById("createRoomButton");
+  const leaveRoomButton = document.getElementById("leaveRoomButton");
   const createRoomModal = document.getElementById("createRoomModal");
   const roomNameInput = document.getElementById("roomNameInput");
+  const roomVisibilityInput = document.getElementById("roomVisibilityInput");
+  const roomPasswordInput = document.getElementById("roomPasswordInput");
+  const roomHiddenInput = document.getElementById("roomHiddenInput");
   const cancelCreateRoom = document.getElementById("cancelCreateRoom");
   const confirmCreateRoom = document.getElementById("confirmCreateRoom");
 
+  // 対応しているプロトコルバージョンと機能
+  const PROTOCOL_VERSION = 2;
+  const CAPABILITIES = ["history", "typing", "reactions", "read_receipts", "direct_messages"];
+
+  // ログイン・登録の失敗 (エラーコードごとの表示)
+  const LOGIN_ERRORS = {
+    invalid_credentials: "ユーザー名またはパスワードが違います",
+    username_taken: "このユーザー名は使われているか、登録済みです",
+    weak_password: "パスワードは8文字以上にしてください",
+    account_in_use: "このアカウントは別の画面でログイン中です",
+    guests_disabled: "ゲストではログインできません。アカウントを登録してください",
+  };
+
   // WebSocket接続
   let socket = null;
   let currentRoom = "general";
   let currentUsername = "";
   let currentUserId = "";
 
-  // ログインボタンクリック
-  loginButton.addEventListener("click", () => {
+  // 参加中のルームごとのメッセージ (表示するルームを切り替えても残る)
+  const roomMessages = { general: [] };
+  const unreadRooms = {};
+  let knownRooms = ["general"];
+  // 参加を要求中のルーム (パスワードを求められたら入力して送り直す)
+  let pendingJoin = null;
+  // 返信しようとしているメッセージ (次に送るメッセージがその返信になる)
+  let replyTo = null;
+
+  // ルームごとの入力中のユーザー。入力中は TYPING_INTERVAL_MS おきに Typing を送り直す
+  const TYPING_INTERVAL_MS = 2000;
+  const typingUsers = {};
+  let lastTypingSentAt = 0;
+
+  // ルームごとにサーバーへ送った既読位置と、他のメンバーの既読位置 (ReadBy)
+  const readSeqs = {};
+  const readBy = {};
+
+  // 相手ごとの DM 履歴と未読数。dmPeer が設定されている間は DM ペインを表示する
+  const directMessages = {};
+  const unreadDirect = {};
+  let dmPeer = null;
+
+  // セッションの再開用 (Welcome のトークンと、最後に受け取ったメッセージの通し番号)
+  const RECONNECT_DELAY_MS = 2000;
+  let sessionToken = null;
+  let lastSeq = 0;
+  let reconnectTimer = null;
+
+  // ログイン (パスワードが空ならゲスト) / 登録
+  function submitLogin(register) {
     const username = usernameInput.value.trim();
-    if (username) {
-      connectWebSocket(username);
+    const password = passwordInput.value;
+    if (!username || (register && !password)) {
+      return;
     }
-  });
+    connectWebSocket(username, password, register);
+  }
+
+  loginButton.addEventListener("click", () => submitLogin(false));
+  registerButton.addEventListener("click", () => submitLogin(true));
 
   // Enterキーでログイン
-  usernameInput.addEventListener("keypress", (e) => {
-    if (e.key === "Enter") {
-      const username = usernameInput.value.trim();
-      if (username) {
-        connectWebSocket(username);
+  [usernameInput, passwordInput].forEach((input) => {
+    input.addEventListener("keypress", (e) => {
+      if (e.key === "Enter") {
+        submitLogin(false);
       }
-    }
+    });
   });
 
   // WebSocket接続
-  function connectWebSocket(username) {
+  function connectWebSocket(username, password, register) {
+    openSocket(() => {
+      // ログインメッセージを送信
+      const loginMessage = register
+        ? { type: "Register", username: username, password: password }
+        : { type: "Login", username: username };
+      if (!register && password) {
+        loginMessage.password = password;
+      }
+      socket.send(JSON.stringify(loginMessage));
+      passwordInput.value = "";
+
+      currentUsername = username;
+    });
+  }
+
+  // 切断したセッションを再開する (取りこぼしたメッセージはサーバーが送り直す)
+  function resumeSession() {
+    reconnectTimer = null;
+    openSocket(() => {
+      socket.send(
+        JSON.stringify({
+          type: "Resume",
+          token: sessionToken,
+          last_seen_seq: lastSeq,
+        })
+      );
+    });
+  }
+
+  function openSocket(onOpen) {
     const protocol = window.location.protocol === "https:" ? "wss:" : "ws:";
     const wsUrl = `${protocol}//${window.location.host}/ws`;
 
@@ -50,24 +139,32 @@ document.addEventListener("DOMContentLoaded", () => {
     socket.onopen = () => {
       console.log("WebSocket connected");
 
-      // ログインメッセージを送信
-      const loginMessage = {
-        type: "Login",
-        username: username,
-      };
-      socket.send(JSON.stringify(loginMessage));
-
-      currentUsername = username;
+      // ハンドシェイク (サーバーは受信順に処理する)
+      socket.send(
+        JSON.stringify({
+          type: "Hello",
+          protocol_version: PROTOCOL_VERSION,
+          capabilities: CAPABILITIES,
+        })
+      );
+      onOpen();
     };
 
     socket.onmessage = (event) => {
       const message = JSON.parse(event.data);
+      if (message.session_seq) {
+        lastSeq = message.session_seq;
+      }
       handleServerMessage(message);
     };
 
     socket.onclose = () => {
       console.log("WebSocket disconnected");
-      // 再接続ロジックをここに追加することもできます
+      // ログイン済みなら少し待って再開する (サーバーは猶予の間セッションを残している)
+      if (currentUserId && sessionToken && !reconnectTimer) {
+        addSystemMessage("接続が切れました。再接続しています…");
+        reconnectTimer = setTimeout(resumeSession, RECONNECT_DELAY_MS);
+      }
     };
 
     socket.onerror = (error) => {
@@ -80,8 +177,16 @@ document.addEventListener("DOMContentLoaded", () => {
     console.log("Received message:", message);
 
     switch (message.type) {
+      case "Hello":
+        console.log(
+          `Server protocol v${message.protocol_version}:`,
+          message.capabilities
+        );
+        break;
+
       case "Welcome":
         currentUserId = message.user_id;
+        sessionToken = message.session_token || null;
         loginPanel.style.display = "none";
         chatContainer.style.display = "flex";
 
@@ -93,41 +198,213 @@ document.addEventListener("DOMContentLoaded", () => {
         // ユーザー一覧を取得
         sendMessage({
           type: "ListUsers",
+          room_name: currentRoom,
         });
         break;
 
+      case "Resumed":
+        currentUserId = message.user_id;
+        currentUsername = message.username;
+        addSystemMessage("再接続しました");
+        sendMessage({ type: "ListRooms" });
+        refreshUserList();
+        break;
+
       case "NewMessage":
-        addChatMessage(message.sender, message.content, message.room_name);
+        addChatMessage(message);
         break;
 
-      case "UserJoined":
-        if (message.room_name === currentRoom) {
-          addSystemMessage(`${message.username} がルームに参加しました`);
+      case "Thread":
+        // スレッドの元のメッセージと返信を表示中のルームに並べる (元のメッセージは消えていることがある)
+        addSystemMessage(
+          message.root
+            ? `スレッド: ${describeMessage(message.root)}`
+            : "スレッド: (元のメッセージは履歴に残っていません)",
+          message.room_name
+        );
+        message.replies.forEach((m) => {
+          addSystemMessage(`↪ ${describeMessage(m)}`, message.room_name);
+        });
+        break;
 
-          // ユーザー一覧更新
-          sendMessage({
-            type: "ListUsers",
+      case "MessageEdited":
+        updateRoomMessage(message.room_name, message.message_id, (entry) => {
+          entry.content = message.content;
+          entry.edited = true;
+        });
+        break;
+
+      case "MessageDeleted":
+        updateRoomMessage(message.room_name, message.message_id, (entry) => {
+          entry.content = "";
+          entry.deleted = true;
+        });
+        break;
+
+      case "UserTyping":
+        if (!typingUsers[message.room_name]) {
+          typingUsers[message.room_name] = new Set();
+        }
+        typingUsers[message.room_name].add(message.username);
+        renderTypingIndicator();
+        break;
+
+      case "UserStoppedTyping":
+        typingUsers[message.room_name]?.delete(message.username);
+        renderTypingIndicator();
+        break;
+
+      case "ReactionsUpdated":
+        updateRoomMessage(message.room_name, message.message_id, (entry) => {
+          entry.reactions = message.reactions;
+        });
+        break;
+
+      case "DirectMessage":
+        addDirectMessage(message.from, message.from, message.content);
+        break;
+
+      case "NickChanged":
+        renameUser(message.old_username, message.new_username);
+        addSystemMessage(
+          `${message.old_username} は ${message.new_username} に名前を変更しました`
+        );
+
+        // ユーザー一覧更新
+        refreshUserList();
+        break;
+
+      case "ModeratorGranted":
+        addSystemMessage(
+          `${message.by} が ${message.username} をモデレーターにしました`,
+          message.room_name
+        );
+        break;
+
+      case "UserKicked":
+        addSystemMessage(
+          `${message.username} は ${message.by} にキックされました`,
+          message.room_name
+        );
+        break;
+
+      case "UserBanned":
+        addSystemMessage(
+          message.expires_at
+            ? `${message.username} は ${message.by} に ${new Date(
+                message.expires_at
+              ).toLocaleString()} まで BAN されました`
+            : `${message.username} は ${message.by} に BAN されました`,
+          message.room_name
+        );
+        break;
+
+      case "UserUnbanned":
+        addSystemMessage(
+          `${message.username} の BAN は ${message.by} が解除しました`,
+          message.room_name
+        );
+        break;
+
+      case "UserMuted":
+        addSystemMessage(
+          `${message.username} は ${message.by} にミュートされました`,
+          message.room_name
+        );
+        break;
+
+      case "UserUnmuted":
+        addSystemMessage(
+          `${message.username} のミュートは ${message.by} が解除しました`,
+          message.room_name
+        );
+        break;
+
+      case "UserInvited":
+        if (message.username === currentUsername) {
+          // 非表示のルームでも選べるよう一覧に加える
+          addSystemMessage(
+            `${message.by} から「${message.room_name}」に招待されました`
+          );
+          if (!knownRooms.includes(message.room_name)) {
+            updateRoomList([...knownRooms, message.room_name]);
+          }
+        } else {
+          addSystemMessage(
+            `${message.by} が ${message.username} を招待しました`,
+            message.room_name
+          );
+        }
+        break;
+
+      case "History":
+        // 参加直後に届く直近の履歴 (未読には数えない)
+        if (message.room_name in roomMessages && message.messages.length > 0) {
+          message.messages.forEach((m) => {
+            roomMessages[message.room_name].push({
+              id: m.id,
+              seq: m.seq,
+              sender: m.sender,
+              content: m.content,
+              edited: Boolean(m.edited_at),
+              deleted: Boolean(m.deleted),
+              replyTo: m.reply_to ?? null,
+              reactions: m.reactions ?? [],
+            });
+          });
+          roomMessages[message.room_name].push({
+            system: "ここまでが過去のメッセージです",
           });
+          if (message.room_name === currentRoom) {
+            renderRoom();
+            markRoomRead(currentRoom);
+          }
         }
         break;
 
-      case "UserLeft":
+      case "UserJoined":
+        addSystemMessage(
+          `${message.username} がルームに参加しました`,
+          message.room_name
+        );
         if (message.room_name === currentRoom) {
-          addSystemMessage(`${message.username} がルームを退出しました`);
+          refreshUserList();
+        }
+        break;
 
-          // ユーザー一覧更新
-          sendMessage({
-            type: "ListUsers",
-          });
+      case "UserLeft":
+        addSystemMessage(
+          `${message.username} がルームを退出しました`,
+          message.room_name
+        );
+        if (message.room_name === currentRoom) {
+          refreshUserList();
         }
         break;
 
       case "RoomList":
+        // サーバーが数えた未読数で置き換える (表示中のルームは既読にする)
+        Object.entries(message.unread || {}).forEach(([room, count]) => {
+          if (room !== currentRoom || dmPeer !== null) {
+            unreadRooms[room] = count;
+          }
+        });
         updateRoomList(message.rooms);
+        markRoomRead(currentRoom);
+        break;
+
+      case "ReadBy":
+        if (!readBy[message.room_name]) {
+          readBy[message.room_name] = {};
+        }
+        readBy[message.room_name][message.username] = message.seq;
+        renderReadReceipt();
         break;
 
       case "UserList":
-        updateUserList(message.users);
+        if (message.room_name === currentRoom) {
+          updateUserList(message.users);
+        }
         break;
 
       case "RoomCreated":
@@ -146,36 +423,122 @@ document.addEventListener("DOMContentLoaded", () => {
         break;
 
       case "JoinedRoom":
-        currentRoom = message.room_name;
-        currentRoomHeader.textContent = currentRoom;
-
-        // ルームリストのアクティブ項目を更新
-        const roomItems = roomList.querySelectorAll("li");
-        roomItems.forEach((item) => {
-          if (item.dataset.room === currentRoom) {
-            item.classList.add("active");
-          } else {
-            item.classList.remove("active");
-          }
-        });
-
-        // メッセージをクリア
-        messageContainer.innerHTML = "";
+        // 参加したルームを表示する (他のルームには参加したまま)
+        pendingJoin = null;
+        roomMessages[message.room_name] = [];
+        addSystemMessage(
+          `「${message.room_name}」に参加しました`,
+          message.room_name
+        );
+        switchRoom(message.room_name);
+        break;
 
-        addSystemMessage(`「${currentRoom}」に参加しました`);
+      case "LeftRoom":
+        delete roomMessages[message.room_name];
+        delete unreadRooms[message.room_name];
+        delete readSeqs[message.room_name];
+        delete readBy[message.room_name];
 
-        // ユーザー一覧を更新
-        sendMessage({
-          type: "ListUsers",
-        });
+        // 表示中のルームから退出した場合は残りのルームに切り替える
+        if (message.room_name === currentRoom) {
+          const remaining = Object.keys(roomMessages);
+          if (remaining.length > 0) {
+            switchRoom(remaining[0]);
+          } else {
+            setCurrentRoom(null);
+            messageContainer.innerHTML = "";
+            userList.innerHTML = "";
+          }
+        }
+        addSystemMessage(`「${message.room_name}」から退出しました`);
+        updateRoomList(knownRooms);
         break;
 
       case "Error":
+        if (message.code === "invalid_session") {
+          // 猶予が過ぎてセッションが削除された
+          alert(
+            "接続が切れている間にセッションが終了しました。ログインし直してください"
+          );
+          window.location.reload();
+          break;
+        }
+        if (!currentUserId) {
+          // ログイン前 (ハンドシェイク失敗や使えないユーザー名・パスワード) はチャット画面が表示されていない
+          alert(`エラー: ${LOGIN_ERRORS[message.code] || message.message}`);
+          socket.close();
+          break;
+        }
+        if (
+          pendingJoin &&
+          (message.message === "Password required" ||
+            message.message === "Wrong password")
+        ) {
+          const password = prompt(
+            `「${pendingJoin}」のパスワードを入力してください`
+          );
+          if (password) {
+            joinRoom(pendingJoin, password);
+            break;
+          }
+        }
+        pendingJoin = null;
         addSystemMessage(`エラー: ${message.message}`);
         break;
     }
   }
 
+  // 表示するルームを切り替える (参加中のルームのみ)
+  function switchRoom(roomName) {
+    setReplyTo(null);
+    setCurrentRoom(roomName);
+    unreadRooms[roomName] = 0;
+    renderRoom();
+    markRoomRead(roomName);
+    updateRoomList(knownRooms);
+    refreshUserList();
+  }
+
+  // 表示中のルームのメッセージを描き直す
+  function renderRoom() {
+    messageContainer.innerHTML = "";
+    (roomMessages[currentRoom] || []).forEach((entry) => {
+      messageContainer.appendChild(createEntryElement(entry));
+    });
+    scrollToBottom();
+  }
+
+  function refreshUserList() {
+    if (currentRoom) {
+      sendMessage({
+        type: "ListUsers",
+        room_name: currentRoom,
+      });
+    }
+  }
+
+  // 表示中のルーム (null はどのルームも表示していない状態)
+  function setCurrentRoom(roomName) {
+    currentRoom = roomName;
+    renderTypingIndicator();
+    renderReadReceipt();
+    // DM ペインの表示中はヘッダーを変えない
+    if (dmPeer !== null) return;
+
+    currentRoomHeader.textContent = roomName ?? "ルーム未参加";
+    leaveRoomButton.disabled = roomName === null;
+
+    // ルームリストのアクティブ項目を更新
+    const roomItems = roomList.querySelectorAll("li");
+    roomItems.forEach((item) => {
+      if (item.dataset.room === currentRoom) {
+        item.classList.add("active");
+      } else {
+        item.classList.remove("active");
+      }
+    });
+  }
+
   // メッセージ送信ボタンクリック
   sendButton.addEventListener("click", () => {
     sendChatMessage();
@@ -188,19 +551,145 @@ document.addEventListener("DOMContentLoaded", () => {
     }
   });
 
-  // チャットメッセージ送信
+  // ルームで入力している間は Typing を送る (コマンドと DM では送らない)
+  messageInput.addEventListener("input", () => {
+    const content = messageInput.value;
+    const now = Date.now();
+    if (
+      dmPeer === null &&
+      currentRoom &&
+      content.trim() &&
+      !content.startsWith("/") &&
+      now - lastTypingSentAt >= TYPING_INTERVAL_MS
+    ) {
+      lastTypingSentAt = now;
+      sendMessage({ type: "Typing", room_name: currentRoom });
+    }
+  });
+
+  // 表示中のルームの最新のメッセージまでを既読にする (同じ位置は送り直さない)
+  function markRoomRead(roomName) {
+    if (roomName !== currentRoom || dmPeer !== null) return;
+    const last = lastRoomMessage(roomName);
+    if (!last || (readSeqs[roomName] ?? -1) >= last.seq) return;
+    readSeqs[roomName] = last.seq;
+    sendMessage({ type: "MarkRead", room_name: roomName, seq: last.seq });
+  }
+
+  function lastRoomMessage(roomName) {
+    return (roomMessages[roomName] || [])
+      .filter((e) => e.seq !== undefined)
+      .at(-1);
+  }
+
+  // 表示中のルームの最新のメッセージが自分のものなら、読んだメンバーを表示する
+  function renderReadReceipt() {
+    const last = dmPeer === null ? lastRoomMessage(currentRoom) : null;
+    const readers =
+      last && last.sender === currentUsername
+        ? Object.entries(readBy[currentRoom] || {})
+            .filter(
+              ([name, seq]) => name !== currentUsername && seq >= last.seq,
+            )
+            .map(([name]) => name)
+        : [];
+    readReceipt.textContent =
+      readers.length > 0 ? `既読: ${readers.join("、")}` : "";
+  }
+
+  // 表示中のルームで入力中のユーザー
+  function renderTypingIndicator() {
+    const users = dmPeer === null ? [...(typingUsers[currentRoom] || [])] : [];
+    typingIndicator.textContent =
+      users.length > 0 ? `${users.join("、")} が入力中…` : "";
+  }
+
+  // 招待とモデレーション用のコマンド (/invite /mod /kick /ban <名前> [秒数] /unban /mute /unmute)
+  const MODERATION_COMMANDS = {
+    "/invite": "Invite",
+    "/mod": "GrantModerator",
+    "/kick": "Kick",
+    "/ban": "Ban",
+    "/unban": "Unban",
+    "/mute": "Mute",
+    "/unmute": "Unmute",
+  };
+
+  // 表示中のルーム宛てのモデレーション操作に変換する (該当しなければ null)
+  function parseModerationCommand(content) {
+    const [command, username, seconds] = content.split(/\s+/);
+    const type = MODERATION_COMMANDS[command];
+    if (!type || !username || !currentRoom) return null;
+
+    const message = { type, room_name: currentRoom, username };
+    if (type === "Ban") {
+      message.duration_secs = seconds ? Number(seconds) : null;
+    }
+    return message;
+  }
+
+  // チャットメッセージ送信 (/nick <名前> で名前を変更する)
   function sendChatMessage() {
     const content = messageInput.value.trim();
-    if (content && socket) {
+    const moderation = dmPeer === null ? parseModerationCommand(content) : null;
+    if (moderation && socket) {
+      sendMessage(moderation);
+      messageInput.value = "";
+    } else if (content.startsWith("/nick ") && socket) {
       sendMessage({
-        type: "SendMessage",
+        type: "ChangeNick",
+        username: content.slice("/nick ".length).trim(),
+      });
+      messageInput.value = "";
+    } else if (content && socket && dmPeer) {
+      sendMessage({
+        type: "DirectMessage",
+        to: dmPeer,
         content: content,
       });
 
+      // 自分の送信分はサーバーから返らないので手元で追加する
+      addDirectMessage(dmPeer, currentUsername, content);
+      messageInput.value = "";
+    } else if (content && socket && currentRoom) {
+      const message = {
+        type: "SendMessage",
+        room_name: currentRoom,
+        content: content,
+      };
+      if (replyTo) {
+        message.reply_to = replyTo.id;
+      }
+      sendMessage(message);
+
+      // 送信するとサーバー側で入力中が解除されるので、次の入力ですぐに送り直す
+      lastTypingSentAt = 0;
+      setReplyTo(null);
       messageInput.value = "";
     }
   }
 
+  // 返信先を設定する (null で取り消す)
+  function setReplyTo(entry) {
+    replyTo = entry;
+    if (entry) {
+      replyPreview.textContent = `↪ ${describeMessage(entry)}`;
+      replyBar.style.display = "flex";
+      messageInput.focus();
+    } else {
+      replyBar.style.display = "none";
+    }
+  }
+
+  cancelReplyButton.addEventListener("click", () => setReplyTo(null));
+
+  // 引用や一覧に使う 1 行の表示
+  function describeMessage(message) {
+    return message.deleted
+      ? `${message.sender}: (削除されました)`
+      : `${message.sender}: ${message.content}`;
+  }
+
   // WebSocketを通じてメッセージを送信する汎用関数
   function sendMessage(message) {
     if (socket && socket.readyState === WebSocket.OPEN) {
@@ -208,10 +697,184 @@ document.addEventListener("DOMContentLoaded", () => {
     }
   }
 
-  // チャットメッセージをUIに追加
-  function addChatMessage(sender, content, roomName) {
-    if (roomName !== currentRoom) return;
+  // チャットメッセージをルームの履歴に追加
+  function addChatMessage(message) {
+    addRoomEntry(message.room_name, {
+      id: message.id,
+      seq: message.seq,
+      sender: message.sender,
+      content: message.content,
+      replyTo: message.reply_to ?? null,
+      reactions: [],
+    });
+  }
+
+  // 自分が付けたリアクションなら外し、そうでなければ付ける
+  function toggleReaction(entry, emoji) {
+    const reaction = entry.reactions.find((r) => r.emoji === emoji);
+    const reacted = reaction && reaction.users.includes(currentUsername);
+    sendMessage({
+      type: reacted ? "Unreact" : "React",
+      room_name: currentRoom,
+      message_id: entry.id,
+      emoji,
+    });
+  }
+
+  // 絵文字ごとの件数。押すと自分のリアクションを付け外しする
+  function createReactionsElement(entry) {
+    const reactionsElement = document.createElement("div");
+    reactionsElement.className = "reactions";
+    entry.reactions.forEach((reaction) => {
+      const button = document.createElement("button");
+      button.textContent = `${reaction.emoji} ${reaction.count}`;
+      button.title = reaction.users.join(", ");
+      if (reaction.users.includes(currentUsername)) {
+        button.classList.add("mine");
+      }
+      button.addEventListener("click", () =>
+        toggleReaction(entry, reaction.emoji)
+      );
+      reactionsElement.appendChild(button);
+    });
+    return reactionsElement;
+  }
+
+  // 編集・削除されたメッセージを履歴で書き換え、表示中ならその要素だけ差し替える
+  function updateRoomMessage(roomName, messageId, update) {
+    const entry = (roomMessages[roomName] || []).find((e) => e.id === messageId);
+    if (!entry) return;
+    update(entry);
+
+    const element = messageContainer.querySelector(
+      `[data-id="${CSS.escape(messageId)}"]`
+    );
+    if (element) {
+      element.replaceWith(createEntryElement(entry));
+    }
+  }
+
+  // 表示中のルームなら画面にも追加し、それ以外は未読に数える
+  function addRoomEntry(roomName, entry) {
+    if (!(roomName in roomMessages)) return;
+    roomMessages[roomName].push(entry);
+
+    if (roomName === currentRoom && dmPeer === null) {
+      messageContainer.appendChild(createEntryElement(entry));
+      scrollToBottom();
+      markRoomRead(roomName);
+      renderReadReceipt();
+    } else if (entry.sender !== undefined) {
+      unreadRooms[roomName] = (unreadRooms[roomName] || 0) + 1;
+      updateRoomList(knownRooms);
+    }
+  }
+
+  function createEntryElement(entry) {
+    if (entry.system !== undefined) {
+      return createSystemElement(entry.system);
+    }
+    return createRoomMessageElement(entry);
+  }
+
+  // ルームのメッセージ。返信には返信先を引用し、自分のメッセージには編集・削除のボタンを付ける
+  function createRoomMessageElement(entry) {
+    const messageElement = createMessageElement(
+      entry.sender,
+      entry.deleted ? "このメッセージは削除されました" : entry.content
+    );
+    messageElement.dataset.id = entry.id;
+
+    if (entry.replyTo) {
+      // クリックするとスレッド全体を取得する
+      const parent = (roomMessages[currentRoom] || []).find(
+        (e) => e.id === entry.replyTo
+      );
+      const quote = document.createElement("div");
+      quote.className = "reply-quote";
+      quote.textContent = parent
+        ? `↪ ${describeMessage(parent)}`
+        : "↪ 以前のメッセージへの返信";
+      quote.addEventListener("click", () => {
+        sendMessage({
+          type: "GetThread",
+          room_name: currentRoom,
+          message_id: entry.replyTo,
+        });
+      });
+      messageElement.insertBefore(quote, messageElement.firstChild);
+    }
+
+    if (entry.deleted) {
+      messageElement.classList.add("deleted");
+      return messageElement;
+    }
+
+    if (entry.edited) {
+      const editedElement = document.createElement("div");
+      editedElement.className = "edited";
+      editedElement.textContent = "(編集済み)";
+      messageElement.appendChild(editedElement);
+    }
+
+    if (entry.reactions.length > 0) {
+      messageElement.appendChild(createReactionsElement(entry));
+    }
+
+    const actions = document.createElement("div");
+    actions.className = "message-actions";
+
+    const replyButton = document.createElement("button");
+    replyButton.textContent = "返信";
+    replyButton.addEventListener("click", () => setReplyTo(entry));
+    actions.appendChild(replyButton);
+
+    const reactButton = document.createElement("button");
+    reactButton.textContent = "リアクション";
+    reactButton.addEventListener("click", () => {
+      const emoji = prompt("リアクションする絵文字", "👍");
+      if (emoji && emoji.trim()) {
+        toggleReaction(entry, emoji.trim());
+      }
+    });
+    actions.appendChild(reactButton);
+
+    if (entry.sender === currentUsername) {
+
+      const editButton = document.createElement("button");
+      editButton.textContent = "編集";
+      editButton.addEventListener("click", () => {
+        const content = prompt("メッセージを編集", entry.content);
+        if (content && content.trim() && content.trim() !== entry.content) {
+          sendMessage({
+            type: "EditMessage",
+            room_name: currentRoom,
+            message_id: entry.id,
+            content: content.trim(),
+          });
+        }
+      });
+
+      const deleteButton = document.createElement("button");
+      deleteButton.textContent = "削除";
+      deleteButton.addEventListener("click", () => {
+        if (confirm("このメッセージを削除しますか？")) {
+          sendMessage({
+            type: "DeleteMessage",
+            room_name: currentRoom,
+            message_id: entry.id,
+          });
+        }
+      });
+
+      actions.appendChild(editButton);
+      actions.appendChild(deleteButton);
+    }
+    messageElement.appendChild(actions);
+    return messageElement;
+  }
 
+  function createMessageElement(sender, content) {
     const messageElement = document.createElement("div");
     messageElement.className = `message ${
       sender === currentUsername ? "sent" : "received"
@@ -228,18 +891,123 @@ document.addEventListener("DOMContentLoaded", () => {
     messageElement.appendChild(usernameElement);
     messageElement.appendChild(contentElement);
 
-    messageContainer.appendChild(messageElement);
+    return messageElement;
+  }
+
+  // DM を履歴に追加し、表示中の相手ならペインにも追加する
+  function addDirectMessage(peer, sender, content) {
+    if (!directMessages[peer]) {
+      directMessages[peer] = [];
+    }
+    directMessages[peer].push({ sender, content });
+
+    if (peer === dmPeer) {
+      dmContainer.appendChild(createMessageElement(sender, content));
+      dmContainer.scrollTop = dmContainer.scrollHeight;
+    } else {
+      unreadDirect[peer] = (unreadDirect[peer] || 0) + 1;
+    }
+    updateDmList();
+  }
+
+  // 名前の変更を自分の表示と DM の相手に反映する
+  function renameUser(oldUsername, newUsername) {
+    if (oldUsername === currentUsername) {
+      currentUsername = newUsername;
+    }
+
+    if (directMessages[oldUsername]) {
+      directMessages[newUsername] = directMessages[oldUsername];
+      unreadDirect[newUsername] = unreadDirect[oldUsername];
+      delete directMessages[oldUsername];
+      delete unreadDirect[oldUsername];
+      if (dmPeer === oldUsername) {
+        dmPeer = newUsername;
+        currentRoomHeader.textContent = `@${newUsername}`;
+      }
+      updateDmList();
+    }
+  }
+
+  // DM ペインを開く (null でルームの表示に戻る)
+  function openDirectMessage(peer) {
+    dmPeer = peer;
+    setReplyTo(null);
+    renderTypingIndicator();
+    renderReadReceipt();
+
+    if (peer === null) {
+      dmContainer.style.display = "none";
+      messageContainer.style.display = "block";
+      if (currentRoom) {
+        switchRoom(currentRoom);
+      } else {
+        setCurrentRoom(null);
+      }
+      return;
+    }
+
+    if (!directMessages[peer]) {
+      directMessages[peer] = [];
+    }
+    unreadDirect[peer] = 0;
+
+    dmContainer.innerHTML = "";
+    directMessages[peer].forEach((m) => {
+      dmContainer.appendChild(createMessageElement(m.sender, m.content));
+    });
+    messageContainer.style.display = "none";
+    dmContainer.style.display = "block";
+    dmContainer.scrollTop = dmContainer.scrollHeight;
+
+    currentRoomHeader.textContent = `@${peer}`;
+    leaveRoomButton.disabled = true;
+    roomList
+      .querySelectorAll("li")
+      .forEach((item) => item.classList.remove("active"));
+    updateDmList();
+  }
+
+  // DM の相手一覧を更新
+  function updateDmList() {
+    dmList.innerHTML = "";
+
+    Object.keys(directMessages).forEach((peer) => {
+      const peerElement = document.createElement("li");
+      peerElement.textContent = peer;
+
+      if (peer === dmPeer) {
+        peerElement.classList.add("active");
+      }
+
+      if (unreadDirect[peer]) {
+        const badge = document.createElement("span");
+        badge.className = "unread";
+        badge.textContent = unreadDirect[peer];
+        peerElement.appendChild(badge);
+      }
+
+      peerElement.addEventListener("click", () => openDirectMessage(peer));
+      dmList.appendChild(peerElement);
+    });
+  }
+
+  // システムメッセージをUIに追加 (ルームを省略すると表示中のルーム)
+  function addSystemMessage(text, roomName = currentRoom) {
+    if (roomName in roomMessages) {
+      addRoomEntry(roomName, { system: text });
+      return;
+    }
+
+    messageContainer.appendChild(createSystemElement(text));
     scrollToBottom();
   }
 
-  // システムメッセージをUIに追加
-  function addSystemMessage(text) {
+  function createSystemElement(text) {
     const messageElement = document.createElement("div");
     messageElement.className = "system-message";
     messageElement.textContent = text;
-
-    messageContainer.appendChild(messageElement);
-    scrollToBottom();
+    return messageElement;
   }
 
   // メッセージコンテナを最下部にスクロール
@@ -247,8 +1015,9 @@ document.addEventListener("DOMContentLoaded", () => {
     messageContainer.scrollTop = messageContainer.scrollHeight;
   }
 
-  // ルームリストを更新
+  // ルームリストを更新 (参加中のルームは太字、クリックで表示を切り替える)
   function updateRoomList(rooms) {
+    knownRooms = rooms;
     roomList.innerHTML = "";
 
     rooms.forEach((room) => {
@@ -256,12 +1025,30 @@ document.addEventListener("DOMContentLoaded", () => {
       roomElement.textContent = room;
       roomElement.dataset.room = room;
 
-      if (room === currentRoom) {
+      if (room === currentRoom && dmPeer === null) {
         roomElement.classList.add("active");
       }
 
+      if (room in roomMessages) {
+        roomElement.classList.add("joined");
+      }
+
+      if (unreadRooms[room]) {
+        const badge = document.createElement("span");
+        badge.className = "unread";
+        badge.textContent = unreadRooms[room];
+        roomElement.appendChild(badge);
+      }
+
       roomElement.addEventListener("click", () => {
-        if (room !== currentRoom) {
+        if (dmPeer !== null) {
+          openDirectMessage(null);
+        }
+        if (room in roomMessages) {
+          if (room !== currentRoom) {
+            switchRoom(room);
+          }
+        } else {
           joinRoom(room);
         }
       });
@@ -280,6 +1067,11 @@ document.addEventListener("DOMContentLoaded", () => {
 
       if (username === currentUsername) {
         userElement.style.fontWeight = "bold";
+      } else {
+        // クリックでその相手との DM を開く
+        userElement.addEventListener("click", () =>
+          openDirectMessage(username)
+        );
       }
 
       userList.appendChild(userElement);
@@ -287,20 +1079,42 @@ document.addEventListener("DOMContentLoaded", () => {
   }
 
   // ルームに参加
-  function joinRoom(roomName) {
-    sendMessage({
-      type: "JoinRoom",
-      room_name: roomName,
-    });
+  function joinRoom(roomName, password = null) {
+    pendingJoin = roomName;
+    const message = { type: "JoinRoom", room_name: roomName };
+    if (password) {
+      message.password = password;
+    }
+    sendMessage(message);
   }
 
+  // ルームから退出
+  leaveRoomButton.addEventListener("click", () => {
+    if (currentRoom) {
+      sendMessage({
+        type: "LeaveRoom",
+        room_name: currentRoom,
+      });
+    }
+  });
+
   // 新規ルーム作成モーダル表示
   createRoomButton.addEventListener("click", () => {
     createRoomModal.style.display = "flex";
     roomNameInput.value = "";
+    roomVisibilityInput.value = "public";
+    roomPasswordInput.value = "";
+    roomPasswordInput.style.display = "none";
+    roomHiddenInput.checked = false;
     roomNameInput.focus();
   });
 
+  // パスワード付きを選んだときだけパスワード欄を表示する
+  roomVisibilityInput.addEventListener("change", () => {
+    roomPasswordInput.style.display =
+      roomVisibilityInput.value === "password" ? "block" : "none";
+  });
+
   // モーダルのキャンセルボタン
   cancelCreateRoom.addEventListener("click", () => {
     createRoomModal.style.display = "none";
@@ -313,29 +1127,30 @@ document.addEventListener("DOMContentLoaded", () => {
     }
   });
 
-  // 新規ルーム作成
-  confirmCreateRoom.addEventListener("click", () => {
+  // 新規ルーム作成 (公開範囲とパスワード、一覧に出すかを指定する)
+  function createRoom() {
     const roomName = roomNameInput.value.trim();
-    if (roomName) {
-      sendMessage({
-        type: "CreateRoom",
-        room_name: roomName,
-      });
-      createRoomModal.style.display = "none";
+    if (!roomName) return;
+
+    const message = {
+      type: "CreateRoom",
+      room_name: roomName,
+      visibility: roomVisibilityInput.value,
+      hidden: roomHiddenInput.checked,
+    };
+    if (roomVisibilityInput.value === "password") {
+      message.password = roomPasswordInput.value;
     }
-  });
+    sendMessage(message);
+    createRoomModal.style.display = "none";
+  }
+
+  confirmCreateRoom.addEventListener("click", createRoom);
 
   // Enter キーでルーム作成
   roomNameInput.addEventListener("keypress", (e) => {
     if (e.key === "Enter") {
-      const roomName = roomNameInput.value.trim();
-      if (roomName) {
-        sendMessage({
-          type: "CreateRoom",
-          room_name: roomName,
-        });
-        createRoomModal.style.display = "none";
-      }
+      createRoom();
     }
   });
 
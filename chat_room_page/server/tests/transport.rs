@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use chat_core::server::ChatServer;
+use chat_core::transport::Transport;
+use chat_core::transport::tcp::TcpTransport;
+use chat_protocol::{ClientMessage, ServerMessage, PROTOCOL_VERSION};
+use chat_room_page_server::transport::ws_route;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use warp::test::WsClient;
+
+async fn ws_send(client: &mut WsClient, message: ClientMessage) {
+    client.send_text(serde_json::to_string(&message).unwrap()).await;
+}
+
+async fn ws_recv(client: &mut WsClient) -> ServerMessage {
+    let message = timeout(Duration::from_secs(2), client.recv())
+        .await
+        .expect("timed out waiting for server message")
+        .unwrap();
+    serde_json::from_str(message.to_str().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn websocket_clients_complete_the_handshake() {
+    let server = ChatServer::new();
+    let mut client = warp::test::ws().path("/ws").handshake(ws_route(server)).await.unwrap();
+
+    ws_send(&mut client, ClientMessage::Hello { protocol_version: PROTOCOL_VERSION, capabilities: vec![] }).await;
+    assert!(matches!(ws_recv(&mut client).await, ServerMessage::Hello { .. }));
+
+    ws_send(&mut client, ClientMessage::Login { username: "alice".to_string(), password: None }).await;
+    assert!(matches!(ws_recv(&mut client).await, ServerMessage::Welcome { .. }));
+}
+
+#[tokio::test]
+async fn tcp_and_websocket_clients_share_rooms() {
+    let server = ChatServer::new();
+
+    // 同じ ChatServer を TCP トランスポートにも渡す
+    let tcp = TcpTransport::bind("127.0.0.1:0").await.unwrap();
+    let addr = tcp.local_addr().unwrap();
+    let tcp_server = server.clone();
+    tokio::spawn(async move {
+        let _ = tcp.serve(tcp_server).await;
+    });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let login = serde_json::to_string(&ClientMessage::Login { username: "bob".to_string(), password: None }).unwrap();
+    writer.write_all(format!("{}\n", login).as_bytes()).await.unwrap();
+
+    // bob の History が届けばログイン完了
+    loop {
+        let line = timeout(Duration::from_secs(2), lines.next_line()).await.unwrap().unwrap().unwrap();
+        if matches!(serde_json::from_str(&line).unwrap(), ServerMessage::History { .. }) {
+            break;
+        }
+    }
+
+    let mut alice = warp::test::ws().path("/ws").handshake(ws_route(server)).await.unwrap();
+    ws_send(&mut alice, ClientMessage::Login { username: "alice".to_string(), password: None }).await;
+    ws_send(&mut alice, ClientMessage::SendMessage { room_name: "general".to_string(), content: "hello from the browser".to_string(), reply_to: None }).await;
+
+    loop {
+        let line = timeout(Duration::from_secs(2), lines.next_line()).await.unwrap().unwrap().unwrap();
+        if let ServerMessage::NewMessage { sender, content, room_name, .. } = serde_json::from_str(&line).unwrap() {
+            assert_eq!(sender, "alice");
+            assert_eq!(content, "hello from the browser");
+            assert_eq!(room_name, "general");
+            break;
+        }
+    }
+}